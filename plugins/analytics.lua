@@ -1,3 +1,7 @@
+--- @capability shell
+--- @capability fs
+--- @capability send
+
 local serial = require("scope").serial
 local log = require("scope").log
 local re = require("scope").re
@@ -1,7 +1,11 @@
+--- @capability send
+
 local log = require("scope").log
 local serial = require("scope").serial
 
-local M = {}
+local M = {
+  required_api = "1.0",
+}
 
 --- Test CREG comamnd
 --- @param apn string? The APN to use on CREG command
@@ -12,14 +16,14 @@ function M.test_creg(apn)
   local err, rsp = serial.recv({timeout_ms = 200})
 
   if err then
-    log.err("[ERR] Test CREG Timeout")
+    log.error("[ERR] Test CREG Timeout")
     return
   end
 
   if rsp == "OK\r\n" then
-    log.inf("[ OK] Test CREG Success")
+    log.info("[ OK] Test CREG Success")
   else
-    log.err("[ERR] Test GREG Fail")
+    log.error("[ERR] Test GREG Fail")
   end
 end
 
@@ -1,3 +1,5 @@
+--- @capability send
+
 local serial = require('scope').serial
 
 local M = {}
@@ -1,14 +1,18 @@
+--- @capability shell
+
 local log = require("scope").log
 local serial = require("scope").serial
 local shell = require("shell")
 
-local M = {}
+local M = {
+  required_api = "1.0",
+}
 
 function M.on_load()
   M.shell = shell.new()
 
   if not M.shell:exist("idf.py") then
-    log.err("There isn't a command called idf.py. Export it before enter in Scope")
+    log.error("There isn't a command called idf.py. Export it before enter in Scope")
     return false
   end
 
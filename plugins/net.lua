@@ -0,0 +1,39 @@
+local M = {}
+
+local Connection = {}
+Connection.__index = Connection
+
+function M.tcp_connect(host, port, opts)
+    local res = coroutine.yield({ ":net.tcp_connect", host, port, opts })
+    if res.err then
+        return nil, res.err
+    end
+    return setmetatable({ id = res.id }, Connection)
+end
+
+function Connection:send(data)
+    local res = coroutine.yield({ ":net.tcp_send", self.id, data })
+    return res.err
+end
+
+function Connection:recv(opts)
+    local res = coroutine.yield({ ":net.tcp_recv", self.id, opts })
+    return res.data, res.err
+end
+
+function Connection:close()
+    local res = coroutine.yield({ ":net.tcp_close", self.id })
+    return res.err
+end
+
+function M.udp_send(host, port, data)
+    local res = coroutine.yield({ ":net.udp_send", host, port, data })
+    return res.err
+end
+
+function M.http_post(url, body, opts)
+    local res = coroutine.yield({ ":net.http_post", url, body, opts })
+    return res.status, res.body, res.err
+end
+
+return M
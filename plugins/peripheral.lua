@@ -1,3 +1,5 @@
+--- @capability send
+
 local log = require("scope").plugin
 local serial = require("scope").plugin
 local sys = require("scope").sys
@@ -1,3 +1,5 @@
+local codec = require("codec")
+
 local status = {
     a = 0,
     b = 0,
@@ -9,20 +11,8 @@ local status = {
 }
 status.__index = status
 
-local function decode_float(float, pos)
-    local f = float[pos] | (float[pos + 1] << 8) | (float[pos + 2] << 16) | (float[pos + 3] << 24)
-
-    local sign = (f >> 31) & 0x1
-    local exponent = (f >> 23) & 0xFF
-    local mantissa = f & 0x7FFFFF
-
-    if exponent == 0 then
-        return (-1) ^ sign * (mantissa / (2 ^ 23)) * (2 ^ -126)
-    elseif exponent == 255 then
-        return mantissa == 0 and ((-1) ^ sign * math.huge) or (0 / 0)
-    else
-        return (-1) ^ sign * (1 + mantissa / (2 ^ 23)) * (2 ^ (exponent - 127))
-    end
+local function decode_float(bytes, pos)
+    return (codec.unpack("<f", bytes, pos))
 end
 
 local function decode_quaternion(bytes, pos)
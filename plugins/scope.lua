@@ -6,8 +6,22 @@ local M = {
     sys = {},
     re = {},
     rtt = {},
+    store = { shared = {} },
+    bus = {},
+    buffer = {},
 }
 
+--- Version of the plugin API provided by Scope, like "1.2", followed by its
+--- major and minor numbers.
+function M.version()
+    return require("scope.api").version()
+end
+
+--- Whether Scope provides a feature, like "rtt.read", or a namespace, like "store".
+function M.has(feature)
+    return require("scope.api").has(feature)
+end
+
 function M.fmt.to_str(val)
     if type(val) == "table" then
         local bytearr = {}
@@ -88,6 +102,63 @@ function M.rtt.read(opts)
     return res.err, res.data
 end
 
+function M.store.get(key)
+    local res = coroutine.yield({ ":store.get", key })
+    return res.value, res.err
+end
+
+function M.store.set(key, value)
+    local res = coroutine.yield({ ":store.set", key, value })
+    return res.err
+end
+
+function M.store.keys()
+    local res = coroutine.yield({ ":store.keys" })
+    return res.keys, res.err
+end
+
+function M.store.shared.get(key)
+    local res = coroutine.yield({ ":store.shared.get", key })
+    return res.value, res.err
+end
+
+function M.store.shared.set(key, value)
+    local res = coroutine.yield({ ":store.shared.set", key, value })
+    return res.err
+end
+
+function M.store.shared.keys()
+    local res = coroutine.yield({ ":store.shared.keys" })
+    return res.keys, res.err
+end
+
+function M.bus.publish(topic, value)
+    coroutine.yield({ ":bus.publish", topic, value })
+end
+
+function M.bus.subscribe(topic)
+    coroutine.yield({ ":bus.subscribe", topic })
+end
+
+function M.bus.unsubscribe(topic)
+    coroutine.yield({ ":bus.unsubscribe", topic })
+end
+
+function M.buffer.len()
+    local res = coroutine.yield({ ":buffer.len" })
+    return res.len
+end
+
+function M.buffer.lines(from, to)
+    local res = coroutine.yield({ ":buffer.lines", from, to })
+    return res.lines
+end
+
+function M.buffer.search(pattern, from, to, max)
+    local res = coroutine.yield({ ":buffer.search", pattern, from, to, max })
+    return res.lines, res.err
+end
+
 function M.sys.os_name()
     if os.getenv("OS") == "Windows_NT" then
         return "windows"
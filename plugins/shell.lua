@@ -10,4 +10,40 @@ function M.exist(program)
     return res.exist
 end
 
+local Process = {}
+Process.__index = Process
+
+function M.spawn(cmd, opts)
+    local res = coroutine.yield({ ":shell.spawn", cmd, opts })
+    if res.err then
+        return nil, res.err
+    end
+    return setmetatable({ id = res.id }, Process)
+end
+
+function Process:read_line(opts)
+    local res = coroutine.yield({ ":shell.read_line", self.id, opts })
+    return res.line, res.err
+end
+
+function Process:write(data)
+    local res = coroutine.yield({ ":shell.write", self.id, data })
+    return res.err
+end
+
+function Process:close_stdin()
+    local res = coroutine.yield({ ":shell.close_stdin", self.id })
+    return res.err
+end
+
+function Process:kill()
+    local res = coroutine.yield({ ":shell.kill", self.id })
+    return res.err
+end
+
+function Process:wait()
+    local res = coroutine.yield({ ":shell.wait", self.id })
+    return res.status, res.err
+end
+
 return M
@@ -1,3 +1,5 @@
+--- @capability send
+
 local log = require("scope").log
 local fmt = require("scope").fmt
 local serial = require("scope").serial
@@ -1,14 +1,18 @@
+--- @capability shell
+
 local log = require("scope").log
 local serial = require("scope").serial
 local shell = require("shell")
 
-local M = {}
+local M = {
+  required_api = "1.0",
+}
 
 function M.on_load()
   M.shell = shell.new()
 
   if not M.shell:exist("west") then
-    log.err("west not found. Export it before enter in Scope")
+    log.error("west not found. Export it before enter in Scope")
     return false
   end
 
@@ -53,7 +53,6 @@ pub type GraphicsTask = Task<(), GraphicsCommand>;
 
 pub struct GraphicsConfig {
     pub storage_base_filename: String,
-    pub capacity: usize,
     pub latency: u64,
 }
 
@@ -68,7 +67,7 @@ pub struct GraphicsConnections {
     typewriter: TypeWriter,
     recorder: Recorder,
     latency: u64,
-    buffer: Buffer,
+    buffer: Arc<RwLock<Buffer>>,
     screen: Screen,
     clipboard: Option<Clipboard>,
 }
@@ -364,24 +363,52 @@ impl GraphicsTask {
         frame: &mut Frame,
         command_bar_y: u16,
     ) {
-        let (autocomplete_list, pattern, input_mode, cursor, command) = {
+        let (tag_list, command_completion, input_mode, cursor, command) = {
             let inputs_shared = inputs_shared
                 .read()
                 .expect("Cannot get inputs lock for read");
 
             (
-                inputs_shared.tag_list.autocomplete_list(),
-                inputs_shared.tag_list.pattern(),
+                inputs_shared.tag_list.clone(),
+                inputs_shared.command_completion.clone(),
                 inputs_shared.mode,
                 inputs_shared.cursor,
                 inputs_shared.command_line.clone(),
             )
         };
 
-        if autocomplete_list.is_empty() || pattern.is_empty() || input_mode != InputMode::Normal {
+        if input_mode != InputMode::Normal {
             return;
         }
 
+        let (autocomplete_list, pattern, skip_chars, anchor) =
+            if !tag_list.autocomplete_list().is_empty() && !tag_list.pattern().is_empty() {
+                let pattern = tag_list.pattern();
+                let skip_chars = pattern.chars().count().saturating_sub(1);
+                let latest_at = command
+                    .chars()
+                    .take(cursor)
+                    .enumerate()
+                    .filter(|(_, c)| *c == '@')
+                    .map(|(i, _)| i)
+                    .last()
+                    .unwrap_or(0);
+
+                (tag_list.autocomplete_list(), pattern, skip_chars, latest_at)
+            } else if !command_completion.candidates().is_empty() {
+                let pattern = command_completion.pattern();
+                let skip_chars = pattern.chars().count();
+
+                (
+                    command_completion.candidates(),
+                    pattern,
+                    skip_chars,
+                    command_completion.start(),
+                )
+            } else {
+                return;
+            };
+
         let max_entries = min(frame.size().height as usize / 2, autocomplete_list.len());
         let mut entries = autocomplete_list[..max_entries].to_vec();
         if entries.len() < autocomplete_list.len() {
@@ -398,15 +425,7 @@ impl GraphicsTask {
                 .width
                 .saturating_sub(area_size.0)
                 .saturating_sub(2);
-        let latest_at = command
-            .chars()
-            .take(cursor)
-            .enumerate()
-            .filter(|(_, c)| *c == '@')
-            .map(|(i, _)| i)
-            .last()
-            .unwrap_or(0);
-        let area_x = latest_at.clamp(2, max_x as usize);
+        let area_x = anchor.clamp(2, max_x as usize);
         let area_y = command_bar_y.saturating_sub(area_size.1);
         let area = Rect::new(area_x as u16, area_y, area_size.0, area_size.1);
 
@@ -429,7 +448,6 @@ impl GraphicsTask {
                         if x.as_str() == "..." {
                             x.to_string()
                         } else {
-                            let skip_chars = pattern.chars().count().saturating_sub(1);
                             x.as_str().chars().skip(skip_chars).collect::<String>()
                         },
                         Style::default().fg(Color::DarkGray),
@@ -458,6 +476,8 @@ impl GraphicsTask {
 
         let content = private
             .buffer
+            .read()
+            .expect("Cannot get buffer lock for read")
             .get_selection_content(selection, private.screen.decoder());
         if content.is_empty() {
             return Ok(());
@@ -576,7 +596,11 @@ impl GraphicsTask {
                     }
                     GraphicsCommand::Clear => {
                         private.screen.clear();
-                        private.buffer.clear();
+                        private
+                            .buffer
+                            .write()
+                            .expect("Cannot get buffer lock for write")
+                            .clear();
                     }
                     GraphicsCommand::ScrollLeft => {
                         let max_main_axis = Self::max_main_axis(&private);
@@ -729,8 +753,14 @@ impl GraphicsTask {
                     error!(private.logger, "{}", err);
                 }
                 private.typewriter += new_messages.iter().map(|gm| gm.serialize()).collect();
-                private.buffer += new_messages;
-                private.screen.update_after_new_lines(&private.buffer);
+                {
+                    let mut buffer = private
+                        .buffer
+                        .write()
+                        .expect("Cannot get buffer lock for write");
+                    *buffer += new_messages;
+                    private.screen.update_after_new_lines(&buffer);
+                }
                 save_stats.file_size = private.typewriter.get_size();
                 new_messages = vec![];
 
@@ -765,7 +795,10 @@ impl GraphicsTask {
                             .split(f.size());
 
                         private.screen.draw(
-                            &private.buffer,
+                            &private
+                                .buffer
+                                .read()
+                                .expect("Cannot get buffer lock for read"),
                             &save_stats,
                             f,
                             private.system_log_level,
@@ -821,7 +854,11 @@ impl GraphicsTask {
             return;
         }
 
-        for message in private.buffer.iter() {
+        let buffer = private
+            .buffer
+            .read()
+            .expect("Cannot get buffer lock for read");
+        for message in buffer.iter() {
             let line = message.line;
             let message = message.decode(decoder).message;
             let message = ANSI::remove_encoding(message);
@@ -845,6 +882,7 @@ impl GraphicsTask {
                 search_start_byte = abs_byte + pattern.len();
             }
         }
+        drop(buffer);
 
         private.screen.mode_mut().update_current();
 
@@ -855,7 +893,11 @@ impl GraphicsTask {
     }
 
     fn max_main_axis(private: &GraphicsConnections) -> u16 {
-        let buffer_len = private.buffer.len() as u16;
+        let buffer_len = private
+            .buffer
+            .read()
+            .expect("Cannot get buffer lock for read")
+            .len() as u16;
         let screen_height = private.screen.size().height.saturating_sub(2);
 
         buffer_len.saturating_sub(screen_height)
@@ -863,6 +905,7 @@ impl GraphicsTask {
 }
 
 impl GraphicsConnections {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         logger: Logger,
         logger_receiver: Receiver<LogMessage>,
@@ -870,6 +913,7 @@ impl GraphicsConnections {
         rx: Consumer<Arc<TimedBytes>>,
         inputs_shared: Shared<InputsShared>,
         interface_shared: Shared<InterfaceShared>,
+        buffer: Arc<RwLock<Buffer>>,
         config: GraphicsConfig,
     ) -> Self {
         Self {
@@ -879,7 +923,7 @@ impl GraphicsConnections {
             rx,
             inputs_shared,
             interface_shared,
-            buffer: Buffer::new(config.capacity),
+            buffer,
             screen: Screen::default(),
             typewriter: TypeWriter::new(config.storage_base_filename.clone()),
             recorder: Recorder::new(config.storage_base_filename).expect("Cannot create Recorder"),
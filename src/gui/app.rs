@@ -1,19 +1,43 @@
-use chrono::{DateTime, Local};
-use egui::{Color32, FontId, Key, KeyboardShortcut, Modifiers, RichText, ScrollArea, Stroke};
+use chrono::Local;
+use egui::text::{CCursor, CCursorRange, LayoutJob};
+use egui::{
+    Color32, FontId, Key, KeyboardShortcut, Modifiers, RichText, ScrollArea, Stroke, TextFormat,
+};
 use serde::{Deserialize, Serialize};
 use serialport::{DataBits, FlowControl, Parity, StopBits};
-use std::path::PathBuf;
+use std::collections::{HashMap, hash_map};
+use std::io::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use super::serial_worker::{
-    SerialCommand, SerialConfig, SerialEvent, SerialHandle, spawn_serial_worker,
-};
+use super::log_store::{EntryKind, LogEntry, LogStore};
+use super::pane_interface::PaneInterface;
+use super::pane_log::{PaneLog, SharedPaneLog};
+use super::plugin_host;
+use super::recorder::RecordFormat;
+use super::search::{Finder, HighlightRule, Highlighter, Pattern, backgrounds};
+use crate::infra::ByteFormat;
+use crate::infra::tags::TagList;
+use crate::inputs::inputs_task::InputsTask;
+use crate::interfaces::rtt_if::{self, RttSetup};
+use crate::interfaces::serial_if::SerialSetup;
+use crate::interfaces::{InterfaceSetup, InterfaceStatus, InterfaceType};
+use crate::plugin::engine::PluginEngineCommand;
 
 const COMMON_BAUD_RATES: &[u32] = &[
     300, 1200, 2400, 4800, 9600, 14400, 19200, 28800, 38400, 57600, 115200, 230400, 460800, 921600,
 ];
 
-const MAX_ENTRIES: usize = 5000;
+/// Max suggestions shown below the RTT target field.
+const MAX_TARGET_SUGGESTIONS: usize = 20;
+/// Max tags suggested below the send input.
+const MAX_TAG_SUGGESTIONS: usize = 10;
+
+/// Most log rows kept formatted, cleared when full.
+const MAX_CACHED_ROWS: usize = 4096;
+/// Most log entries checked by the find bar on each frame.
+const FIND_SCAN_BUDGET: usize = 20_000;
 const MAX_HISTORY: usize = 1000;
 const PORT_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
 const STATE_AUTOSAVE_DEBOUNCE: Duration = Duration::from_secs(2);
@@ -23,12 +47,40 @@ const MIN_LOG_FONT_SIZE: f32 = 8.0;
 const MAX_LOG_FONT_SIZE: f32 = 32.0;
 const LOG_FONT_STEP: f32 = 1.0;
 
+const FIND_MATCH_COLOR: Color32 = Color32::from_rgb(255, 235, 120);
+const FIND_CURRENT_COLOR: Color32 = Color32::from_rgb(255, 160, 60);
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum SessionType {
+    #[default]
+    Serial,
+    Rtt,
+}
+
+impl SessionType {
+    fn label(self) -> &'static str {
+        match self {
+            SessionType::Serial => "Serial",
+            SessionType::Rtt => "RTT",
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 enum SendMode {
     Ascii,
     Hex,
 }
 
+impl SendMode {
+    fn label(self) -> &'static str {
+        match self {
+            SendMode::Ascii => "ASCII",
+            SendMode::Hex => "Hex",
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 enum LineEnding {
     None,
@@ -57,30 +109,98 @@ impl LineEnding {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// A send panel button that sends its payload in one click.
+#[derive(Clone, Serialize, Deserialize)]
+struct Macro {
+    label: String,
+    payload: String,
+    send_mode: SendMode,
+    line_ending: LineEnding,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum MacroScope {
+    /// Saved with the pane.
+    Pane,
+    /// Saved once and shown on every pane.
+    Global,
+}
+
+impl MacroScope {
+    fn label(self) -> &'static str {
+        match self {
+            MacroScope::Pane => "This pane",
+            MacroScope::Global => "All panes",
+        }
+    }
+}
+
+/// The macro being added or edited on a pane.
+struct MacroEditor {
+    /// Where the edited macro is, `None` when adding one.
+    editing: Option<(MacroScope, usize)>,
+    scope: MacroScope,
+    value: Macro,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 enum DisplayMode {
     Ascii,
     Hex,
     HexDump,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-enum EntryKind {
-    Rx,
-    Tx,
-    System,
-    SystemError,
+/// An entry formatted for a display mode, with the highlights of its body.
+struct LogRow {
+    timestamp: String,
+    kind: EntryKind,
+    body: String,
+    highlights: Vec<(Range<usize>, Color32)>,
 }
 
-struct LogEntry {
-    timestamp: DateTime<Local>,
-    kind: EntryKind,
-    bytes: Vec<u8>,
-    message: Option<String>,
+/// Rows of the log view by display mode and entry id, so the visible ones
+/// aren't formatted again on every frame.
+#[derive(Default)]
+struct RowCache {
+    rows: HashMap<(DisplayMode, u64), LogRow>,
+}
+
+impl RowCache {
+    fn get(
+        &mut self,
+        log: &mut LogStore,
+        id: u64,
+        mode: DisplayMode,
+        highlighter: &Highlighter,
+    ) -> Option<&LogRow> {
+        if self.rows.len() >= MAX_CACHED_ROWS && !self.rows.contains_key(&(mode, id)) {
+            self.rows.clear();
+        }
+
+        match self.rows.entry((mode, id)) {
+            hash_map::Entry::Occupied(row) => Some(row.into_mut()),
+            hash_map::Entry::Vacant(row) => {
+                let entry = log.get(id)?;
+                let body = entry_text(entry, mode);
+                Some(row.insert(LogRow {
+                    timestamp: format!("[{}] ", entry.timestamp.format("%H:%M:%S%.3f")),
+                    kind: entry.kind,
+                    highlights: highlighter.spans(&body),
+                    body,
+                }))
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.rows.clear();
+    }
 }
 
 pub struct SerialSession {
     id: u64,
+    egui_ctx: egui::Context,
+    session_type: SessionType,
 
     available_ports: Vec<String>,
     last_port_refresh: Instant,
@@ -94,8 +214,17 @@ pub struct SerialSession {
     parity: Parity,
     flow_control: FlowControl,
 
+    rtt_target: String,
+    rtt_channel: usize,
+    /// Probe selector, empty for the first probe found.
+    rtt_probe: String,
+    /// Connected probes, as (selector, description) pairs.
+    available_probes: Vec<(String, String)>,
+
     connected: bool,
     connecting: bool,
+    /// Retry, with backoff, when the connection fails or is lost.
+    auto_reconnect: bool,
 
     send_input: String,
     send_mode: SendMode,
@@ -107,20 +236,44 @@ pub struct SerialSession {
     /// The user's in-progress draft, saved when they start navigating
     /// history so Down past the most-recent entry can restore it.
     history_draft: String,
-
-    entries: Vec<LogEntry>,
+    /// Tags of the tag file and of the pane's plugins.
+    tag_list: TagList,
+    /// Char index of the send input cursor when the input last had focus.
+    tag_cursor: usize,
+    macros: Vec<Macro>,
+    macro_editor: Option<MacroEditor>,
+
+    log: SharedPaneLog,
+    rows: RowCache,
     display_mode: DisplayMode,
     show_timestamps: bool,
     auto_scroll: bool,
     show_tx_in_log: bool,
     require_ctrl_enter_to_send: bool,
 
-    bytes_rx: u64,
-    bytes_tx: u64,
+    find_open: bool,
+    find_pattern: Pattern,
+    /// Show only the entries matching the find bar.
+    only_matching: bool,
+    finder: Finder,
+    /// Focus the find field on the next frame.
+    focus_find: bool,
+    /// Entry to bring into view on the next frame.
+    scroll_to_id: Option<u64>,
+    highlight_rules: Vec<HighlightRule>,
+    highlighter: Highlighter,
+    show_highlight_rules: bool,
+
+    record_format: RecordFormat,
 
     status: String,
 
-    serial: SerialHandle,
+    interface: PaneInterface,
+
+    /// Files of the plugins loaded on this pane, reloaded with the session.
+    plugin_files: Vec<String>,
+    plugin_file_input: String,
+    plugin_command_input: String,
 }
 
 /// Direction in which a `Container`'s children are arranged.
@@ -163,13 +316,16 @@ pub struct GuiApp {
     egui_ctx: egui::Context,
     /// Global send history shared across all panes/tabs. Newest entries at the back.
     send_history: Vec<String>,
+    tag_list: TagList,
+    /// Macros shown on every pane.
+    macros: Vec<Macro>,
     /// Set when in-memory state diverges from the persisted file.
     state_dirty: bool,
     last_save_attempt: Instant,
 }
 
 impl GuiApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, tag_list: TagList) -> Self {
         let egui_ctx = cc.egui_ctx.clone();
         let mut app = Self {
             tabs: Vec::new(),
@@ -180,6 +336,8 @@ impl GuiApp {
             visuals_initialized: false,
             egui_ctx,
             send_history: Vec::new(),
+            tag_list,
+            macros: Vec::new(),
             state_dirty: false,
             last_save_attempt: Instant::now(),
         };
@@ -205,13 +363,13 @@ impl GuiApp {
     fn new_session(&mut self) -> SerialSession {
         let id = self.next_session_id;
         self.next_session_id += 1;
-        SerialSession::new(id, self.egui_ctx.clone())
+        SerialSession::new(id, self.egui_ctx.clone(), self.tag_list.clone())
     }
 
     fn new_session_from(&mut self, ps: &PersistedSession) -> SerialSession {
         let id = self.next_session_id;
         self.next_session_id += 1;
-        SerialSession::from_persisted(id, self.egui_ctx.clone(), ps)
+        SerialSession::from_persisted(id, self.egui_ctx.clone(), self.tag_list.clone(), ps)
     }
 
     fn apply_persisted_state(&mut self, state: PersistedState) {
@@ -223,6 +381,7 @@ impl GuiApp {
             let excess = self.send_history.len() - MAX_HISTORY;
             self.send_history.drain(..excess);
         }
+        self.macros = state.macros;
         self.tabs = state
             .tabs
             .into_iter()
@@ -264,6 +423,7 @@ impl GuiApp {
             schema: PERSIST_SCHEMA,
             log_font_size: self.log_font_size,
             send_history: self.send_history.clone(),
+            macros: self.macros.clone(),
             active_tab: self.active_tab,
             tabs: self
                 .tabs
@@ -325,9 +485,7 @@ impl GuiApp {
             return;
         }
         // shut down all sessions in the tab
-        for_each_leaf(&self.tabs[idx].root, &mut |s| {
-            let _ = s.serial.cmd_tx.send(SerialCommand::Shutdown);
-        });
+        for_each_leaf(&self.tabs[idx].root, &mut |s| s.shutdown());
         self.tabs.remove(idx);
         if self.active_tab >= self.tabs.len() {
             self.active_tab = self.tabs.len() - 1;
@@ -353,7 +511,7 @@ impl GuiApp {
             return;
         }
         if let Some(removed) = remove_leaf_at_path(&mut tab.root, path) {
-            let _ = removed.serial.cmd_tx.send(SerialCommand::Shutdown);
+            removed.shutdown();
             collapse_single_children(&mut tab.root);
             // Active path may now be invalid — reset to the first leaf in the tree.
             tab.active_path = first_leaf_path(&tab.root);
@@ -410,6 +568,11 @@ impl GuiApp {
         }) {
             self.reset_log_font();
         }
+        if ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::F)))
+            && let Some(session) = self.active_session_mut()
+        {
+            session.open_find();
+        }
         if ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::COMMAND, Key::T)))
         {
             self.add_tab();
@@ -494,6 +657,7 @@ impl GuiApp {
         let log_font_size = self.log_font_size;
         let active_tab = self.active_tab;
         let history = &mut self.send_history;
+        let macros = &mut self.macros;
         let tab = &mut self.tabs[active_tab];
         let tab_id = tab.id;
         let multi_pane = count_leaves(&tab.root) > 1;
@@ -509,13 +673,14 @@ impl GuiApp {
             log_font_size,
             multi_pane,
             history,
+            macros,
             &mut collected,
         );
 
         if let Some(path) = collected.activate {
             tab.active_path = path;
         }
-        if collected.sent {
+        if collected.sent || collected.settings_changed {
             self.state_dirty = true;
         }
         if let Some((path, dir)) = collected.split {
@@ -533,7 +698,8 @@ impl GuiApp {
         self.tabs.iter().any(|t| {
             let mut busy = false;
             for_each_leaf(&t.root, &mut |s| {
-                if s.connected || s.connecting {
+                // Plugins log from their own thread, so keep polling while any is loaded.
+                if s.connected || s.connecting || !s.plugin_files.is_empty() {
                     busy = true;
                 }
             });
@@ -548,6 +714,7 @@ struct PaneAction {
     split_down: bool,
     close: bool,
     sent: bool,
+    settings_changed: bool,
 }
 
 #[derive(Default)]
@@ -556,6 +723,7 @@ struct CollectedActions {
     split: Option<(Vec<usize>, Direction)>,
     close: Option<Vec<usize>>,
     sent: bool,
+    settings_changed: bool,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -568,12 +736,13 @@ fn render_node(
     log_font_size: f32,
     multi_pane: bool,
     history: &mut Vec<String>,
+    macros: &mut Vec<Macro>,
     actions: &mut CollectedActions,
 ) {
     match node {
         LayoutNode::Leaf(session) => {
             let is_active = path.as_slice() == active_path;
-            let pane_action = session.ui(ui, log_font_size, multi_pane, is_active, history);
+            let pane_action = session.ui(ui, log_font_size, multi_pane, is_active, history, macros);
             if pane_action.split_right {
                 actions.split = Some((path.clone(), Direction::Horizontal));
             } else if pane_action.split_down {
@@ -585,6 +754,9 @@ fn render_node(
             if pane_action.sent {
                 actions.sent = true;
             }
+            if pane_action.settings_changed {
+                actions.settings_changed = true;
+            }
             if ui.input(|inp| inp.pointer.any_click()) && ui.ui_contains_pointer() {
                 actions.activate = Some(path);
             }
@@ -620,6 +792,7 @@ fn render_node(
                                     log_font_size,
                                     multi_pane,
                                     history,
+                                    macros,
                                     actions,
                                 );
                             });
@@ -639,6 +812,7 @@ fn render_node(
                                     log_font_size,
                                     multi_pane,
                                     history,
+                                    macros,
                                     actions,
                                 );
                             });
@@ -660,6 +834,7 @@ fn render_node(
                         log_font_size,
                         multi_pane,
                         history,
+                        macros,
                         actions,
                     );
                 });
@@ -835,12 +1010,15 @@ fn collapse_single_children(node: &mut LayoutNode) {
 }
 
 impl SerialSession {
-    fn new(id: u64, egui_ctx: egui::Context) -> Self {
-        let serial = spawn_serial_worker(egui_ctx);
+    fn new(id: u64, egui_ctx: egui::Context, tag_list: TagList) -> Self {
+        let log = PaneLog::new_shared(&format!("pane{}", id), true);
+        let interface = spawn_interface(SessionType::Serial, true, egui_ctx.clone(), log.clone());
         let available_ports = list_ports();
         let selected_port = available_ports.first().cloned().unwrap_or_default();
         Self {
             id,
+            egui_ctx,
+            session_type: SessionType::Serial,
             available_ports,
             last_port_refresh: Instant::now(),
             selected_port,
@@ -851,34 +1029,73 @@ impl SerialSession {
             stop_bits: StopBits::One,
             parity: Parity::None,
             flow_control: FlowControl::None,
+            rtt_target: String::new(),
+            rtt_channel: 0,
+            rtt_probe: String::new(),
+            available_probes: Vec::new(),
             connected: false,
             connecting: false,
+            auto_reconnect: true,
             send_input: String::new(),
             send_mode: SendMode::Ascii,
             line_ending: LineEnding::CrLf,
             history_cursor: None,
             history_draft: String::new(),
-            entries: Vec::new(),
+            tag_list,
+            tag_cursor: 0,
+            macros: Vec::new(),
+            macro_editor: None,
+            log,
+            rows: RowCache::default(),
             display_mode: DisplayMode::Ascii,
             show_timestamps: true,
             auto_scroll: true,
             show_tx_in_log: true,
             require_ctrl_enter_to_send: false,
-            bytes_rx: 0,
-            bytes_tx: 0,
+            find_open: false,
+            find_pattern: Pattern::default(),
+            only_matching: false,
+            finder: Finder::default(),
+            focus_find: false,
+            scroll_to_id: None,
+            highlight_rules: Vec::new(),
+            highlighter: Highlighter::default(),
+            show_highlight_rules: false,
+            record_format: RecordFormat::default(),
             status: "Idle".to_string(),
-            serial,
+            interface,
+            plugin_files: Vec::new(),
+            plugin_file_input: String::new(),
+            plugin_command_input: String::new(),
+        }
+    }
+
+    fn from_persisted(
+        id: u64,
+        egui_ctx: egui::Context,
+        tag_list: TagList,
+        p: &PersistedSession,
+    ) -> Self {
+        let log = PaneLog::new_shared(&format!("pane{}", id), p.show_tx_in_log);
+        let interface = spawn_interface(
+            p.session_type,
+            p.auto_reconnect,
+            egui_ctx.clone(),
+            log.clone(),
+        );
+        for filepath in &p.plugins {
+            interface.plugins().send(PluginEngineCommand::LoadPlugin {
+                filepath: filepath.clone(),
+            });
         }
-    }
-
-    fn from_persisted(id: u64, egui_ctx: egui::Context, p: &PersistedSession) -> Self {
-        let serial = spawn_serial_worker(egui_ctx);
         let available_ports = list_ports();
         // Keep the persisted port even if it isn't currently plugged in — the
         // user may want to reconnect once the device shows up. Combo box will
         // show it in the dropdown via `selected_text`.
         Self {
             id,
+            egui_ctx,
+            session_type: p.session_type,
             available_ports,
             last_port_refresh: Instant::now(),
             selected_port: p.port.clone(),
@@ -889,23 +1106,48 @@ impl SerialSession {
             stop_bits: stop_bits_from_u8(p.stop_bits),
             parity: p.parity.into(),
             flow_control: p.flow.into(),
+            rtt_target: p.rtt_target.clone(),
+            rtt_channel: p.rtt_channel,
+            rtt_probe: p.rtt_probe.clone(),
+            available_probes: if p.session_type == SessionType::Rtt {
+                rtt_if::list_probes()
+            } else {
+                Vec::new()
+            },
             connected: false,
             connecting: false,
+            auto_reconnect: p.auto_reconnect,
             send_input: String::new(),
             send_mode: p.send_mode,
             line_ending: p.line_ending,
             history_cursor: None,
             history_draft: String::new(),
-            entries: Vec::new(),
+            tag_list,
+            tag_cursor: 0,
+            macros: p.macros.clone(),
+            macro_editor: None,
+            log,
+            rows: RowCache::default(),
             display_mode: p.display_mode,
             show_timestamps: p.show_timestamps,
             auto_scroll: p.auto_scroll,
             show_tx_in_log: p.show_tx_in_log,
             require_ctrl_enter_to_send: p.require_ctrl_enter_to_send,
-            bytes_rx: 0,
-            bytes_tx: 0,
+            find_open: false,
+            find_pattern: Pattern::default(),
+            only_matching: false,
+            finder: Finder::default(),
+            focus_find: false,
+            scroll_to_id: None,
+            highlight_rules: p.highlight_rules.clone(),
+            highlighter: Highlighter::new(&p.highlight_rules),
+            show_highlight_rules: false,
+            record_format: p.record_format,
             status: "Idle".to_string(),
-            serial,
+            interface,
+            plugin_files: p.plugins.clone(),
+            plugin_file_input: String::new(),
+            plugin_command_input: String::new(),
         }
     }
 
@@ -926,96 +1168,164 @@ impl SerialSession {
             auto_scroll: self.auto_scroll,
             show_tx_in_log: self.show_tx_in_log,
             require_ctrl_enter_to_send: self.require_ctrl_enter_to_send,
+            plugins: self.plugin_files.clone(),
+            session_type: self.session_type,
+            rtt_target: self.rtt_target.clone(),
+            rtt_channel: self.rtt_channel,
+            rtt_probe: self.rtt_probe.clone(),
+            auto_reconnect: self.auto_reconnect,
+            highlight_rules: self.highlight_rules.clone(),
+            record_format: self.record_format,
+            macros: self.macros.clone(),
+        }
+    }
+
+    /// Replaces the pane's worker and plugin engine by the ones of
+    /// `session_type`. The loaded plugins are loaded again on the new engine.
+    fn set_session_type(&mut self, session_type: SessionType) {
+        if session_type == self.session_type {
+            return;
         }
+        self.shutdown();
+        self.interface = spawn_interface(
+            session_type,
+            self.auto_reconnect,
+            self.egui_ctx.clone(),
+            self.log.clone(),
+        );
+        self.session_type = session_type;
+        self.connected = false;
+        self.connecting = false;
+        for filepath in &self.plugin_files {
+            self.interface
+                .plugins()
+                .send(PluginEngineCommand::LoadPlugin {
+                    filepath: filepath.clone(),
+                });
+        }
+        self.refresh_ports();
+        self.status = "Idle".to_string();
+        self.push_system(format!("Switched to {} session", session_type.label()));
+    }
+
+    fn shutdown(&self) {
+        self.interface.shutdown();
     }
 
     fn refresh_ports(&mut self) {
+        if self.session_type == SessionType::Rtt {
+            self.available_probes = rtt_if::list_probes();
+            return;
+        }
         self.available_ports = list_ports();
         if !self.available_ports.contains(&self.selected_port) {
             self.selected_port = self.available_ports.first().cloned().unwrap_or_default();
         }
     }
 
-    fn drain_serial_events(&mut self) {
-        while let Ok(evt) = self.serial.event_rx.try_recv() {
-            match evt {
-                SerialEvent::Connected { port, baud_rate } => {
-                    self.connected = true;
-                    self.connecting = false;
-                    self.status = format!("Connected to {} @ {}bps", port, baud_rate);
-                    self.push_system(format!("Connected to {} @ {}bps", port, baud_rate));
+    fn drain_interface(&mut self) {
+        if let Some(status) = self.interface.poll_status() {
+            self.status = match status {
+                InterfaceStatus::Connected => {
+                    format!("Connected to {}", self.interface.description())
                 }
-                SerialEvent::Disconnected => {
-                    self.connected = false;
-                    self.connecting = false;
-                    self.status = "Disconnected".to_string();
-                    self.push_system("Disconnected".to_string());
-                }
-                SerialEvent::Error(msg) => {
-                    self.connecting = false;
-                    self.status = format!("Error: {}", msg);
-                    self.push_system_error(msg);
-                }
-                SerialEvent::RxLine { timestamp, bytes } => {
-                    self.bytes_rx += bytes.len() as u64;
-                    self.entries.push(LogEntry {
-                        timestamp,
-                        kind: EntryKind::Rx,
-                        bytes,
-                        message: None,
-                    });
-                    self.trim_log();
-                }
-                SerialEvent::TxEcho { timestamp, bytes } => {
-                    self.bytes_tx += bytes.len() as u64;
-                    if self.show_tx_in_log {
-                        self.entries.push(LogEntry {
-                            timestamp,
-                            kind: EntryKind::Tx,
-                            bytes,
-                            message: None,
-                        });
-                        self.trim_log();
-                    }
-                }
-            }
+                InterfaceStatus::Connecting => format!("Opening {}...", self.connect_target()),
+                InterfaceStatus::Disconnected => "Disconnected".to_string(),
+            };
         }
+        // Mirrored on every frame: a failed attempt without auto-reconnect
+        // goes back to `Disconnected` without a change to poll.
+        let status = self.interface.status();
+        self.connected = status == InterfaceStatus::Connected;
+        self.connecting = status == InterfaceStatus::Connecting;
+    }
+
+    /// Sends a command to the pane's plugin engine, keeping track of the
+    /// loaded files. Returns true when they changed.
+    fn run_plugin_command(&mut self, cmd: PluginEngineCommand) -> bool {
+        let changed = match &cmd {
+            PluginEngineCommand::LoadPlugin { filepath } => {
+                let name = plugin_name(filepath);
+                self.plugin_files.retain(|f| plugin_name(f) != name);
+                self.plugin_files.push(filepath.clone());
+                true
+            }
+            PluginEngineCommand::UnloadPlugin { plugin_name: name } => {
+                let len = self.plugin_files.len();
+                self.plugin_files.retain(|f| plugin_name(f) != *name);
+                self.plugin_files.len() != len
+            }
+            _ => false,
+        };
+        self.interface.plugins().send(cmd);
+        changed
     }
 
     fn push_system(&mut self, msg: String) {
-        self.entries.push(LogEntry {
-            timestamp: Local::now(),
-            kind: EntryKind::System,
-            bytes: Vec::new(),
-            message: Some(msg),
-        });
-        self.trim_log();
+        self.log
+            .lock()
+            .expect("Cannot get pane log lock")
+            .push_system(msg);
     }
 
     fn push_system_error(&mut self, msg: String) {
-        self.entries.push(LogEntry {
-            timestamp: Local::now(),
-            kind: EntryKind::SystemError,
-            bytes: Vec::new(),
-            message: Some(msg),
-        });
-        self.trim_log();
+        self.log
+            .lock()
+            .expect("Cannot get pane log lock")
+            .push_system_error(msg);
     }
 
-    fn trim_log(&mut self) {
-        if self.entries.len() > MAX_ENTRIES {
-            let excess = self.entries.len() - MAX_ENTRIES;
-            self.entries.drain(..excess);
-        }
+    fn toggle_recording(&mut self) {
+        let filename = format!("{}_rec{}", Local::now().format("%Y%m%d_%H%M%S"), self.id);
+        self.log
+            .lock()
+            .expect("Cannot get pane log lock")
+            .toggle_recording(PathBuf::from(filename), self.record_format);
     }
 
-    fn build_send_payload(&self) -> Result<Vec<u8>, String> {
-        let mut payload = match self.send_mode {
-            SendMode::Ascii => self.send_input.as_bytes().to_vec(),
-            SendMode::Hex => parse_hex_input(&self.send_input)?,
+    /// Sends `text` with `line_ending`. In ASCII, the `@tags` are expanded,
+    /// the ones of the plugins going through the plugin engine, and the `$XX`
+    /// sequences become bytes, like on the TUI. Returns false when there's
+    /// nothing to send.
+    fn send_text(
+        &mut self,
+        text: &str,
+        send_mode: SendMode,
+        line_ending: LineEnding,
+    ) -> Result<bool, String> {
+        let mut payload = match send_mode {
+            SendMode::Ascii => {
+                let (text, has_plugin_tags) = self.tag_list.replace_tags(text.to_string());
+                if has_plugin_tags {
+                    self.interface
+                        .plugins()
+                        .send(PluginEngineCommand::SendWithTags {
+                            command_line: text,
+                            end_bytes: line_ending.bytes().to_vec(),
+                        });
+                    return Ok(true);
+                }
+                InputsTask::replace_hex_sequence(text)
+            }
+            SendMode::Hex => parse_hex_input(text)?,
         };
-        payload.extend_from_slice(self.line_ending.bytes());
-        Ok(payload)
+        payload.extend_from_slice(line_ending.bytes());
+        if payload.is_empty() {
+            return Ok(false);
+        }
+
+        self.interface.send(payload);
+        Ok(true)
+    }
+
+    fn run_macro(&mut self, m: &Macro) {
+        if !self.connected {
+            self.push_system_error("Cannot send: not connected".to_string());
+            return;
+        }
+        if let Err(e) = self.send_text(&m.payload, m.send_mode, m.line_ending) {
+            self.push_system_error(format!("Macro \"{}\": {}", m.label, e));
+        }
     }
 
     /// Returns true when something was actually sent (caller should mark
@@ -1026,11 +1336,11 @@ impl SerialSession {
             return false;
         }
         let typed = self.send_input.clone();
-        match self.build_send_payload() {
-            Ok(payload) if payload.is_empty() => false,
-            Ok(payload) => {
-                let _ = self.serial.cmd_tx.send(SerialCommand::Send(payload));
+        match self.send_text(&typed, self.send_mode, self.line_ending) {
+            Ok(false) => false,
+            Ok(true) => {
                 self.send_input.clear();
+                self.tag_list.clear();
                 push_history(history, typed);
                 self.history_cursor = None;
                 self.history_draft.clear();
@@ -1043,14 +1353,30 @@ impl SerialSession {
         }
     }
 
-    fn current_config(&self) -> SerialConfig {
-        SerialConfig {
-            port: self.selected_port.clone(),
-            baud_rate: self.baud_rate,
-            data_bits: self.data_bits,
-            stop_bits: self.stop_bits,
-            parity: self.parity,
-            flow_control: self.flow_control,
+    fn interface_setup(&self) -> InterfaceSetup {
+        match self.session_type {
+            SessionType::Serial => InterfaceSetup::Serial(SerialSetup {
+                port: Some(self.selected_port.clone()),
+                baudrate: Some(self.baud_rate),
+                data_bits: Some(self.data_bits),
+                stop_bits: Some(self.stop_bits),
+                parity: Some(self.parity),
+                flow_control: Some(self.flow_control),
+                auto_reconnect: Some(self.auto_reconnect),
+            }),
+            SessionType::Rtt => InterfaceSetup::Rtt(RttSetup {
+                target: Some(self.rtt_target.trim().to_string()),
+                channel: Some(self.rtt_channel),
+                probe: Some(self.rtt_probe.clone()),
+                auto_reconnect: Some(self.auto_reconnect),
+            }),
+        }
+    }
+
+    fn connect_target(&self) -> &str {
+        match self.session_type {
+            SessionType::Serial => &self.selected_port,
+            SessionType::Rtt => &self.rtt_target,
         }
     }
 
@@ -1089,27 +1415,19 @@ impl SerialSession {
     fn save_log_to_timestamped_file(&mut self) {
         let filename = format!("{}.txt", Local::now().format("%Y%m%d_%H%M%S"));
         let path = PathBuf::from(&filename);
-        let mut content = String::new();
-        for entry in &self.entries {
-            let ts = format!("[{}] ", entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"));
-            let prefix = match entry.kind {
-                EntryKind::Rx => "RX ",
-                EntryKind::Tx => "TX ",
-                EntryKind::System => "-- ",
-                EntryKind::SystemError => "!! ",
-            };
-            let body = match entry.kind {
-                EntryKind::System | EntryKind::SystemError => {
-                    entry.message.clone().unwrap_or_default()
+        // Written as read, the log may not fit in memory.
+        let res = std::fs::File::create(&path).and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            let mut log = self.log.lock().expect("Cannot get pane log lock");
+            for id in log.store.first_id()..log.store.end_id() {
+                if let Some(entry) = log.store.get(id) {
+                    let line = log_line(entry, &entry_text(entry, self.display_mode));
+                    writeln!(writer, "{}", line)?;
                 }
-                EntryKind::Rx | EntryKind::Tx => format_bytes(&entry.bytes, self.display_mode),
-            };
-            content.push_str(&ts);
-            content.push_str(prefix);
-            content.push_str(&body);
-            content.push('\n');
-        }
-        match std::fs::write(&path, content) {
+            }
+            writer.flush()
+        });
+        match res {
             Ok(()) => {
                 let abs = std::fs::canonicalize(&path)
                     .map(|p| p.display().to_string())
@@ -1129,13 +1447,22 @@ impl SerialSession {
         multi_pane: bool,
         is_active: bool,
         history: &mut Vec<String>,
+        macros: &mut Vec<Macro>,
     ) -> PaneAction {
         // Per-frame housekeeping
         if self.last_port_refresh.elapsed() >= PORT_REFRESH_INTERVAL && !self.connected {
             self.refresh_ports();
             self.last_port_refresh = Instant::now();
         }
-        self.drain_serial_events();
+        self.drain_interface();
+        self.tag_list.set_plugin_tags(
+            self.interface
+                .plugins()
+                .plugins()
+                .into_iter()
+                .flat_map(|plugin| plugin.tags)
+                .collect(),
+        );
 
         if is_active {
             // Faint highlight on the active pane border
@@ -1167,13 +1494,19 @@ impl SerialSession {
                 .min_height(80.0)
                 .show_inside(ui, |ui| {
                     ui.add_space(2.0);
-                    self.send_panel(ui, history, &mut action);
+                    self.send_panel(ui, history, macros, &mut action);
                     ui.add_space(2.0);
                 });
             egui::CentralPanel::default()
                 .frame(egui::Frame::central_panel(ui.style()).inner_margin(2.0))
                 .show_inside(ui, |ui| {
                     self.log_toolbar(ui);
+                    if self.show_highlight_rules {
+                        self.highlight_rules_editor(ui, &mut action);
+                    }
+                    if self.find_open {
+                        self.find_bar(ui);
+                    }
                     ui.separator();
                     self.log_view(ui, log_font_size);
                 });
@@ -1185,70 +1518,27 @@ impl SerialSession {
     fn settings_panel(&mut self, ui: &mut egui::Ui, multi_pane: bool, action: &mut PaneAction) {
         ui.add_space(4.0);
         ui.horizontal_wrapped(|ui| {
-            ui.label("Port:");
-            let port_label = if self.selected_port.is_empty() {
-                "<no ports>".to_string()
-            } else {
-                self.selected_port.clone()
-            };
-            egui::ComboBox::from_id_salt("port_combo")
-                .selected_text(port_label)
-                .show_ui(ui, |ui| {
-                    if self.available_ports.is_empty() {
-                        ui.label("No serial ports detected");
-                    }
-                    for p in self.available_ports.clone() {
-                        ui.selectable_value(&mut self.selected_port, p.clone(), p);
-                    }
-                });
-            if ui.button("Refresh").clicked() {
-                self.refresh_ports();
+            ui.label("Type:");
+            let mut session_type = self.session_type;
+            ui.add_enabled_ui(!self.connected && !self.connecting, |ui| {
+                egui::ComboBox::from_id_salt("session_type_combo")
+                    .selected_text(session_type.label())
+                    .show_ui(ui, |ui| {
+                        for v in [SessionType::Serial, SessionType::Rtt] {
+                            ui.selectable_value(&mut session_type, v, v.label());
+                        }
+                    });
+            });
+            if session_type != self.session_type {
+                self.set_session_type(session_type);
+                action.settings_changed = true;
             }
 
             ui.separator();
 
-            ui.label("Baud:");
-            let baud_text = if self.use_custom_baud {
-                "Custom".to_string()
-            } else {
-                self.baud_rate.to_string()
-            };
-            egui::ComboBox::from_id_salt("baud_combo")
-                .selected_text(baud_text)
-                .show_ui(ui, |ui| {
-                    for &b in COMMON_BAUD_RATES {
-                        if ui
-                            .selectable_label(
-                                !self.use_custom_baud && self.baud_rate == b,
-                                b.to_string(),
-                            )
-                            .clicked()
-                        {
-                            self.baud_rate = b;
-                            self.custom_baud_str = b.to_string();
-                            self.use_custom_baud = false;
-                        }
-                    }
-                    if ui
-                        .selectable_label(self.use_custom_baud, "Custom...")
-                        .clicked()
-                    {
-                        self.use_custom_baud = true;
-                    }
-                });
-            if self.use_custom_baud {
-                let resp = ui.add(
-                    egui::TextEdit::singleline(&mut self.custom_baud_str)
-                        .desired_width(80.0)
-                        .hint_text("baud"),
-                );
-                if resp.changed() {
-                    if let Ok(b) = self.custom_baud_str.parse::<u32>()
-                        && b > 0
-                    {
-                        self.baud_rate = b;
-                    }
-                }
+            match self.session_type {
+                SessionType::Serial => self.serial_port_settings(ui),
+                SessionType::Rtt => self.rtt_settings(ui),
             }
 
             ui.separator();
@@ -1256,25 +1546,36 @@ impl SerialSession {
             let connect_label = if self.connected {
                 "Disconnect"
             } else if self.connecting {
-                "Connecting..."
+                "Cancel"
             } else {
                 "Connect"
             };
-            let can_act = !self.selected_port.is_empty() && self.baud_rate > 0;
-            let btn = ui.add_enabled(
-                can_act && !self.connecting,
-                egui::Button::new(connect_label),
-            );
+            let can_act = match self.session_type {
+                SessionType::Serial => !self.selected_port.is_empty() && self.baud_rate > 0,
+                SessionType::Rtt => !self.rtt_target.trim().is_empty(),
+            };
+            let btn = ui.add_enabled(can_act, egui::Button::new(connect_label));
             if btn.clicked() {
-                if self.connected {
-                    let _ = self.serial.cmd_tx.send(SerialCommand::Disconnect);
+                if self.connected || self.connecting {
+                    self.interface.disconnect();
+                    self.connecting = false;
                 } else {
                     self.connecting = true;
-                    self.status = format!("Opening {}...", self.selected_port);
-                    let cfg = self.current_config();
-                    let _ = self.serial.cmd_tx.send(SerialCommand::Connect(cfg));
+                    self.status = format!("Opening {}...", self.connect_target());
+                    // Setup reconnects with the new settings when they changed.
+                    self.interface.setup(self.interface_setup());
+                    self.interface.connect();
                 }
             }
+            if ui
+                .checkbox(&mut self.auto_reconnect, "Auto-reconnect")
+                .on_hover_text("Keep retrying when the connection fails or is lost")
+                .changed()
+            {
+                self.interface
+                    .setup(auto_reconnect_setup(self.session_type, self.auto_reconnect));
+                action.settings_changed = true;
+            }
 
             ui.separator();
             let (dot, color) = if self.connected {
@@ -1313,7 +1614,16 @@ impl SerialSession {
             });
         });
 
+        if self.session_type == SessionType::Serial {
+            ui.add_space(4.0);
+            self.serial_line_settings(ui);
+        }
         ui.add_space(4.0);
+        self.plugins_row(ui, action);
+        ui.add_space(2.0);
+    }
+
+    fn serial_line_settings(&mut self, ui: &mut egui::Ui) {
         ui.horizontal_wrapped(|ui| {
             ui.label("Data bits:");
             egui::ComboBox::from_id_salt("databits_combo")
@@ -1366,26 +1676,238 @@ impl SerialSession {
             if self.connected {
                 ui.separator();
                 if ui.button("Apply").clicked() {
-                    let cfg = self.current_config();
-                    let _ = self.serial.cmd_tx.send(SerialCommand::Disconnect);
-                    self.connecting = true;
-                    let _ = self.serial.cmd_tx.send(SerialCommand::Connect(cfg));
+                    self.interface.setup(self.interface_setup());
+                }
+            }
+        });
+    }
+
+    fn serial_port_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Port:");
+        let port_label = if self.selected_port.is_empty() {
+            "<no ports>".to_string()
+        } else {
+            self.selected_port.clone()
+        };
+        egui::ComboBox::from_id_salt("port_combo")
+            .selected_text(port_label)
+            .show_ui(ui, |ui| {
+                if self.available_ports.is_empty() {
+                    ui.label("No serial ports detected");
+                }
+                for p in self.available_ports.clone() {
+                    ui.selectable_value(&mut self.selected_port, p.clone(), p);
+                }
+            });
+        if ui.button("Refresh").clicked() {
+            self.refresh_ports();
+        }
+
+        ui.separator();
+
+        ui.label("Baud:");
+        let baud_text = if self.use_custom_baud {
+            "Custom".to_string()
+        } else {
+            self.baud_rate.to_string()
+        };
+        egui::ComboBox::from_id_salt("baud_combo")
+            .selected_text(baud_text)
+            .show_ui(ui, |ui| {
+                for &b in COMMON_BAUD_RATES {
+                    if ui
+                        .selectable_label(
+                            !self.use_custom_baud && self.baud_rate == b,
+                            b.to_string(),
+                        )
+                        .clicked()
+                    {
+                        self.baud_rate = b;
+                        self.custom_baud_str = b.to_string();
+                        self.use_custom_baud = false;
+                    }
+                }
+                if ui
+                    .selectable_label(self.use_custom_baud, "Custom...")
+                    .clicked()
+                {
+                    self.use_custom_baud = true;
+                }
+            });
+        if self.use_custom_baud {
+            let resp = ui.add(
+                egui::TextEdit::singleline(&mut self.custom_baud_str)
+                    .desired_width(80.0)
+                    .hint_text("baud"),
+            );
+            if resp.changed() {
+                if let Ok(b) = self.custom_baud_str.parse::<u32>()
+                    && b > 0
+                {
+                    self.baud_rate = b;
+                }
+            }
+        }
+    }
+
+    fn rtt_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Target:");
+        let resp = ui.add(
+            egui::TextEdit::singleline(&mut self.rtt_target)
+                .desired_width(140.0)
+                .hint_text("nRF52840_xxAA"),
+        );
+        let popup_id = ui.make_persistent_id("rtt_target_popup");
+        if resp.changed() && self.rtt_target.len() >= 3 {
+            ui.memory_mut(|m| m.open_popup(popup_id));
+        }
+        egui::popup_below_widget(
+            ui,
+            popup_id,
+            &resp,
+            egui::PopupCloseBehavior::CloseOnClickOutside,
+            |ui| {
+                ui.set_min_width(200.0);
+                let targets = rtt_if::search_targets(self.rtt_target.trim());
+                if targets.is_empty() {
+                    ui.label(RichText::new("Unknown target").color(Color32::GRAY));
+                }
+                for target in targets.into_iter().take(MAX_TARGET_SUGGESTIONS) {
+                    if ui.selectable_label(false, &target).clicked() {
+                        self.rtt_target = target;
+                        ui.memory_mut(|m| m.close_popup());
+                    }
+                }
+            },
+        );
+
+        ui.separator();
+        ui.label("Channel:");
+        ui.add(egui::DragValue::new(&mut self.rtt_channel).range(0..=15));
+
+        ui.separator();
+        ui.label("Probe:");
+        let probe_label = self
+            .available_probes
+            .iter()
+            .find(|(selector, _)| *selector == self.rtt_probe)
+            .map(|(_, description)| description.clone())
+            .unwrap_or_else(|| {
+                if self.rtt_probe.is_empty() {
+                    "First available".to_string()
+                } else {
+                    self.rtt_probe.clone()
+                }
+            });
+        egui::ComboBox::from_id_salt("probe_combo")
+            .selected_text(probe_label)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.rtt_probe, String::new(), "First available");
+                for (selector, description) in self.available_probes.clone() {
+                    ui.selectable_value(&mut self.rtt_probe, selector, description);
+                }
+            });
+        if ui.button("Refresh").clicked() {
+            self.refresh_ports();
+        }
+    }
+
+    fn plugins_row(&mut self, ui: &mut egui::Ui, action: &mut PaneAction) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Plugins:");
+            let loaded = self.interface.plugins().plugins();
+            if loaded.is_empty() {
+                ui.label(RichText::new("none").color(Color32::GRAY));
+            }
+            for info in loaded {
+                let commands = info
+                    .commands
+                    .iter()
+                    .map(|c| format!("{} {} {}", info.name, c.name, c.args))
+                    .collect::<Vec<_>>();
+                let hover = if commands.is_empty() {
+                    "No commands".to_string()
+                } else {
+                    commands.join("\n")
+                };
+                ui.label(RichText::new(info.name.as_str()).strong())
+                    .on_hover_text(hover);
+                let filepath = self
+                    .plugin_files
+                    .iter()
+                    .find(|f| plugin_name(f) == *info.name)
+                    .cloned();
+                if let Some(filepath) = filepath
+                    && ui.small_button("↻").on_hover_text("Reload plugin").clicked()
+                {
+                    action.settings_changed |=
+                        self.run_plugin_command(PluginEngineCommand::LoadPlugin { filepath });
+                }
+                if ui.small_button("✕").on_hover_text("Unload plugin").clicked() {
+                    action.settings_changed |=
+                        self.run_plugin_command(PluginEngineCommand::UnloadPlugin {
+                            plugin_name: info.name.to_string(),
+                        });
+                }
+            }
+
+            ui.separator();
+            ui.add(
+                egui::TextEdit::singleline(&mut self.plugin_file_input)
+                    .desired_width(160.0)
+                    .hint_text("plugins/echo.lua"),
+            );
+            if ui
+                .add_enabled(
+                    !self.plugin_file_input.trim().is_empty(),
+                    egui::Button::new("Load"),
+                )
+                .clicked()
+            {
+                let filepath = self.plugin_file_input.trim().to_string();
+                action.settings_changed |=
+                    self.run_plugin_command(PluginEngineCommand::LoadPlugin { filepath });
+                self.plugin_file_input.clear();
+            }
+
+            ui.separator();
+            let resp = ui.add(
+                egui::TextEdit::singleline(&mut self.plugin_command_input)
+                    .desired_width(220.0)
+                    .hint_text("<plugin> <command> [args]"),
+            )
+            .on_hover_text("Runs a plugin command, or \"plugin load|unload|allow|deny|...\" like the TUI \"!\" commands");
+            let enter = resp.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if (ui.button("Run").clicked() || enter) && !self.plugin_command_input.trim().is_empty()
+            {
+                match plugin_host::parse_command(&self.plugin_command_input) {
+                    Ok(cmd) => {
+                        action.settings_changed |= self.run_plugin_command(cmd);
+                        self.plugin_command_input.clear();
+                    }
+                    Err(e) => self.push_system_error(format!("Plugin command error: {}", e)),
                 }
             }
         });
-        ui.add_space(2.0);
     }
 
     fn log_toolbar(&mut self, ui: &mut egui::Ui) {
         ui.horizontal_wrapped(|ui| {
             ui.label("View:");
+            let display_mode = self.display_mode;
             ui.selectable_value(&mut self.display_mode, DisplayMode::Ascii, "ASCII");
             ui.selectable_value(&mut self.display_mode, DisplayMode::Hex, "Hex");
             ui.selectable_value(&mut self.display_mode, DisplayMode::HexDump, "Hex+ASCII");
+            if self.display_mode != display_mode {
+                // The find bar matches the entries as they're shown.
+                self.finder.restart();
+            }
             ui.separator();
             ui.checkbox(&mut self.show_timestamps, "Timestamps");
             ui.checkbox(&mut self.auto_scroll, "Auto-scroll");
-            ui.checkbox(&mut self.show_tx_in_log, "Echo TX");
+            if ui.checkbox(&mut self.show_tx_in_log, "Echo TX").changed() {
+                self.log.lock().expect("Cannot get pane log lock").show_tx = self.show_tx_in_log;
+            }
             ui.separator();
             if ui
                 .button("Save…")
@@ -1394,64 +1916,270 @@ impl SerialSession {
             {
                 self.save_log_to_timestamped_file();
             }
+            let recording = self
+                .log
+                .lock()
+                .expect("Cannot get pane log lock")
+                .recorder
+                .is_some();
+            let record_text = if recording { "⏹ Stop" } else { "⏺ Record" };
+            if ui
+                .selectable_label(recording, record_text)
+                .on_hover_text(
+                    "Write every RX/TX entry to a file in the current directory as it arrives",
+                )
+                .clicked()
+            {
+                self.toggle_recording();
+            }
+            ui.add_enabled_ui(!recording, |ui| {
+                egui::ComboBox::from_id_salt("record_format_combo")
+                    .selected_text(self.record_format.label())
+                    .show_ui(ui, |ui| {
+                        for v in [RecordFormat::Text, RecordFormat::Hex, RecordFormat::Jsonl] {
+                            ui.selectable_value(&mut self.record_format, v, v.label());
+                        }
+                    });
+            });
+            ui.separator();
+            if ui
+                .button("Find…")
+                .on_hover_text("Search the log (Ctrl+F)")
+                .clicked()
+            {
+                self.open_find();
+            }
+            ui.toggle_value(&mut self.show_highlight_rules, "Highlights")
+                .on_hover_text("Colour the parts of the log matching a rule");
+            ui.separator();
             if ui.button("Clear").clicked() {
-                self.entries.clear();
-                self.bytes_rx = 0;
-                self.bytes_tx = 0;
+                self.log.lock().expect("Cannot get pane log lock").clear();
+                self.rows.clear();
             }
         });
     }
 
-    fn log_view(&self, ui: &mut egui::Ui, log_font_size: f32) {
-        let row_height = log_font_size + 4.0;
-        let mut area = ScrollArea::vertical().auto_shrink([false, false]);
-        if self.auto_scroll {
-            area = area.stick_to_bottom(true);
-        }
-        area.show_rows(ui, row_height, self.entries.len(), |ui, range| {
-            for entry in &self.entries[range] {
-                self.render_entry(ui, entry, log_font_size);
+    fn open_find(&mut self) {
+        self.find_open = true;
+        self.focus_find = true;
+    }
+
+    fn find_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Find:");
+            let edit_id = egui::Id::new(("find_input_edit", self.id));
+            let edit_focused = ui.ctx().memory(|m| m.has_focus(edit_id));
+            // Consumed before the TextEdit, which would drop the focus on Enter.
+            let prev_pressed =
+                edit_focused && ui.input_mut(|i| i.consume_key(Modifiers::SHIFT, Key::Enter));
+            let next_pressed =
+                edit_focused && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter));
+            let close_pressed =
+                edit_focused && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape));
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.find_pattern.text)
+                    .id(edit_id)
+                    .desired_width(220.0)
+                    .hint_text("Text or regex (Enter / Shift+Enter)")
+                    .font(egui::TextStyle::Monospace),
+            );
+            if self.focus_find {
+                response.request_focus();
+                self.focus_find = false;
+            }
+            ui.toggle_value(&mut self.find_pattern.is_case_sensitive, "Aa")
+                .on_hover_text("Match case");
+            ui.toggle_value(&mut self.find_pattern.is_regex, ".*")
+                .on_hover_text("Regular expression");
+
+            self.finder.set_pattern(&self.find_pattern);
+            let mut log = self.log.lock().expect("Cannot get pane log lock");
+            let (first_id, len) = (log.store.first_id(), log.store.len());
+            let display_mode = self.display_mode;
+            self.finder.scan(first_id, len, FIND_SCAN_BUDGET, |idx| {
+                log.store
+                    .get(first_id + idx as u64)
+                    .map(|entry| entry_text(entry, display_mode))
+                    .unwrap_or_default()
+            });
+            drop(log);
+
+            let prev_clicked = ui.button("↑").on_hover_text("Previous match").clicked();
+            let next_clicked = ui.button("↓").on_hover_text("Next match").clicked();
+            if prev_pressed || prev_clicked {
+                self.scroll_to_match(Finder::select_prev);
+            } else if next_pressed || next_clicked {
+                self.scroll_to_match(Finder::select_next);
+            }
+
+            if ui
+                .checkbox(&mut self.only_matching, "Only matching")
+                .changed()
+            {
+                // Keeps the current match in view across the switch.
+                self.scroll_to_id = self.finder.current_id();
+            }
+
+            if let Some(err) = self.finder.error() {
+                ui.label(
+                    RichText::new(err.lines().last().unwrap_or(err))
+                        .small()
+                        .color(Color32::from_rgb(190, 30, 30)),
+                );
+            } else if self.finder.is_scanning() {
+                ui.label(RichText::new("Searching…").small().color(Color32::GRAY));
+                ui.ctx().request_repaint();
+            } else if self.finder.is_active() {
+                let text = match self.finder.position() {
+                    Some((current, total)) => format!("{}/{}", current + 1, total),
+                    None => format!("{} matches", self.finder.matches().len()),
+                };
+                ui.label(RichText::new(text).small().color(Color32::GRAY));
+            }
+
+            if ui.small_button("✕").on_hover_text("Close (Esc)").clicked() || close_pressed {
+                self.find_open = false;
             }
         });
     }
 
-    fn render_entry(&self, ui: &mut egui::Ui, entry: &LogEntry, log_font_size: f32) {
-        let ts = if self.show_timestamps {
-            format!("[{}] ", entry.timestamp.format("%H:%M:%S%.3f"))
-        } else {
-            String::new()
-        };
+    fn scroll_to_match(&mut self, step: fn(&mut Finder) -> Option<u64>) {
+        if let Some(id) = step(&mut self.finder) {
+            self.scroll_to_id = Some(id);
+            self.auto_scroll = false;
+        }
+    }
+
+    fn highlight_rules_editor(&mut self, ui: &mut egui::Ui, action: &mut PaneAction) {
+        let mut changed = false;
+        let mut removed = None;
+        for (idx, rule) in self.highlight_rules.iter_mut().enumerate() {
+            ui.push_id(idx, |ui| {
+                ui.horizontal(|ui| {
+                    changed |= ui.color_edit_button_srgb(&mut rule.color).changed();
+                    changed |= ui
+                        .add(
+                            egui::TextEdit::singleline(&mut rule.pattern.text)
+                                .desired_width(200.0)
+                                .hint_text("Text or regex to highlight")
+                                .font(egui::TextStyle::Monospace),
+                        )
+                        .changed();
+                    changed |= ui
+                        .toggle_value(&mut rule.pattern.is_case_sensitive, "Aa")
+                        .on_hover_text("Match case")
+                        .changed();
+                    changed |= ui
+                        .toggle_value(&mut rule.pattern.is_regex, ".*")
+                        .on_hover_text("Regular expression")
+                        .changed();
+                    if ui.small_button("✕").on_hover_text("Remove rule").clicked() {
+                        removed = Some(idx);
+                    }
+                    if let Err(err) = rule.pattern.compile() {
+                        ui.label(
+                            RichText::new(err.lines().last().unwrap_or(&err).to_string())
+                                .small()
+                                .color(Color32::from_rgb(190, 30, 30)),
+                        );
+                    }
+                });
+            });
+        }
+        if let Some(idx) = removed {
+            self.highlight_rules.remove(idx);
+            changed = true;
+        }
+        if ui.small_button("+ Add rule").clicked() {
+            self.highlight_rules.push(HighlightRule::default());
+            changed = true;
+        }
 
-        let (prefix, color) = match entry.kind {
-            EntryKind::Rx => ("← ", Color32::from_rgb(10, 60, 170)),
-            EntryKind::Tx => ("→ ", Color32::from_rgb(15, 110, 35)),
-            EntryKind::System => ("· ", Color32::from_rgb(80, 80, 80)),
-            EntryKind::SystemError => ("! ", Color32::from_rgb(190, 30, 30)),
-        };
+        if changed {
+            self.highlighter = Highlighter::new(&self.highlight_rules);
+            self.rows.clear();
+            action.settings_changed = true;
+        }
+    }
 
-        let body = match entry.kind {
-            EntryKind::System | EntryKind::SystemError => entry.message.clone().unwrap_or_default(),
-            EntryKind::Rx | EntryKind::Tx => format_bytes(&entry.bytes, self.display_mode),
-        };
+    fn log_view(&mut self, ui: &mut egui::Ui, log_font_size: f32) {
+        let row_height = log_font_size + 4.0;
+        let finding = self.find_open && self.finder.is_active();
+        // Held while drawing, the rows must stay where they were counted.
+        let mut log = self.log.lock().expect("Cannot get pane log lock");
+        let (first_id, end_id) = (log.store.first_id(), log.store.end_id());
+        let matching = (finding && self.only_matching).then(|| {
+            self.finder
+                .matches()
+                .iter()
+                .copied()
+                .filter(|id| (first_id..end_id).contains(id))
+                .collect::<Vec<_>>()
+        });
 
-        let line = format!("{}{}{}", ts, prefix, body);
-        ui.label(
-            RichText::new(line)
-                .color(color)
-                .font(FontId::monospace(log_font_size)),
-        );
+        let mut area = ScrollArea::vertical().auto_shrink([false, false]);
+        let target_row = self
+            .scroll_to_id
+            .take()
+            .filter(|id| (first_id..end_id).contains(id))
+            .and_then(|id| match &matching {
+                Some(matching) => matching.iter().position(|m| *m == id),
+                None => Some((id - first_id) as usize),
+            });
+        if let Some(row) = target_row {
+            let spacing = ui.spacing().item_spacing.y;
+            let offset = row as f32 * (row_height + spacing) - ui.available_height() / 2.0;
+            area = area.vertical_scroll_offset(offset.max(0.0));
+        } else if self.auto_scroll {
+            area = area.stick_to_bottom(true);
+        }
+
+        let total_rows = matching.as_ref().map_or(log.store.len(), Vec::len);
+        let display_mode = self.display_mode;
+        let show_timestamps = self.show_timestamps;
+        let finder = finding.then_some(&self.finder);
+        area.show_rows(ui, row_height, total_rows, |ui, range| {
+            for row in range {
+                let id = matching
+                    .as_ref()
+                    .map_or(first_id + row as u64, |matching| matching[row]);
+                match self
+                    .rows
+                    .get(&mut log.store, id, display_mode, &self.highlighter)
+                {
+                    Some(log_row) => {
+                        render_row(ui, id, log_row, finder, show_timestamps, log_font_size)
+                    }
+                    None => {
+                        ui.label(
+                            RichText::new("! Cannot read this entry back from disk")
+                                .font(FontId::monospace(log_font_size))
+                                .color(Color32::from_rgb(190, 30, 30)),
+                        );
+                    }
+                }
+            }
+        });
     }
 
     fn send_panel(
         &mut self,
         ui: &mut egui::Ui,
         history: &mut Vec<String>,
+        macros: &mut Vec<Macro>,
         action: &mut PaneAction,
     ) {
+        self.macros_row(ui, macros, action);
+        if self.macro_editor.is_some() {
+            self.macro_editor_row(ui, macros, action);
+        }
+
         ui.horizontal(|ui| {
             ui.label("Mode:");
-            ui.selectable_value(&mut self.send_mode, SendMode::Ascii, "ASCII");
-            ui.selectable_value(&mut self.send_mode, SendMode::Hex, "Hex");
+            for v in [SendMode::Ascii, SendMode::Hex] {
+                ui.selectable_value(&mut self.send_mode, v, v.label());
+            }
             ui.separator();
             ui.label("Line ending:");
             egui::ComboBox::from_id_salt("line_ending_combo")
@@ -1487,7 +2215,7 @@ impl SerialSession {
 
         ui.horizontal(|ui| {
             let hint = match self.send_mode {
-                SendMode::Ascii => "Type text to send (↑/↓ for history)...",
+                SendMode::Ascii => "Type text to send, @ for tags (↑/↓ for history)...",
                 SendMode::Hex => "Hex bytes, e.g. A0 B1 0F or A0B10F (↑/↓ for history)",
             };
             let edit_id = egui::Id::new(("send_input_edit", self.id));
@@ -1512,15 +2240,28 @@ impl SerialSession {
             if down_pressed {
                 self.history_down(history);
             }
+            // Tab completes the first suggested tag instead of moving the focus.
+            let tab_pressed = edit_focused
+                && self.tag_list.get_first_autocomplete_list().is_some()
+                && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab));
+
+            let output = egui::TextEdit::multiline(&mut self.send_input)
+                .id(edit_id)
+                .desired_rows(2)
+                .desired_width(f32::INFINITY)
+                .hint_text(hint)
+                .font(egui::TextStyle::Monospace)
+                .show(ui);
+            if output.response.has_focus()
+                && let Some(range) = output.cursor_range
+            {
+                self.tag_cursor = range.primary.ccursor.index;
+                self.update_tag_suggestions();
+            }
 
-            ui.add(
-                egui::TextEdit::multiline(&mut self.send_input)
-                    .id(edit_id)
-                    .desired_rows(2)
-                    .desired_width(f32::INFINITY)
-                    .hint_text(hint)
-                    .font(egui::TextStyle::Monospace),
-            );
+            if tab_pressed && let Some(tag_name) = self.tag_list.get_first_autocomplete_list() {
+                self.complete_tag(ui.ctx(), edit_id, &tag_name);
+            }
 
             if plain_enter_pressed || ctrl_enter_pressed {
                 if self.try_send(history) {
@@ -1529,6 +2270,8 @@ impl SerialSession {
             }
         });
 
+        self.tag_suggestions(ui);
+
         ui.horizontal(|ui| {
             let send_btn = ui.add_enabled(
                 self.connected && !self.send_input.is_empty(),
@@ -1539,6 +2282,7 @@ impl SerialSession {
             }
             if ui.button("Clear input").clicked() {
                 self.send_input.clear();
+                self.tag_list.clear();
             }
             if self.send_mode == SendMode::Hex {
                 match parse_hex_input(&self.send_input) {
@@ -1562,14 +2306,270 @@ impl SerialSession {
         });
     }
 
+    fn update_tag_suggestions(&mut self) {
+        if self.send_mode != SendMode::Ascii {
+            self.tag_list.clear();
+            return;
+        }
+        self.tag_list
+            .update_pattern(&self.send_input, self.tag_cursor);
+        self.tag_list.update_autocomplete_list();
+    }
+
+    fn complete_tag(&mut self, ctx: &egui::Context, edit_id: egui::Id, tag_name: &str) {
+        let (text, cursor) = self
+            .tag_list
+            .complete(&self.send_input, self.tag_cursor, tag_name);
+        self.send_input = text;
+        self.tag_cursor = cursor;
+        if let Some(mut state) = egui::TextEdit::load_state(ctx, edit_id) {
+            state
+                .cursor
+                .set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+            state.store(ctx, edit_id);
+        }
+        ctx.memory_mut(|m| m.request_focus(edit_id));
+        self.tag_list.clear();
+    }
+
+    /// Tags matching the `@tag` being typed. Tab completes the first one.
+    fn tag_suggestions(&mut self, ui: &mut egui::Ui) {
+        let suggestions = self.tag_list.autocomplete_list();
+        if suggestions.is_empty() || self.send_mode != SendMode::Ascii {
+            return;
+        }
+
+        let mut picked = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label(RichText::new("Tags:").small().color(Color32::GRAY));
+            for tag_name in suggestions.iter().take(MAX_TAG_SUGGESTIONS) {
+                let hover = if self.tag_list.is_plugin_tag(tag_name) {
+                    "Computed by a plugin when sent".to_string()
+                } else {
+                    self.tag_list.get_tagged_key(&format!("@{}", tag_name))
+                };
+                if ui
+                    .small_button(format!("@{}", tag_name))
+                    .on_hover_text(hover)
+                    .clicked()
+                {
+                    picked = Some(tag_name.clone());
+                }
+            }
+            if suggestions.len() > MAX_TAG_SUGGESTIONS {
+                ui.label(
+                    RichText::new(format!("+{} more", suggestions.len() - MAX_TAG_SUGGESTIONS))
+                        .small()
+                        .color(Color32::GRAY),
+                );
+            }
+        });
+
+        if let Some(tag_name) = picked {
+            let edit_id = egui::Id::new(("send_input_edit", self.id));
+            self.complete_tag(ui.ctx(), edit_id, &tag_name);
+        }
+    }
+
+    fn macros_row(&mut self, ui: &mut egui::Ui, macros: &mut Vec<Macro>, action: &mut PaneAction) {
+        let mut clicked = None;
+        let mut edited = None;
+        let mut removed = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Macros:");
+            for (scope, list) in [
+                (MacroScope::Global, &*macros),
+                (MacroScope::Pane, &self.macros),
+            ] {
+                for (idx, m) in list.iter().enumerate() {
+                    ui.push_id((scope, idx), |ui| {
+                        let hover = format!(
+                            "{}\n{}, {}\nRight-click to edit ({})",
+                            m.payload,
+                            m.send_mode.label(),
+                            m.line_ending.label(),
+                            scope.label().to_lowercase(),
+                        );
+                        let response = ui.button(&m.label).on_hover_text(hover);
+                        if response.clicked() {
+                            clicked = Some(m.clone());
+                        }
+                        response.context_menu(|ui| {
+                            if ui.button("Edit…").clicked() {
+                                edited = Some((scope, idx));
+                                ui.close_menu();
+                            }
+                            if ui.button("Remove").clicked() {
+                                removed = Some((scope, idx));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                }
+            }
+            if ui
+                .small_button("+ Macro")
+                .on_hover_text("Add a button sending a payload in one click")
+                .clicked()
+            {
+                self.macro_editor = Some(MacroEditor {
+                    editing: None,
+                    scope: MacroScope::Pane,
+                    value: Macro {
+                        label: String::new(),
+                        payload: self.send_input.clone(),
+                        send_mode: self.send_mode,
+                        line_ending: self.line_ending,
+                    },
+                });
+            }
+        });
+
+        if let Some(m) = clicked {
+            self.run_macro(&m);
+        }
+        if let Some((scope, idx)) = edited {
+            let value = match scope {
+                MacroScope::Global => macros[idx].clone(),
+                MacroScope::Pane => self.macros[idx].clone(),
+            };
+            self.macro_editor = Some(MacroEditor {
+                editing: Some((scope, idx)),
+                scope,
+                value,
+            });
+        }
+        if let Some((scope, idx)) = removed {
+            match scope {
+                MacroScope::Global => macros.remove(idx),
+                MacroScope::Pane => self.macros.remove(idx),
+            };
+            // The index of the edited macro may have moved.
+            self.macro_editor = None;
+            action.settings_changed = true;
+        }
+    }
+
+    fn macro_editor_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        macros: &mut Vec<Macro>,
+        action: &mut PaneAction,
+    ) {
+        let Some(editor) = &mut self.macro_editor else {
+            return;
+        };
+
+        let mut save = false;
+        let mut cancel = false;
+        ui.horizontal_wrapped(|ui| {
+            let value = &mut editor.value;
+            ui.add(
+                egui::TextEdit::singleline(&mut value.label)
+                    .desired_width(100.0)
+                    .hint_text("Label"),
+            );
+            ui.add(
+                egui::TextEdit::singleline(&mut value.payload)
+                    .desired_width(220.0)
+                    .hint_text(match value.send_mode {
+                        SendMode::Ascii => "Payload, @tags allowed",
+                        SendMode::Hex => "Hex bytes, e.g. A0 B1 0F",
+                    })
+                    .font(egui::TextStyle::Monospace),
+            );
+            for v in [SendMode::Ascii, SendMode::Hex] {
+                ui.selectable_value(&mut value.send_mode, v, v.label());
+            }
+            egui::ComboBox::from_id_salt("macro_line_ending_combo")
+                .selected_text(value.line_ending.label())
+                .show_ui(ui, |ui| {
+                    for v in [
+                        LineEnding::None,
+                        LineEnding::Cr,
+                        LineEnding::Lf,
+                        LineEnding::CrLf,
+                    ] {
+                        ui.selectable_value(&mut value.line_ending, v, v.label());
+                    }
+                });
+            egui::ComboBox::from_id_salt("macro_scope_combo")
+                .selected_text(editor.scope.label())
+                .show_ui(ui, |ui| {
+                    for v in [MacroScope::Pane, MacroScope::Global] {
+                        ui.selectable_value(&mut editor.scope, v, v.label());
+                    }
+                });
+
+            let error = if editor.value.label.trim().is_empty() {
+                Some("Missing label".to_string())
+            } else if editor.value.send_mode == SendMode::Hex {
+                parse_hex_input(&editor.value.payload).err()
+            } else {
+                None
+            };
+            save = ui
+                .add_enabled(error.is_none(), egui::Button::new("Save"))
+                .clicked();
+            cancel = ui.button("Cancel").clicked();
+            if let Some(error) = error {
+                ui.label(
+                    RichText::new(error)
+                        .small()
+                        .color(Color32::from_rgb(190, 30, 30)),
+                );
+            }
+        });
+
+        if cancel {
+            self.macro_editor = None;
+        } else if save && let Some(editor) = self.macro_editor.take() {
+            let MacroEditor {
+                editing,
+                scope,
+                mut value,
+            } = editor;
+            value.label = value.label.trim().to_string();
+            if let Some((old_scope, idx)) = editing
+                && old_scope != scope
+            {
+                match old_scope {
+                    MacroScope::Global => macros.remove(idx),
+                    MacroScope::Pane => self.macros.remove(idx),
+                };
+            }
+            let list = match scope {
+                MacroScope::Global => macros,
+                MacroScope::Pane => &mut self.macros,
+            };
+            match editing {
+                Some((old_scope, idx)) if old_scope == scope => list[idx] = value,
+                _ => list.push(value),
+            }
+            action.settings_changed = true;
+        }
+    }
+
     fn status_bar(&self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             ui.label(RichText::new(&self.status).small());
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let log = self.log.lock().expect("Cannot get pane log lock");
                 ui.label(
-                    RichText::new(format!("RX: {} B   TX: {} B", self.bytes_rx, self.bytes_tx))
+                    RichText::new(format!("RX: {} B   TX: {} B", log.bytes_rx, log.bytes_tx))
                         .small(),
                 );
+                if let Some(recorder) = &log.recorder {
+                    ui.label(
+                        RichText::new(format!(
+                            "⏺ REC {}",
+                            ByteFormat::from(recorder.size() as u128).0
+                        ))
+                        .small()
+                        .color(Color32::from_rgb(190, 30, 30)),
+                    )
+                    .on_hover_text(recorder.path().display().to_string());
+                }
             });
         });
     }
@@ -1601,13 +2601,49 @@ impl eframe::App for GuiApp {
         // Best-effort: flush pending state to disk before workers shut down.
         self.save_state_now();
         for tab in &self.tabs {
-            for_each_leaf(&tab.root, &mut |s| {
-                let _ = s.serial.cmd_tx.send(SerialCommand::Shutdown);
-            });
+            for_each_leaf(&tab.root, &mut |s| s.shutdown());
         }
     }
 }
 
+fn spawn_interface(
+    session_type: SessionType,
+    auto_reconnect: bool,
+    egui_ctx: egui::Context,
+    log: SharedPaneLog,
+) -> PaneInterface {
+    let interface_type = match session_type {
+        SessionType::Serial => InterfaceType::Serial,
+        SessionType::Rtt => InterfaceType::Rtt,
+    };
+    let interface = PaneInterface::spawn(interface_type, egui_ctx, log);
+    interface.setup(auto_reconnect_setup(session_type, auto_reconnect));
+    interface
+}
+
+/// Setup that only changes the auto-reconnect, which doesn't reconnect.
+fn auto_reconnect_setup(session_type: SessionType, auto_reconnect: bool) -> InterfaceSetup {
+    match session_type {
+        SessionType::Serial => InterfaceSetup::Serial(SerialSetup {
+            auto_reconnect: Some(auto_reconnect),
+            ..SerialSetup::default()
+        }),
+        SessionType::Rtt => InterfaceSetup::Rtt(RttSetup {
+            auto_reconnect: Some(auto_reconnect),
+            ..RttSetup::default()
+        }),
+    }
+}
+
+/// Name the plugin engine gives to the plugin at `filepath`.
+fn plugin_name(filepath: &str) -> String {
+    Path::new(filepath)
+        .with_extension("")
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
 fn list_ports() -> Vec<String> {
     serialport::available_ports()
         .map(|ports| ports.into_iter().map(|p| p.port_name).collect())
@@ -1674,6 +2710,72 @@ fn parse_hex_input(s: &str) -> Result<Vec<u8>, String> {
     Ok(out)
 }
 
+/// The text of an entry as shown on the log, without timestamp and prefix.
+fn render_row(
+    ui: &mut egui::Ui,
+    id: u64,
+    row: &LogRow,
+    finder: Option<&Finder>,
+    show_timestamps: bool,
+    log_font_size: f32,
+) {
+    let ts = if show_timestamps {
+        row.timestamp.as_str()
+    } else {
+        ""
+    };
+
+    let (prefix, color) = match row.kind {
+        EntryKind::Rx => ("← ", Color32::from_rgb(10, 60, 170)),
+        EntryKind::Tx => ("→ ", Color32::from_rgb(15, 110, 35)),
+        EntryKind::System => ("· ", Color32::from_rgb(80, 80, 80)),
+        EntryKind::SystemError => ("! ", Color32::from_rgb(190, 30, 30)),
+    };
+
+    let mut spans = row.highlights.clone();
+    if let Some(finder) = finder {
+        let find_color = if finder.current_id() == Some(id) {
+            FIND_CURRENT_COLOR
+        } else {
+            FIND_MATCH_COLOR
+        };
+        spans.extend(
+            finder
+                .find_ranges(&row.body)
+                .into_iter()
+                .map(|range| (range, find_color)),
+        );
+    }
+
+    let font_id = FontId::monospace(log_font_size);
+    let mut job = LayoutJob::default();
+    job.append(
+        &format!("{}{}", ts, prefix),
+        0.0,
+        TextFormat::simple(font_id.clone(), color),
+    );
+    for (range, background) in backgrounds(row.body.len(), spans) {
+        job.append(
+            &row.body[range],
+            0.0,
+            TextFormat {
+                font_id: font_id.clone(),
+                color,
+                background: background.unwrap_or(Color32::TRANSPARENT),
+                ..Default::default()
+            },
+        );
+    }
+    ui.label(job);
+}
+
+fn entry_text(entry: &LogEntry, mode: DisplayMode) -> String {
+    match entry.kind {
+        EntryKind::System | EntryKind::SystemError => entry.message.clone().unwrap_or_default(),
+        EntryKind::Rx | EntryKind::Tx => format_bytes(&entry.bytes, mode),
+    }
+}
+
 fn format_bytes(bytes: &[u8], mode: DisplayMode) -> String {
     match mode {
         DisplayMode::Ascii => format_ascii(bytes),
@@ -1682,7 +2784,7 @@ fn format_bytes(bytes: &[u8], mode: DisplayMode) -> String {
     }
 }
 
-fn format_ascii(bytes: &[u8]) -> String {
+pub(super) fn format_ascii(bytes: &[u8]) -> String {
     let mut out = String::with_capacity(bytes.len());
     for &b in bytes {
         match b {
@@ -1696,7 +2798,7 @@ fn format_ascii(bytes: &[u8]) -> String {
     out
 }
 
-fn format_hex(bytes: &[u8]) -> String {
+pub(super) fn format_hex(bytes: &[u8]) -> String {
     let mut out = String::with_capacity(bytes.len() * 3);
     for (i, b) in bytes.iter().enumerate() {
         if i > 0 {
@@ -1707,6 +2809,22 @@ fn format_hex(bytes: &[u8]) -> String {
     out
 }
 
+/// An entry as a line of the saved and recorded log files.
+pub(super) fn log_line(entry: &LogEntry, body: &str) -> String {
+    let prefix = match entry.kind {
+        EntryKind::Rx => "RX ",
+        EntryKind::Tx => "TX ",
+        EntryKind::System => "-- ",
+        EntryKind::SystemError => "!! ",
+    };
+    format!(
+        "[{}] {}{}",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+        prefix,
+        body
+    )
+}
+
 fn format_hex_dump(bytes: &[u8]) -> String {
     let mut hex = String::with_capacity(bytes.len() * 3);
     let mut ascii = String::with_capacity(bytes.len());
@@ -1760,6 +2878,9 @@ struct PersistedState {
     log_font_size: f32,
     #[serde(default)]
     send_history: Vec<String>,
+    /// Macros shown on every pane.
+    #[serde(default)]
+    macros: Vec<Macro>,
     #[serde(default)]
     active_tab: usize,
     tabs: Vec<PersistedTab>,
@@ -1803,6 +2924,30 @@ struct PersistedSession {
     auto_scroll: bool,
     show_tx_in_log: bool,
     require_ctrl_enter_to_send: bool,
+    /// Plugin files loaded on the pane.
+    #[serde(default)]
+    plugins: Vec<String>,
+    #[serde(default)]
+    session_type: SessionType,
+    #[serde(default)]
+    rtt_target: String,
+    #[serde(default)]
+    rtt_channel: usize,
+    /// Probe selector, empty for the first probe found.
+    #[serde(default)]
+    rtt_probe: String,
+    #[serde(default = "default_auto_reconnect")]
+    auto_reconnect: bool,
+    #[serde(default)]
+    highlight_rules: Vec<HighlightRule>,
+    #[serde(default)]
+    macros: Vec<Macro>,
+    #[serde(default)]
+    record_format: RecordFormat,
+}
+
+fn default_auto_reconnect() -> bool {
+    true
 }
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
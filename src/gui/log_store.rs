@@ -0,0 +1,336 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// Entries per segment.
+const SEGMENT_LEN: usize = 4096;
+/// Segments read back from disk and kept in memory, the most recently used.
+const CACHED_SEGMENTS: usize = 8;
+/// Segments kept in memory when they can't be written to disk. Beyond it, the
+/// oldest of them are dropped.
+const MEMORY_SEGMENTS: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    Rx,
+    Tx,
+    System,
+    SystemError,
+}
+
+impl EntryKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            EntryKind::Rx => 0,
+            EntryKind::Tx => 1,
+            EntryKind::System => 2,
+            EntryKind::SystemError => 3,
+        }
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(EntryKind::Rx),
+            1 => Some(EntryKind::Tx),
+            2 => Some(EntryKind::System),
+            3 => Some(EntryKind::SystemError),
+            _ => None,
+        }
+    }
+}
+
+pub struct LogEntry {
+    pub timestamp: DateTime<Local>,
+    pub kind: EntryKind,
+    pub bytes: Vec<u8>,
+    pub message: Option<String>,
+}
+
+enum Segment {
+    Disk(PathBuf),
+    /// Kept in memory when it couldn't be written to disk.
+    Memory(Vec<LogEntry>),
+    /// Couldn't be written to disk and didn't fit in memory.
+    Dropped,
+}
+
+/// Log of a pane. Entries are grouped in segments of `SEGMENT_LEN`, and each
+/// full segment is written to a file, so only the last one and a few recently
+/// read ones are in memory. Each entry has an id that keeps growing, also
+/// across `clear`.
+pub struct LogStore {
+    dir: PathBuf,
+    /// Id of the first entry of `sealed[0]`, or of `tail` without sealed
+    /// segments.
+    first_id: u64,
+    sealed: Vec<Segment>,
+    tail: Vec<LogEntry>,
+    /// Sealed segments in memory, as (segment index, entries), the most
+    /// recently used at the back.
+    cache: VecDeque<(usize, Vec<LogEntry>)>,
+}
+
+impl LogStore {
+    /// The segment files go to a directory of the temp dir named after the
+    /// process and `name`, removed when the store is dropped.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir()
+            .join(format!("scope-gui-{}", std::process::id()))
+            .join(name);
+
+        Self {
+            dir,
+            first_id: 0,
+            sealed: Vec::new(),
+            tail: Vec::with_capacity(SEGMENT_LEN),
+            cache: VecDeque::new(),
+        }
+    }
+
+    pub fn first_id(&self) -> u64 {
+        self.first_id
+    }
+
+    /// Id of the next entry pushed.
+    pub fn end_id(&self) -> u64 {
+        self.first_id + self.len() as u64
+    }
+
+    pub fn len(&self) -> usize {
+        self.sealed.len() * SEGMENT_LEN + self.tail.len()
+    }
+
+    /// Fails when a full segment can't be written to disk. The segment is
+    /// kept in memory then, up to `MEMORY_SEGMENTS` of them, after which the
+    /// entries of the oldest one are lost.
+    pub fn push(&mut self, entry: LogEntry) -> Result<(), String> {
+        self.tail.push(entry);
+        if self.tail.len() < SEGMENT_LEN {
+            return Ok(());
+        }
+
+        let index = self.sealed.len();
+        let entries = std::mem::replace(&mut self.tail, Vec::with_capacity(SEGMENT_LEN));
+        let path = self.dir.join(format!("{}.seg", index));
+        let res = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&path, encode(&entries)))
+            .map_err(|err| format!("Cannot write log segment {}: {}", path.display(), err));
+
+        match res {
+            Ok(()) => {
+                self.sealed.push(Segment::Disk(path));
+                self.cache_segment(index, entries);
+                Ok(())
+            }
+            Err(err) => {
+                self.sealed.push(Segment::Memory(entries));
+                let is_memory = |segment: &Segment| matches!(segment, Segment::Memory(_));
+                if self.sealed.iter().filter(|s| is_memory(s)).count() > MEMORY_SEGMENTS
+                    && let Some(oldest) = self.sealed.iter_mut().find(|s| is_memory(s))
+                {
+                    *oldest = Segment::Dropped;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Reads the entry back from disk when its segment isn't in memory.
+    pub fn get(&mut self, id: u64) -> Option<&LogEntry> {
+        let idx = usize::try_from(id.checked_sub(self.first_id)?).ok()?;
+        let (index, offset) = (idx / SEGMENT_LEN, idx % SEGMENT_LEN);
+        if let Some(Segment::Disk(path)) = self.sealed.get(index) {
+            match self.cache.iter().position(|(i, _)| *i == index) {
+                Some(pos) => {
+                    let cached = self.cache.remove(pos)?;
+                    self.cache.push_back(cached);
+                }
+                None => {
+                    let entries = decode(&std::fs::read(path).ok()?)?;
+                    self.cache_segment(index, entries);
+                }
+            }
+        }
+
+        self.segment(index)?.get(offset)
+    }
+
+    fn segment(&self, index: usize) -> Option<&[LogEntry]> {
+        if index == self.sealed.len() {
+            return Some(&self.tail);
+        }
+
+        match self.sealed.get(index)? {
+            Segment::Memory(entries) => Some(entries),
+            Segment::Dropped => None,
+            Segment::Disk(_) => self
+                .cache
+                .iter()
+                .find(|(i, _)| *i == index)
+                .map(|(_, entries)| entries.as_slice()),
+        }
+    }
+
+    /// Drops every entry. The next one keeps counting from the last id.
+    pub fn clear(&mut self) {
+        self.first_id = self.end_id();
+        self.sealed.clear();
+        self.tail.clear();
+        self.cache.clear();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+
+    fn cache_segment(&mut self, index: usize, entries: Vec<LogEntry>) {
+        if self.cache.len() == CACHED_SEGMENTS {
+            self.cache.pop_front();
+        }
+        self.cache.push_back((index, entries));
+    }
+}
+
+impl Drop for LogStore {
+    /// Also removes the directory of the process once its last store is gone.
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+        if let Some(parent) = self.dir.parent() {
+            let _ = std::fs::remove_dir(parent);
+        }
+    }
+}
+
+/// Each entry as: timestamp in microseconds (i64), kind (u8), bytes length
+/// (u32) and bytes, message length (u32, `u32::MAX` for none) and message.
+fn encode(entries: &[LogEntry]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        out.extend_from_slice(&entry.timestamp.timestamp_micros().to_le_bytes());
+        out.push(entry.kind.to_u8());
+        out.extend_from_slice(&(entry.bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(&entry.bytes);
+        match &entry.message {
+            Some(message) => {
+                out.extend_from_slice(&(message.len() as u32).to_le_bytes());
+                out.extend_from_slice(message.as_bytes());
+            }
+            None => out.extend_from_slice(&u32::MAX.to_le_bytes()),
+        }
+    }
+    out
+}
+
+fn decode(mut data: &[u8]) -> Option<Vec<LogEntry>> {
+    fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        let (head, rest) = data.split_at_checked(len)?;
+        *data = rest;
+        Some(head)
+    }
+    fn take_u32(data: &mut &[u8]) -> Option<u32> {
+        Some(u32::from_le_bytes(take(data, 4)?.try_into().ok()?))
+    }
+
+    let mut entries = Vec::with_capacity(SEGMENT_LEN);
+    while !data.is_empty() {
+        let micros = i64::from_le_bytes(take(&mut data, 8)?.try_into().ok()?);
+        let timestamp = DateTime::from_timestamp_micros(micros)?.with_timezone(&Local);
+        let kind = EntryKind::from_u8(take(&mut data, 1)?[0])?;
+        let len = take_u32(&mut data)? as usize;
+        let bytes = take(&mut data, len)?.to_vec();
+        let message = match take_u32(&mut data)? {
+            u32::MAX => None,
+            len => Some(String::from_utf8(take(&mut data, len as usize)?.to_vec()).ok()?),
+        };
+        entries.push(LogEntry {
+            timestamp,
+            kind,
+            bytes,
+            message,
+        });
+    }
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CACHED_SEGMENTS, EntryKind, LogEntry, LogStore, MEMORY_SEGMENTS, SEGMENT_LEN};
+    use chrono::Local;
+
+    /// Received data on even entries and logs on odd ones.
+    fn entry(n: usize) -> LogEntry {
+        let is_log = n % 2 == 1;
+        LogEntry {
+            timestamp: Local::now(),
+            kind: if is_log {
+                EntryKind::System
+            } else {
+                EntryKind::Rx
+            },
+            bytes: n.to_string().into_bytes(),
+            message: is_log.then(|| format!("message {}", n)),
+        }
+    }
+
+    fn check(store: &mut LogStore, id: u64, n: usize) {
+        let expected = entry(n);
+        let entry = store.get(id).unwrap();
+        assert!(entry.kind == expected.kind);
+        assert_eq!(entry.bytes, expected.bytes);
+        assert_eq!(entry.message, expected.message);
+    }
+
+    #[test]
+    fn test_spill_and_read_back() {
+        let mut store = LogStore::new("test_spill_and_read_back");
+        let total = SEGMENT_LEN * (CACHED_SEGMENTS + 2) + 10;
+        for n in 0..total {
+            store.push(entry(n)).unwrap();
+        }
+        assert_eq!(store.len(), total);
+        assert!(store.dir.join("0.seg").exists());
+
+        // The first segments were dropped from the cache and come from disk.
+        check(&mut store, 0, 0);
+        check(&mut store, 1, 1);
+        check(&mut store, SEGMENT_LEN as u64 + 5, SEGMENT_LEN + 5);
+        check(&mut store, total as u64 - 1, total - 1);
+        assert!(store.get(total as u64).is_none());
+
+        let dir = store.dir.clone();
+        store.clear();
+        assert_eq!(store.len(), 0);
+        assert_eq!(store.first_id(), total as u64);
+        assert!(!dir.exists());
+
+        store.push(entry(0)).unwrap();
+        check(&mut store, total as u64, 0);
+        assert!(store.get(0).is_none());
+
+        drop(store);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_drop_segments_not_written() {
+        let mut store = LogStore::new("test_drop_segments_not_written");
+        // A directory can't be created inside a file, not even by root.
+        let file =
+            std::env::temp_dir().join(format!("scope-gui-test-{}-not-a-dir", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        store.dir = file.join("pane");
+
+        let total = SEGMENT_LEN * (MEMORY_SEGMENTS + 1);
+        for n in 0..total {
+            let res = store.push(entry(n));
+            assert_eq!(res.is_err(), (n + 1) % SEGMENT_LEN == 0);
+        }
+        assert_eq!(store.len(), total);
+
+        // The oldest segment was dropped, the others are still in memory.
+        assert!(store.get(0).is_none());
+        assert!(store.get(SEGMENT_LEN as u64 - 1).is_none());
+        check(&mut store, SEGMENT_LEN as u64, SEGMENT_LEN);
+        check(&mut store, total as u64 - 1, total - 1);
+
+        drop(store);
+        std::fs::remove_file(&file).unwrap();
+    }
+}
@@ -1,9 +1,24 @@
 mod app;
-mod serial_worker;
+mod log_store;
+mod pane_interface;
+mod pane_log;
+mod plugin_host;
+mod recorder;
+mod search;
 
+use crate::infra::tags::TagList;
 use app::GuiApp;
+use std::path::PathBuf;
+
+pub fn run_gui(tag_file: PathBuf) -> Result<(), String> {
+    let tag_list = TagList::new(tag_file.clone()).map_err(|err| {
+        format!(
+            "Failed to read or parse tag file at {}: {}",
+            tag_file.display(),
+            err
+        )
+    })?;
 
-pub fn run_gui() -> Result<(), String> {
     let viewport = egui::ViewportBuilder::default()
         .with_title("Scope Monitor")
         .with_inner_size([1100.0, 720.0])
@@ -17,7 +32,7 @@ pub fn run_gui() -> Result<(), String> {
     eframe::run_native(
         "Scope Monitor",
         options,
-        Box::new(|cc| Ok(Box::new(GuiApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(GuiApp::new(cc, tag_list)))),
     )
     .map_err(|e| format!("GUI error: {}", e))
 }
@@ -0,0 +1,254 @@
+use super::pane_log::SharedPaneLog;
+use super::plugin_host::PluginHost;
+use crate::graphics::buffer::{Buffer, BufferLine};
+use crate::infra::logger::{LogMessage, Logger};
+use crate::infra::messages::TimedBytes;
+use crate::infra::mpmc::{Channel, Consumer, Producer};
+use crate::infra::task::Shared;
+use crate::interfaces::rtt_if::{RttConnections, RttSetup};
+use crate::interfaces::serial_if::{SerialConnections, SerialSetup};
+use crate::interfaces::{Interface, InterfaceSetup, InterfaceStatus, InterfaceTask, InterfaceType};
+use crate::plugin::engine::PluginEngineConnections;
+use chrono::Local;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// Plugins screen buffer size, in lines.
+const BUFFER_CAPACITY: usize = 5000;
+/// Sleep of the serial interface loop when there's no data, in microseconds.
+const SERIAL_LATENCY_US: u64 = 100;
+/// Sleep of the RTT interface loop when there's no data, in milliseconds.
+const RTT_LATENCY_MS: u64 = 5;
+/// Longest wait of the collector for received data before it checks the
+/// sent data and the logs.
+const COLLECT_INTERVAL: Duration = Duration::from_millis(20);
+
+pub enum PaneEvent {
+    Rx(Arc<TimedBytes>),
+    /// Data sent by the pane or by the plugins.
+    Tx(Arc<TimedBytes>),
+    Log(LogMessage),
+}
+
+/// The interface task of a GUI pane, the same the TUI runs, with the pane's
+/// plugin engine wired to it. A collector thread moves its data and logs to
+/// the pane's log as they arrive, whether the pane is drawn or not.
+pub struct PaneInterface {
+    interface: InterfaceTask,
+    plugins: PluginHost,
+    tx: Producer<Arc<TimedBytes>>,
+    status: InterfaceStatus,
+}
+
+/// The channels drained by the collector thread of a pane.
+struct Collector {
+    rx: Consumer<Arc<TimedBytes>>,
+    tx_echo: Consumer<Arc<TimedBytes>>,
+    log_receiver: Receiver<LogMessage>,
+    buffer: Arc<RwLock<Buffer>>,
+    log: SharedPaneLog,
+    egui_ctx: egui::Context,
+}
+
+impl PaneInterface {
+    pub fn spawn(
+        interface_type: InterfaceType,
+        egui_ctx: egui::Context,
+        log: SharedPaneLog,
+    ) -> Self {
+        let (logger, log_receiver) = Logger::new("gui".to_string());
+        let mut tx_channel = Channel::default();
+        let mut rx_channel = Channel::default();
+
+        let interface_tx = tx_channel.new_consumer();
+        let engine_tx = tx_channel.new_consumer();
+        let tx_echo = tx_channel.new_consumer();
+        let engine_rx = rx_channel.new_consumer();
+        let rx = rx_channel.new_consumer();
+
+        let tx_channel = Arc::new(tx_channel);
+        let rx_channel = Arc::new(rx_channel);
+
+        let (interface_cmd_sender, interface_cmd_receiver) = channel();
+        let (plugin_cmd_sender, plugin_cmd_receiver) = channel();
+
+        let interface = match interface_type {
+            InterfaceType::Serial => InterfaceTask::spawn_serial_interface(
+                SerialConnections::new(
+                    logger.clone().with_source("serial".to_string()),
+                    interface_tx,
+                    rx_channel.new_producer(),
+                    plugin_cmd_sender.clone(),
+                    SERIAL_LATENCY_US,
+                ),
+                interface_cmd_sender.clone(),
+                interface_cmd_receiver,
+                SerialSetup::default(),
+            ),
+            InterfaceType::Rtt => InterfaceTask::spawn_rtt_interface(
+                RttConnections::new(
+                    logger.clone().with_source("rtt".to_string()),
+                    interface_tx,
+                    rx_channel.new_producer(),
+                    plugin_cmd_sender.clone(),
+                    RTT_LATENCY_MS,
+                ),
+                interface_cmd_sender.clone(),
+                interface_cmd_receiver,
+                RttSetup::default(),
+            ),
+        };
+
+        let buffer = Arc::new(RwLock::new(Buffer::new(BUFFER_CAPACITY)));
+        let latency = match interface_type {
+            InterfaceType::Serial => SERIAL_LATENCY_US,
+            InterfaceType::Rtt => RTT_LATENCY_MS,
+        };
+        let connections = PluginEngineConnections::new(
+            logger.with_source("plugin".to_string()),
+            tx_channel.clone().new_producer(),
+            engine_tx,
+            engine_rx,
+            interface.shared_ref(),
+            Shared::new(buffer.clone()),
+            latency,
+            interface_type,
+            interface_cmd_sender,
+        );
+        let plugins = PluginHost::spawn(connections, plugin_cmd_sender, plugin_cmd_receiver);
+
+        let collector = Collector {
+            rx,
+            tx_echo,
+            log_receiver,
+            buffer,
+            log,
+            egui_ctx,
+        };
+        thread::Builder::new()
+            .name("gui-pane-collector".to_string())
+            .spawn(move || collector.run())
+            .expect("failed to spawn GUI pane collector thread");
+
+        Self {
+            interface,
+            plugins,
+            tx: tx_channel.new_producer(),
+            status: InterfaceStatus::Disconnected,
+        }
+    }
+
+    pub fn plugins(&self) -> &PluginHost {
+        &self.plugins
+    }
+
+    /// The status seen by the last `poll_status`.
+    pub fn status(&self) -> InterfaceStatus {
+        self.status
+    }
+
+    pub fn description(&self) -> String {
+        self.interface.description()
+    }
+
+    pub fn setup(&self, setup: InterfaceSetup) {
+        self.interface.setup(setup);
+    }
+
+    pub fn connect(&self) {
+        self.interface.connect();
+    }
+
+    pub fn disconnect(&self) {
+        self.interface.disconnect();
+    }
+
+    /// Queues `data` to the interface. The plugins see it and it comes back
+    /// as a `PaneEvent::Tx`.
+    pub fn send(&self, data: Vec<u8>) {
+        self.tx.produce(Arc::new(TimedBytes {
+            timestamp: Local::now(),
+            message: data,
+        }));
+    }
+
+    /// The new status of the interface, if it changed since the last call.
+    pub fn poll_status(&mut self) -> Option<InterfaceStatus> {
+        let status = self.interface.status();
+        if status == self.status {
+            return None;
+        }
+
+        self.status = status;
+        Some(status)
+    }
+
+    pub fn shutdown(&self) {
+        self.interface.exit();
+        self.plugins.shutdown();
+    }
+}
+
+impl Collector {
+    /// Ends with the interface task, which holds the last producer of the rx
+    /// channel.
+    fn run(self) {
+        loop {
+            let (first, is_closed) = match self.rx.recv_timeout(COLLECT_INTERVAL) {
+                Ok(bytes) => (Some(bytes), false),
+                Err(RecvTimeoutError::Timeout) => (None, false),
+                Err(RecvTimeoutError::Disconnected) => (None, true),
+            };
+
+            let events = self.collect(first);
+            if !events.is_empty() {
+                self.log
+                    .lock()
+                    .expect("Cannot get pane log lock")
+                    .push_events(events);
+                self.egui_ctx.request_repaint();
+            }
+
+            if is_closed {
+                break;
+            }
+        }
+    }
+
+    /// The data and logs received so far, in order. They also go to the
+    /// plugins screen buffer.
+    fn collect(&self, first: Option<Arc<TimedBytes>>) -> Vec<PaneEvent> {
+        let mut events = first.into_iter().map(PaneEvent::Rx).collect::<Vec<_>>();
+        while let Ok(bytes) = self.rx.try_recv() {
+            events.push(PaneEvent::Rx(bytes));
+        }
+        while let Ok(bytes) = self.tx_echo.try_recv() {
+            events.push(PaneEvent::Tx(bytes));
+        }
+        while let Ok(log) = self.log_receiver.try_recv() {
+            events.push(PaneEvent::Log(log));
+        }
+        events.sort_by_key(|event| match event {
+            PaneEvent::Rx(bytes) | PaneEvent::Tx(bytes) => bytes.timestamp,
+            PaneEvent::Log(log) => log.timestamp,
+        });
+
+        let mut buffer = self
+            .buffer
+            .write()
+            .expect("Cannot get buffer lock for write");
+        for event in &events {
+            *buffer += match event {
+                PaneEvent::Rx(bytes) => BufferLine::new_rx(bytes.timestamp, bytes.message.clone()),
+                PaneEvent::Tx(bytes) => BufferLine::new_tx(bytes.timestamp, bytes.message.clone()),
+                PaneEvent::Log(log) => {
+                    BufferLine::new_log(log.timestamp, log.level, log.message.as_bytes().to_vec())
+                }
+            };
+        }
+
+        events
+    }
+}
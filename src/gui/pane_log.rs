@@ -0,0 +1,172 @@
+use super::log_store::{EntryKind, LogEntry, LogStore};
+use super::pane_interface::PaneEvent;
+use super::recorder::{RecordFormat, Recorder};
+use crate::infra::ByteFormat;
+use crate::infra::logger::LogLevel;
+use chrono::Local;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub type SharedPaneLog = Arc<Mutex<PaneLog>>;
+
+/// The log of a pane and its recording. The pane's interface feeds it as the
+/// data arrives, so it keeps storing and recording while the pane isn't
+/// drawn, like on a background tab or with the window minimized.
+pub struct PaneLog {
+    pub store: LogStore,
+    /// Streams every entry to a file while recording.
+    pub recorder: Option<Recorder>,
+    /// Show the sent data on the log. It's recorded anyway.
+    pub show_tx: bool,
+    pub bytes_rx: u64,
+    pub bytes_tx: u64,
+}
+
+impl PaneLog {
+    pub fn new_shared(name: &str, show_tx: bool) -> SharedPaneLog {
+        Arc::new(Mutex::new(Self {
+            store: LogStore::new(name),
+            recorder: None,
+            show_tx,
+            bytes_rx: 0,
+            bytes_tx: 0,
+        }))
+    }
+
+    /// Stores the events of the interface, in order, and flushes the
+    /// recording, so the file is never more than a batch behind.
+    pub fn push_events(&mut self, events: Vec<PaneEvent>) {
+        for event in events {
+            match event {
+                PaneEvent::Rx(data) => {
+                    self.bytes_rx += data.message.len() as u64;
+                    self.push(LogEntry {
+                        timestamp: data.timestamp,
+                        kind: EntryKind::Rx,
+                        bytes: data.message.clone(),
+                        message: None,
+                    });
+                }
+                PaneEvent::Tx(data) => {
+                    self.bytes_tx += data.message.len() as u64;
+                    let entry = LogEntry {
+                        timestamp: data.timestamp,
+                        kind: EntryKind::Tx,
+                        bytes: data.message.clone(),
+                        message: None,
+                    };
+                    if self.show_tx {
+                        self.push(entry);
+                    } else {
+                        // Still recorded, the file keeps both directions.
+                        self.record(&entry);
+                    }
+                }
+                PaneEvent::Log(log) => {
+                    let kind = match log.level {
+                        LogLevel::Error => EntryKind::SystemError,
+                        _ => EntryKind::System,
+                    };
+                    self.push(LogEntry {
+                        timestamp: log.timestamp,
+                        kind,
+                        bytes: Vec::new(),
+                        message: Some(log.message),
+                    });
+                }
+            }
+        }
+
+        self.flush_recording();
+    }
+
+    pub fn push_system(&mut self, msg: String) {
+        self.push(LogEntry {
+            timestamp: Local::now(),
+            kind: EntryKind::System,
+            bytes: Vec::new(),
+            message: Some(msg),
+        });
+        self.flush_recording();
+    }
+
+    pub fn push_system_error(&mut self, msg: String) {
+        self.push(LogEntry {
+            timestamp: Local::now(),
+            kind: EntryKind::SystemError,
+            bytes: Vec::new(),
+            message: Some(msg),
+        });
+        self.flush_recording();
+    }
+
+    /// Drops every entry and resets the counters.
+    pub fn clear(&mut self) {
+        self.store.clear();
+        self.bytes_rx = 0;
+        self.bytes_tx = 0;
+    }
+
+    /// Starts recording to `path`, with the extension of `format`, or stops
+    /// the current recording.
+    pub fn toggle_recording(&mut self, path: PathBuf, format: RecordFormat) {
+        if let Some(mut recorder) = self.recorder.take() {
+            match recorder.flush() {
+                Ok(()) => self.push_system(format!(
+                    "Recorded {} to {}",
+                    ByteFormat::from(recorder.size() as u128).0,
+                    recorder.path().display()
+                )),
+                Err(err) => self.push_system_error(format!(
+                    "Failed to record to {}: {}",
+                    recorder.path().display(),
+                    err
+                )),
+            }
+            return;
+        }
+
+        match Recorder::start(path, format) {
+            Ok(recorder) => {
+                let abs = std::fs::canonicalize(recorder.path())
+                    .unwrap_or_else(|_| recorder.path().to_path_buf());
+                self.recorder = Some(recorder);
+                self.push_system(format!("Recording to {}...", abs.display()));
+            }
+            Err(err) => self.push_system_error(format!("Failed to start recording: {}", err)),
+        }
+    }
+
+    fn push(&mut self, entry: LogEntry) {
+        self.record(&entry);
+        if let Err(err) = self.store.push(entry) {
+            self.push_system_error(err);
+        }
+    }
+
+    fn record(&mut self, entry: &LogEntry) {
+        if let Some(recorder) = &mut self.recorder
+            && let Err(err) = recorder.write(entry)
+        {
+            self.fail_recording(err);
+        }
+    }
+
+    fn flush_recording(&mut self) {
+        if let Some(recorder) = &mut self.recorder
+            && let Err(err) = recorder.flush()
+        {
+            self.fail_recording(err);
+        }
+    }
+
+    fn fail_recording(&mut self, err: String) {
+        if let Some(recorder) = self.recorder.take() {
+            self.push_system_error(format!(
+                "Recording to {} stopped: {}",
+                recorder.path().display(),
+                err
+            ));
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::plugin::engine::{
+    PluginEngine, PluginEngineCommand, PluginEngineConnections, PluginInfo,
+};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// The plugin engine of a GUI pane. Plugins see the data of the pane's
+/// interface and their logs are shown on the pane.
+pub struct PluginHost {
+    engine: PluginEngine,
+    cmd_sender: Sender<PluginEngineCommand>,
+}
+
+impl PluginHost {
+    pub fn spawn(
+        connections: PluginEngineConnections,
+        cmd_sender: Sender<PluginEngineCommand>,
+        cmd_receiver: Receiver<PluginEngineCommand>,
+    ) -> Self {
+        let engine =
+            PluginEngine::spawn_plugin_engine(connections, cmd_sender.clone(), cmd_receiver);
+
+        Self { engine, cmd_sender }
+    }
+
+    pub fn send(&self, cmd: PluginEngineCommand) {
+        let _ = self.cmd_sender.send(cmd);
+    }
+
+    /// Loaded plugins, sorted by name.
+    pub fn plugins(&self) -> Vec<PluginInfo> {
+        self.engine
+            .shared_ref()
+            .read()
+            .map(|shared| shared.plugins.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn shutdown(&self) {
+        self.send(PluginEngineCommand::Exit);
+    }
+}
+
+/// Parses a line of the pane's plugin command entry. Takes the same commands
+/// as the TUI, with or without the leading `!`: `plugin load <file>`,
+/// `plugin allow <name> <capability>`, ... or `<name> <command> [args]`.
+pub fn parse_command(command_line: &str) -> Result<PluginEngineCommand, String> {
+    let args = command_line
+        .trim()
+        .trim_start_matches('!')
+        .split_whitespace()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<_>>();
+    let arg = |idx: usize| {
+        args.get(idx)
+            .cloned()
+            .ok_or_else(|| "Insufficient arguments".to_string())
+    };
+
+    let Some(first) = args.first() else {
+        return Err("Empty command".to_string());
+    };
+
+    if first != "plugin" {
+        return Ok(PluginEngineCommand::UserCommand {
+            plugin_name: first.clone(),
+            command: arg(1)?,
+            options: args[2..].to_vec(),
+        });
+    }
+
+    let cmd = match arg(1)?.as_str() {
+        "list" => PluginEngineCommand::ListPlugins,
+        "stats" => PluginEngineCommand::PluginStats {
+            plugin_name: args.get(2).cloned(),
+        },
+        "load" | "reload" => PluginEngineCommand::LoadPlugin { filepath: arg(2)? },
+        "unload" => PluginEngineCommand::UnloadPlugin {
+            plugin_name: arg(2)?,
+        },
+        "help" => PluginEngineCommand::PluginHelp {
+            plugin_name: arg(2)?,
+        },
+        "stop" => PluginEngineCommand::StopPlugin {
+            plugin_name: Some(arg(2)?),
+            command: args.get(3).cloned(),
+        },
+        command @ ("allow" | "deny") => PluginEngineCommand::SetPermission {
+            plugin_name: arg(2)?,
+            capability: arg(3)?,
+            allow: command == "allow",
+        },
+        command @ ("watch" | "unwatch") => PluginEngineCommand::WatchPlugin {
+            plugin_name: arg(2)?,
+            enable: command == "watch",
+        },
+        _ => {
+            return Err(
+                "Invalid command. Please, choose one of these options: load, reload, unload, stop, list, stats, help, watch, unwatch, allow, deny"
+                    .to_string(),
+            );
+        }
+    };
+
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_command;
+    use crate::plugin::engine::PluginEngineCommand;
+
+    #[test]
+    fn test_parse_command() {
+        assert!(matches!(
+            parse_command("!plugin load plugins/echo.lua"),
+            Ok(PluginEngineCommand::LoadPlugin { filepath }) if filepath == "plugins/echo.lua"
+        ));
+        assert!(matches!(
+            parse_command("plugin deny echo shell"),
+            Ok(PluginEngineCommand::SetPermission { plugin_name, capability, allow: false })
+                if plugin_name == "echo" && capability == "shell"
+        ));
+        assert!(matches!(
+            parse_command("echo hello a b"),
+            Ok(PluginEngineCommand::UserCommand { plugin_name, command, options })
+                if plugin_name == "echo" && command == "hello" && options == ["a", "b"]
+        ));
+        assert!(parse_command("echo").is_err());
+        assert!(parse_command("plugin allow echo").is_err());
+        assert!(parse_command("plugin foo").is_err());
+        assert!(parse_command("  ").is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use super::app::{format_ascii, format_hex, log_line};
+use super::log_store::{EntryKind, LogEntry};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordFormat {
+    /// Timestamped lines with the data as escaped ASCII.
+    #[default]
+    Text,
+    /// Timestamped lines with the data as hex bytes.
+    Hex,
+    /// One JSON object per entry.
+    Jsonl,
+}
+
+impl RecordFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            RecordFormat::Text => "Text",
+            RecordFormat::Hex => "Hex",
+            RecordFormat::Jsonl => "JSONL",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            RecordFormat::Text | RecordFormat::Hex => "txt",
+            RecordFormat::Jsonl => "jsonl",
+        }
+    }
+
+    fn line(self, entry: &LogEntry) -> String {
+        let message = entry.message.as_deref().unwrap_or_default();
+        match self {
+            RecordFormat::Text | RecordFormat::Hex => {
+                let body = match entry.kind {
+                    EntryKind::System | EntryKind::SystemError => message.to_string(),
+                    EntryKind::Rx | EntryKind::Tx if self == RecordFormat::Hex => {
+                        format_hex(&entry.bytes)
+                    }
+                    EntryKind::Rx | EntryKind::Tx => format_ascii(&entry.bytes),
+                };
+                log_line(entry, &body)
+            }
+            RecordFormat::Jsonl => {
+                let timestamp = entry.timestamp.to_rfc3339();
+                let value = match entry.kind {
+                    EntryKind::Rx | EntryKind::Tx => serde_json::json!({
+                        "timestamp": timestamp,
+                        "kind": if entry.kind == EntryKind::Rx { "rx" } else { "tx" },
+                        "text": String::from_utf8_lossy(&entry.bytes),
+                        "hex": format_hex(&entry.bytes),
+                    }),
+                    EntryKind::System | EntryKind::SystemError => serde_json::json!({
+                        "timestamp": timestamp,
+                        "kind": if entry.kind == EntryKind::System { "log" } else { "error" },
+                        "message": message,
+                    }),
+                };
+                value.to_string()
+            }
+        }
+    }
+}
+
+/// Streams the entries of a pane to a file as they arrive.
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordFormat,
+    writer: BufWriter<File>,
+    size: u64,
+}
+
+impl Recorder {
+    /// Creates `path` with the extension of `format`.
+    pub fn start(path: PathBuf, format: RecordFormat) -> Result<Self, String> {
+        let path = path.with_extension(format.extension());
+        let file = File::create(&path)
+            .map_err(|err| format!("Cannot create {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            path,
+            format,
+            writer: BufWriter::new(file),
+            size: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes written so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn write(&mut self, entry: &LogEntry) -> Result<(), String> {
+        let mut line = self.format.line(entry);
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .map_err(|err| err.to_string())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Called by `PaneLog::push_events`, on the collector thread, once per
+    /// batch of events, so the file is never more than a batch behind.
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordFormat;
+    use crate::gui::log_store::{EntryKind, LogEntry};
+    use chrono::{Local, TimeZone};
+
+    fn entry(kind: EntryKind, bytes: &[u8], message: Option<&str>) -> LogEntry {
+        LogEntry {
+            timestamp: Local.with_ymd_and_hms(2024, 5, 17, 8, 30, 0).unwrap(),
+            kind,
+            bytes: bytes.to_vec(),
+            message: message.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_record_lines() {
+        let rx = entry(EntryKind::Rx, b"ok\r\n", None);
+        let error = entry(EntryKind::SystemError, b"", Some("lost \"COM3\""));
+
+        assert_eq!(
+            RecordFormat::Text.line(&rx),
+            "[2024-05-17 08:30:00.000] RX ok\\r\\n"
+        );
+        assert_eq!(
+            RecordFormat::Hex.line(&rx),
+            "[2024-05-17 08:30:00.000] RX 6F 6B 0D 0A"
+        );
+        assert_eq!(
+            RecordFormat::Hex.line(&error),
+            "[2024-05-17 08:30:00.000] !! lost \"COM3\""
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&RecordFormat::Jsonl.line(&rx)).unwrap();
+        assert_eq!(json["kind"], "rx");
+        assert_eq!(json["text"], "ok\r\n");
+        assert_eq!(json["hex"], "6F 6B 0D 0A");
+        let json: serde_json::Value =
+            serde_json::from_str(&RecordFormat::Jsonl.line(&error)).unwrap();
+        assert_eq!(json["kind"], "error");
+        assert_eq!(json["message"], "lost \"COM3\"");
+    }
+}
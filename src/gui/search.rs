@@ -0,0 +1,367 @@
+use egui::Color32;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Text searched on the log lines, as typed on the find bar or on a
+/// highlight rule.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pattern {
+    pub text: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    #[serde(default)]
+    pub is_case_sensitive: bool,
+}
+
+impl Pattern {
+    /// `None` for an empty pattern, which matches nothing.
+    pub fn compile(&self) -> Result<Option<Regex>, String> {
+        if self.text.is_empty() {
+            return Ok(None);
+        }
+
+        let text = if self.is_regex {
+            self.text.clone()
+        } else {
+            regex::escape(&self.text)
+        };
+
+        RegexBuilder::new(&text)
+            .case_insensitive(!self.is_case_sensitive)
+            .build()
+            .map(Some)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Paints the background of the parts of a log line that match `pattern`.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: Pattern,
+    /// sRGB colour of the background.
+    pub color: [u8; 3],
+}
+
+impl Default for HighlightRule {
+    fn default() -> Self {
+        Self {
+            pattern: Pattern::default(),
+            color: [255, 200, 120],
+        }
+    }
+}
+
+/// Compiled highlight rules. Rules with an empty or invalid pattern are left
+/// out.
+#[derive(Default)]
+pub struct Highlighter {
+    rules: Vec<(Regex, Color32)>,
+}
+
+impl Highlighter {
+    pub fn new(rules: &[HighlightRule]) -> Self {
+        let rules = rules
+            .iter()
+            .filter_map(|rule| {
+                let regex = rule.pattern.compile().ok().flatten()?;
+                let [r, g, b] = rule.color;
+                Some((regex, Color32::from_rgb(r, g, b)))
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Ranges of `text` to paint, the later rules over the earlier ones.
+    pub fn spans(&self, text: &str) -> Vec<(Range<usize>, Color32)> {
+        self.rules
+            .iter()
+            .flat_map(|(regex, color)| {
+                regex
+                    .find_iter(text)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| (m.range(), *color))
+            })
+            .collect()
+    }
+}
+
+/// Find bar matches over the log lines. Each line has an id that grows as
+/// lines are added, so the lines already checked aren't checked again when
+/// new ones arrive or old ones are dropped.
+pub struct Finder {
+    pattern: Pattern,
+    regex: Result<Option<Regex>, String>,
+    /// Ids of the matching lines, in order.
+    matches: VecDeque<u64>,
+    /// Id of the next line to check.
+    next_id: u64,
+    /// Id after the last line, as of the last scan.
+    end_id: u64,
+    /// Index of the current match in `matches`.
+    current: Option<usize>,
+}
+
+impl Default for Finder {
+    fn default() -> Self {
+        Self {
+            pattern: Pattern::default(),
+            regex: Ok(None),
+            matches: VecDeque::new(),
+            next_id: 0,
+            end_id: 0,
+            current: None,
+        }
+    }
+}
+
+impl Finder {
+    /// Starts over when `pattern` isn't the current one.
+    pub fn set_pattern(&mut self, pattern: &Pattern) {
+        if *pattern == self.pattern {
+            return;
+        }
+
+        self.pattern = pattern.clone();
+        self.regex = pattern.compile();
+        self.restart();
+    }
+
+    /// Checks every line again, like when the way they're shown changed.
+    pub fn restart(&mut self) {
+        self.matches.clear();
+        self.next_id = 0;
+        self.current = None;
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.regex.as_ref().err().map(|err| err.as_str())
+    }
+
+    pub fn is_active(&self) -> bool {
+        matches!(self.regex, Ok(Some(_)))
+    }
+
+    /// Checks up to `max` of the lines not checked yet, the rest are left to
+    /// the next scans. The lines go from `first_id` to `first_id + len` and
+    /// `text` gives the one at an index from `first_id`.
+    pub fn scan(
+        &mut self,
+        first_id: u64,
+        len: usize,
+        max: usize,
+        mut text: impl FnMut(usize) -> String,
+    ) {
+        let dropped = self.matches.iter().take_while(|id| **id < first_id).count();
+        self.matches.drain(..dropped);
+        self.current = self
+            .current
+            .and_then(|current| current.checked_sub(dropped));
+
+        self.end_id = first_id + len as u64;
+        let Ok(Some(regex)) = &self.regex else {
+            self.next_id = self.end_id;
+            return;
+        };
+
+        let start = self.next_id.saturating_sub(first_id) as usize;
+        let end = len.min(start.saturating_add(max));
+        for idx in start..end {
+            if regex.is_match(&text(idx)) {
+                self.matches.push_back(first_id + idx as u64);
+            }
+        }
+        self.next_id = first_id + end as u64;
+    }
+
+    /// Lines are left to check.
+    pub fn is_scanning(&self) -> bool {
+        self.is_active() && self.next_id < self.end_id
+    }
+
+    pub fn matches(&self) -> &VecDeque<u64> {
+        &self.matches
+    }
+
+    /// Ranges of `text` that match.
+    pub fn find_ranges(&self, text: &str) -> Vec<Range<usize>> {
+        match &self.regex {
+            Ok(Some(regex)) => regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Index of the current match and the number of matches.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        self.current.map(|current| (current, self.matches.len()))
+    }
+
+    pub fn current_id(&self) -> Option<u64> {
+        self.current
+            .and_then(|current| self.matches.get(current).copied())
+    }
+
+    /// Moves to the next match, wrapping around, and returns its line id.
+    pub fn select_next(&mut self) -> Option<u64> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current = Some(match self.current {
+            Some(current) => (current + 1) % self.matches.len(),
+            None => 0,
+        });
+        self.current_id()
+    }
+
+    /// Moves to the previous match, wrapping around, and returns its line id.
+    pub fn select_prev(&mut self) -> Option<u64> {
+        if self.matches.is_empty() {
+            return None;
+        }
+
+        self.current = Some(match self.current {
+            Some(0) | None => self.matches.len() - 1,
+            Some(current) => current - 1,
+        });
+        self.current_id()
+    }
+}
+
+/// Splits `0..len` in sections with the background of the last span over
+/// them, or none.
+pub fn backgrounds(
+    len: usize,
+    spans: impl IntoIterator<Item = (Range<usize>, Color32)>,
+) -> Vec<(Range<usize>, Option<Color32>)> {
+    let mut colors = vec![None; len];
+    for (range, color) in spans {
+        for slot in &mut colors[range.start.min(len)..range.end.min(len)] {
+            *slot = Some(color);
+        }
+    }
+
+    let mut sections: Vec<(Range<usize>, Option<Color32>)> = vec![];
+    for (idx, color) in colors.into_iter().enumerate() {
+        match sections.last_mut() {
+            Some((range, last)) if *last == color => range.end = idx + 1,
+            _ => sections.push((idx..idx + 1, color)),
+        }
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finder, HighlightRule, Highlighter, Pattern, backgrounds};
+    use egui::Color32;
+
+    fn pattern(text: &str, is_regex: bool, is_case_sensitive: bool) -> Pattern {
+        Pattern {
+            text: text.to_string(),
+            is_regex,
+            is_case_sensitive,
+        }
+    }
+
+    #[test]
+    fn test_pattern_compile() {
+        assert!(pattern("", false, false).compile().unwrap().is_none());
+
+        let plain = pattern("a.b", false, false).compile().unwrap().unwrap();
+        assert!(plain.is_match("xA.By"));
+        assert!(!plain.is_match("axb"));
+
+        let regex = pattern("a.b", true, true).compile().unwrap().unwrap();
+        assert!(regex.is_match("axb"));
+        assert!(!regex.is_match("AxB"));
+
+        assert!(pattern("(", true, false).compile().is_err());
+    }
+
+    #[test]
+    fn test_finder_scan_and_navigate() {
+        let lines = ["boot", "error 1", "ok", "ERROR 2", "error 3"];
+        let mut finder = Finder::default();
+        finder.set_pattern(&pattern("error", false, false));
+        assert!(finder.is_active());
+
+        finder.scan(0, 3, usize::MAX, |idx| lines[idx].to_string());
+        assert_eq!(finder.matches(), &[1]);
+        assert_eq!(finder.position(), None);
+
+        // Two lines arrive and the first two are dropped.
+        finder.scan(2, 3, usize::MAX, |idx| lines[idx + 2].to_string());
+        assert_eq!(finder.matches(), &[3, 4]);
+
+        assert_eq!(finder.select_next(), Some(3));
+        assert_eq!(finder.select_next(), Some(4));
+        assert_eq!(finder.select_next(), Some(3));
+        assert_eq!(finder.select_prev(), Some(4));
+        assert_eq!(finder.position(), Some((1, 2)));
+
+        finder.scan(4, 1, usize::MAX, |_| lines[4].to_string());
+        assert_eq!(finder.matches(), &[4]);
+        assert_eq!(finder.position(), Some((0, 1)));
+        assert_eq!(finder.find_ranges("error, Error"), vec![0..5, 7..12]);
+
+        // Two lines per scan.
+        finder.set_pattern(&pattern("o", false, false));
+        finder.scan(0, 5, 2, |idx| lines[idx].to_string());
+        assert!(finder.is_scanning());
+        assert_eq!(finder.matches(), &[0, 1]);
+        finder.scan(0, 5, 2, |idx| lines[idx].to_string());
+        finder.scan(0, 5, 2, |idx| lines[idx].to_string());
+        assert!(!finder.is_scanning());
+        assert_eq!(finder.matches(), &[0, 1, 2, 3, 4]);
+
+        finder.set_pattern(&pattern("[", true, false));
+        assert!(!finder.is_active());
+        assert!(finder.error().is_some());
+        assert!(finder.matches().is_empty());
+    }
+
+    #[test]
+    fn test_highlighter_and_backgrounds() {
+        let red = Color32::from_rgb(255, 0, 0);
+        let blue = Color32::from_rgb(0, 0, 255);
+        let highlighter = Highlighter::new(&[
+            HighlightRule {
+                pattern: pattern("warn", false, false),
+                color: [255, 0, 0],
+            },
+            HighlightRule {
+                pattern: pattern("[0-9]+", true, false),
+                color: [0, 0, 255],
+            },
+            HighlightRule {
+                pattern: pattern("(", true, false),
+                color: [0, 255, 0],
+            },
+        ]);
+
+        let spans = highlighter.spans("WARN 42");
+        assert_eq!(spans, vec![(0..4, red), (5..7, blue)]);
+        assert_eq!(
+            backgrounds(8, spans),
+            vec![
+                (0..4, Some(red)),
+                (4..5, None),
+                (5..7, Some(blue)),
+                (7..8, None)
+            ]
+        );
+        assert_eq!(
+            backgrounds(3, vec![(1..9, red)]),
+            vec![(0..1, None), (1..3, Some(red))]
+        );
+        assert!(backgrounds(0, vec![]).is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::graphics::special_char::SpecialCharPosition;
+use crate::graphics::special_char::{SpecialCharItem, SpecialCharPosition, ToSpecialChar};
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -11,6 +11,28 @@ pub struct TagList {
     tags: Arc<HashMap<String, String>>,
     pattern: Arc<String>,
     autocomplete_list: Vec<Arc<String>>,
+    plugin_tags: Arc<Vec<String>>,
+}
+
+/// Finds the first `@tag` of `string` whose name passes `is_tag`.
+pub fn find_tag(string: &str, is_tag: impl Fn(&str) -> bool) -> Option<SpecialCharPosition> {
+    for (char_pos, _) in string.chars().enumerate().filter(|(_, c)| *c == '@') {
+        let tag_name: String = string
+            .chars()
+            .skip(char_pos + 1)
+            .take_while(|c| !c.is_whitespace())
+            .collect();
+
+        if tag_name.is_empty() {
+            continue;
+        }
+
+        if is_tag(&tag_name) {
+            return Some((char_pos, tag_name.chars().count() + 1).into());
+        }
+    }
+
+    None
 }
 
 impl TagList {
@@ -49,6 +71,19 @@ impl TagList {
         Ok(())
     }
 
+    /// Tags registered by the plugins. Their values are computed by the
+    /// plugin engine when the message is sent.
+    pub fn set_plugin_tags(&mut self, plugin_tags: Vec<String>) {
+        if *self.plugin_tags != plugin_tags {
+            self.plugin_tags = Arc::new(plugin_tags);
+        }
+    }
+
+    /// File tags take precedence over plugin tags with the same name.
+    pub fn is_plugin_tag(&self, tag_name: &str) -> bool {
+        !self.tags.contains_key(tag_name) && self.plugin_tags.iter().any(|tag| tag == tag_name)
+    }
+
     pub fn get_tagged_key(&self, key: &str) -> String {
         let tag_name = key.strip_prefix('@').unwrap_or_default();
         self.tags
@@ -98,6 +133,11 @@ impl TagList {
         self.autocomplete_list = self
             .tags
             .keys()
+            .chain(
+                self.plugin_tags
+                    .iter()
+                    .filter(|k| !self.tags.contains_key(*k)),
+            )
             .filter(|&k| match self.pattern.chars().count() {
                 0 => false,
                 1 if self.pattern.as_str() == "@" => true,
@@ -125,23 +165,59 @@ impl TagList {
     }
 
     pub fn tag_filter(&self, string: &str) -> Option<SpecialCharPosition> {
-        for (char_pos, _) in string.chars().enumerate().filter(|(_, c)| *c == '@') {
-            let tag_name: String = string
-                .chars()
-                .skip(char_pos + 1)
-                .take_while(|c| !c.is_whitespace())
-                .collect();
-
-            if tag_name.is_empty() {
-                continue;
-            }
+        find_tag(string, |tag_name| {
+            self.tags.contains_key(tag_name) || self.is_plugin_tag(tag_name)
+        })
+    }
+
+    /// Replaces the file tags by their values. Plugin tags are kept, since
+    /// only the plugin engine can compute them, and reported on the returned
+    /// flag.
+    pub fn replace_tags(&self, command_line: String) -> (String, bool) {
+        let mut res = String::with_capacity(command_line.len());
+        let mut has_plugin_tags = false;
+
+        for item in command_line.to_special_char(|string| self.tag_filter(string)) {
+            match item {
+                SpecialCharItem::Plain(s) => {
+                    res.push_str(&s);
+                }
+                SpecialCharItem::Special(s, _column) => {
+                    if self.is_plugin_tag(s.strip_prefix('@').unwrap_or_default()) {
+                        has_plugin_tags = true;
+                        res.push_str(&s);
+                        continue;
+                    }
 
-            if self.tags.contains_key(&tag_name) {
-                return Some((char_pos, tag_name.chars().count() + 1).into());
+                    let tag_value = self.get_tagged_key(&s);
+                    res.push_str(&tag_value);
+                }
             }
         }
 
-        None
+        (res, has_plugin_tags)
+    }
+
+    /// Replaces the tag typed before `cursor`, a char index, and the rest of
+    /// its word by `@tag_name`. Returns the new text and cursor. The pattern
+    /// comes from the last `update_pattern`.
+    pub fn complete(&self, text: &str, cursor: usize, tag_name: &str) -> (String, usize) {
+        let tag = format!("@{}", tag_name);
+        let start = cursor.saturating_sub(self.pattern.chars().count());
+        let end = text
+            .chars()
+            .skip(cursor)
+            .position(|c| c.is_whitespace())
+            .map_or(text.chars().count(), |pos| pos + cursor);
+
+        let completed = text
+            .chars()
+            .take(start)
+            .chain(tag.chars())
+            .chain(text.chars().skip(end))
+            .collect();
+
+        (completed, start + tag.chars().count())
     }
 
     pub fn autocomplete_list(&self) -> Vec<Arc<String>> {
@@ -152,3 +228,65 @@ impl TagList {
         self.pattern.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TagList;
+    use std::{collections::HashMap, sync::Arc};
+
+    fn tag_list() -> TagList {
+        let mut tag_list = TagList {
+            tags: Arc::new(HashMap::from([
+                ("reset".to_string(), "AT+RST".to_string()),
+                ("read".to_string(), "AT+READ?".to_string()),
+            ])),
+            ..Default::default()
+        };
+        tag_list.set_plugin_tags(vec!["now".to_string()]);
+        tag_list
+    }
+
+    #[test]
+    fn test_replace_tags() {
+        let tag_list = tag_list();
+
+        assert_eq!(
+            tag_list.replace_tags("@reset then @unknown".to_string()),
+            ("AT+RST then @unknown".to_string(), false)
+        );
+        assert_eq!(
+            tag_list.replace_tags("@read @now".to_string()),
+            ("AT+READ? @now".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut tag_list = tag_list();
+
+        tag_list.update_pattern("go @resxyz 1", 7);
+        tag_list.update_autocomplete_list();
+        let first = tag_list.get_first_autocomplete_list().unwrap();
+        assert_eq!(first.as_str(), "reset");
+        assert_eq!(
+            tag_list.complete("go @resxyz 1", 7, &first),
+            ("go @reset 1".to_string(), 9)
+        );
+
+        tag_list.update_pattern("@", 1);
+        tag_list.update_autocomplete_list();
+        assert_eq!(
+            tag_list
+                .autocomplete_list()
+                .iter()
+                .map(|tag| tag.as_str())
+                .collect::<Vec<_>>(),
+            vec!["now", "read", "reset"]
+        );
+        assert_eq!(tag_list.complete("@", 1, "read"), ("@read".to_string(), 5));
+
+        tag_list.update_pattern("no tag", 6);
+        tag_list.update_autocomplete_list();
+        assert!(tag_list.autocomplete_list().is_empty());
+    }
+}
@@ -59,6 +59,11 @@ where
 }
 
 impl<S> Shared<S> {
+    /// Read-only view of a lock that isn't owned by a task.
+    pub fn new(shared: Arc<RwLock<S>>) -> Self {
+        Self { shared }
+    }
+
     pub fn read(
         &self,
     ) -> Result<
@@ -0,0 +1,393 @@
+use crate::interfaces::InterfaceType;
+use crate::plugin::{engine::PluginInfo, permissions::Capability};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const COMMANDS: [&str; 8] = [
+    "serial",
+    "rtt",
+    "connect",
+    "disconnect",
+    "flow",
+    "log",
+    "plugin",
+    "ipsum",
+];
+const SERIAL_SUBCOMMANDS: [&str; 3] = ["connect", "disconnect", "flow"];
+const RTT_SUBCOMMANDS: [&str; 3] = ["connect", "disconnect", "read"];
+const PLUGIN_SUBCOMMANDS: [&str; 11] = [
+    "load", "reload", "unload", "stop", "list", "stats", "help", "watch", "unwatch", "allow",
+    "deny",
+];
+const FLOW_CONTROLS: [&str; 3] = ["none", "sw", "hw"];
+const LOG_LEVELS: [&str; 5] = ["debug", "info", "success", "warning", "error"];
+const IPSUM_MODES: [&str; 7] = ["rx", "tx", "dbg", "inf", "ok", "warn", "err"];
+const BAUDRATES: [&str; 8] = [
+    "9600", "19200", "38400", "57600", "115200", "230400", "460800", "921600",
+];
+
+/// How long the serial ports and the files of a directory are reused. The
+/// completion is updated on every keystroke.
+const LISTING_TTL: Duration = Duration::from_secs(1);
+
+/// Autocomplete of the word under the cursor in a `!` command line.
+#[derive(Default, Clone)]
+pub struct CommandCompletion {
+    start: usize,
+    pattern: Arc<String>,
+    candidates: Vec<Arc<String>>,
+    listing: Option<Listing>,
+}
+
+/// The last serial ports or directory read, as named by `key`.
+#[derive(Clone)]
+struct Listing {
+    key: String,
+    read_at: Instant,
+    entries: Arc<Vec<String>>,
+}
+
+impl CommandCompletion {
+    pub fn update(
+        &mut self,
+        text: &str,
+        cursor: usize,
+        plugins: &[PluginInfo],
+        if_type: &InterfaceType,
+    ) {
+        self.clear();
+
+        if !text.starts_with('!') || cursor == 0 {
+            return;
+        }
+
+        let before_cursor = text.chars().take(cursor).collect::<String>();
+        let start = before_cursor
+            .chars()
+            .enumerate()
+            .filter(|(_, c)| c.is_whitespace())
+            .map(|(i, _)| i + 1)
+            .last()
+            .unwrap_or(1);
+        let pattern = before_cursor.chars().skip(start).collect::<String>();
+        let words = before_cursor
+            .chars()
+            .skip(1)
+            .take(start - 1)
+            .collect::<String>();
+        let words = words.split_whitespace().collect::<Vec<_>>();
+
+        self.candidates = self
+            .candidates_for(&words, &pattern, plugins, if_type)
+            .into_iter()
+            .filter(|candidate| candidate != &pattern && candidate.starts_with(&pattern))
+            .map(Arc::new)
+            .collect();
+        self.candidates.sort_by_key(|a| a.to_ascii_lowercase());
+        self.candidates.dedup();
+        self.start = start;
+        self.pattern = Arc::new(pattern);
+    }
+
+    fn candidates_for(
+        &mut self,
+        words: &[&str],
+        pattern: &str,
+        plugins: &[PluginInfo],
+        if_type: &InterfaceType,
+    ) -> Vec<String> {
+        let to_strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let plugin_names = || {
+            plugins
+                .iter()
+                .map(|plugin| plugin.name.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        match words {
+            [] => to_strings(&COMMANDS)
+                .into_iter()
+                .chain(plugin_names())
+                .collect(),
+            ["serial"] => to_strings(&SERIAL_SUBCOMMANDS),
+            ["serial", "connect"] => self.port_candidates(),
+            ["serial", "connect", _] => to_strings(&BAUDRATES),
+            ["serial", "flow"] | ["flow"] => to_strings(&FLOW_CONTROLS),
+            ["connect"] => match if_type {
+                InterfaceType::Serial => self.port_candidates(),
+                InterfaceType::Rtt => vec![],
+            },
+            ["connect", _] => match if_type {
+                InterfaceType::Serial => to_strings(&BAUDRATES),
+                InterfaceType::Rtt => vec![],
+            },
+            ["rtt"] => to_strings(&RTT_SUBCOMMANDS),
+            ["log"] => ["system".to_string()]
+                .into_iter()
+                .chain(plugin_names())
+                .collect(),
+            ["log", _] => to_strings(&LOG_LEVELS),
+            ["plugin"] => to_strings(&PLUGIN_SUBCOMMANDS),
+            ["plugin", "load" | "reload"] => self.path_candidates(pattern),
+            [
+                "plugin",
+                "unload" | "stop" | "stats" | "help" | "watch" | "unwatch" | "allow" | "deny",
+            ] => plugin_names(),
+            ["plugin", "allow" | "deny", _] => Capability::ALL
+                .iter()
+                .map(|capability| capability.name().to_string())
+                .collect(),
+            ["ipsum"] => to_strings(&IPSUM_MODES),
+            [plugin_name] | ["plugin", "stop", plugin_name] => plugins
+                .iter()
+                .find(|plugin| plugin.name.as_str() == *plugin_name)
+                .map(|plugin| {
+                    plugin
+                        .commands
+                        .iter()
+                        .map(|command| command.name.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    /// The last listing when it's still fresh and of the same `key`, or the
+    /// one given by `read`.
+    fn cached(&mut self, key: String, read: impl FnOnce() -> Vec<String>) -> Arc<Vec<String>> {
+        match &self.listing {
+            Some(listing) if listing.key == key && listing.read_at.elapsed() < LISTING_TTL => {}
+            _ => {
+                self.listing = Some(Listing {
+                    key,
+                    read_at: Instant::now(),
+                    entries: Arc::new(read()),
+                })
+            }
+        }
+
+        self.listing
+            .as_ref()
+            .map(|listing| listing.entries.clone())
+            .unwrap_or_default()
+    }
+
+    fn port_candidates(&mut self) -> Vec<String> {
+        let ports = self.cached("ports".to_string(), || {
+            serialport::available_ports()
+                .map(|ports| ports.into_iter().map(|port| port.port_name).collect())
+                .unwrap_or_default()
+        });
+
+        ports.to_vec()
+    }
+
+    /// Directories and plugin files, `.lua` or `.wasm`, that complete the path
+    /// typed so far.
+    fn path_candidates(&mut self, pattern: &str) -> Vec<String> {
+        let (dir_prefix, file_prefix) = match pattern.rfind(['/', '\\']) {
+            Some(pos) => pattern.split_at(pos + 1),
+            None => ("", pattern),
+        };
+        let dir = if dir_prefix.is_empty() {
+            "."
+        } else {
+            dir_prefix
+        };
+        let entries = self.cached(format!("dir:{}", dir), || Self::list_dir(Path::new(dir)));
+
+        entries
+            .iter()
+            .filter(|name| {
+                name.starts_with(file_prefix)
+                    && (!name.starts_with('.') || file_prefix.starts_with('.'))
+            })
+            .map(|name| format!("{}{}", dir_prefix, name))
+            .collect()
+    }
+
+    /// The directories, with a trailing `/`, and the plugin files of `dir`.
+    fn list_dir(dir: &Path) -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                let is_dir = entry.file_type().ok()?.is_dir();
+
+                if is_dir {
+                    Some(format!("{}/", name))
+                } else if name.ends_with(".lua") || name.ends_with(".wasm") {
+                    Some(name)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.pattern = Arc::new(String::new());
+        self.candidates.clear();
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn pattern(&self) -> Arc<String> {
+        self.pattern.clone()
+    }
+
+    pub fn candidates(&self) -> Vec<Arc<String>> {
+        self.candidates.clone()
+    }
+
+    pub fn first_candidate(&self) -> Option<Arc<String>> {
+        self.candidates.first().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandCompletion;
+    use crate::interfaces::InterfaceType;
+    use crate::plugin::{PluginCommand, engine::PluginInfo};
+    use std::sync::Arc;
+
+    fn plugins() -> Vec<PluginInfo> {
+        vec![PluginInfo {
+            name: Arc::new("auto_test".to_string()),
+            commands: ["run_all", "test_creg"]
+                .into_iter()
+                .map(|name| PluginCommand {
+                    name: name.to_string(),
+                    args: String::new(),
+                    doc: vec![],
+                })
+                .collect(),
+            tags: vec![],
+        }]
+    }
+
+    fn complete(text: &str) -> CommandCompletion {
+        let mut completion = CommandCompletion::default();
+
+        completion.update(
+            text,
+            text.chars().count(),
+            &plugins(),
+            &InterfaceType::Serial,
+        );
+
+        completion
+    }
+
+    fn candidates(completion: &CommandCompletion) -> Vec<String> {
+        completion
+            .candidates()
+            .iter()
+            .map(|candidate| candidate.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_plugin_commands() {
+        let completion = complete("!auto_test ");
+
+        assert_eq!(candidates(&completion), ["run_all", "test_creg"]);
+        assert_eq!(completion.start(), 11);
+        assert_eq!(completion.pattern().as_str(), "");
+    }
+
+    #[test]
+    fn test_plugin_commands_with_pattern() {
+        let completion = complete("!auto_test te");
+
+        assert_eq!(candidates(&completion), ["test_creg"]);
+        assert_eq!(completion.pattern().as_str(), "te");
+    }
+
+    #[test]
+    fn test_unknown_plugin() {
+        assert!(complete("!echo ").candidates().is_empty());
+    }
+
+    #[test]
+    fn test_not_a_command() {
+        assert!(complete("auto_test ").candidates().is_empty());
+    }
+
+    #[test]
+    fn test_commands_and_plugin_names() {
+        assert_eq!(candidates(&complete("!")).len(), 9);
+        assert_eq!(candidates(&complete("!a")), ["auto_test"]);
+        assert_eq!(candidates(&complete("!p")), ["plugin"]);
+    }
+
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(
+            candidates(&complete("!serial ")),
+            ["connect", "disconnect", "flow"]
+        );
+        assert_eq!(candidates(&complete("!rtt r")), ["read"]);
+        assert_eq!(candidates(&complete("!serial flow ")), ["hw", "none", "sw"]);
+        assert_eq!(candidates(&complete("!plugin un")), ["unload", "unwatch"]);
+        assert_eq!(
+            candidates(&complete("!plugin allow auto_test s")),
+            ["send", "shell"]
+        );
+    }
+
+    #[test]
+    fn test_plugin_names() {
+        assert_eq!(candidates(&complete("!plugin help ")), ["auto_test"]);
+        assert_eq!(candidates(&complete("!log ")), ["auto_test", "system"]);
+        assert_eq!(candidates(&complete("!log auto_test w")), ["warning"]);
+        assert_eq!(
+            candidates(&complete("!plugin stop auto_test ")),
+            ["run_all", "test_creg"]
+        );
+    }
+
+    #[test]
+    fn test_plugin_paths() {
+        assert_eq!(
+            candidates(&complete("!plugin load plugins/aut")),
+            ["plugins/auto_test.lua"]
+        );
+        assert!(
+            candidates(&complete("!plugin load plugins/"))
+                .contains(&"plugins/echo.lua".to_string())
+        );
+        assert!(
+            !candidates(&complete("!plugin load plugins/"))
+                .contains(&"plugins/README.md".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cached_listing() {
+        let mut completion = CommandCompletion::default();
+        let mut reads = 0;
+        let mut read = |entries: &[&str]| {
+            reads += 1;
+            entries.iter().map(|entry| entry.to_string()).collect()
+        };
+
+        completion.cached("dir:a".to_string(), || read(&["x.lua"]));
+        let entries = completion.cached("dir:a".to_string(), || read(&["y.lua"]));
+        assert_eq!(*entries, ["x.lua"]);
+
+        let entries = completion.cached("dir:b".to_string(), || read(&["z.lua"]));
+        assert_eq!(*entries, ["z.lua"]);
+        assert_eq!(reads, 2);
+    }
+}
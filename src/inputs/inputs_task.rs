@@ -1,5 +1,5 @@
-use crate::graphics::special_char::{SpecialCharItem, ToSpecialChar};
 use crate::infra::tags::TagList;
+use crate::inputs::completion::CommandCompletion;
 use crate::inputs::history::{AnyHistory, History, HistoryNavResult, PersistHistory};
 use crate::interfaces::rtt_if::{RttCommand, RttSetup};
 use crate::interfaces::{InterfaceCommand, InterfaceType};
@@ -11,10 +11,10 @@ use crate::{
         logger::{LogLevel, Logger},
         messages::TimedBytes,
         mpmc::Producer,
-        task::Task,
+        task::{Shared, Task},
     },
     interfaces::serial_if::{SerialCommand, SerialSetup},
-    plugin::engine::PluginEngineCommand,
+    plugin::engine::{PluginEngineCommand, PluginEngineShared},
     success, warning,
 };
 use chrono::Local;
@@ -43,6 +43,7 @@ pub struct InputsShared {
     pub mode: InputMode,
     pub is_case_sensitive: bool,
     pub tag_list: TagList,
+    pub command_completion: CommandCompletion,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -58,6 +59,7 @@ pub struct InputsConnections {
     graphics_cmd_sender: Sender<GraphicsCommand>,
     interface_cmd_sender: Sender<InterfaceCommand>,
     plugin_engine_cmd_sender: Sender<PluginEngineCommand>,
+    plugin_engine_shared: Shared<PluginEngineShared>,
     hints: Vec<&'static str>,
     history: AnyHistory,
     rx_channel: Producer<Arc<TimedBytes>>,
@@ -126,6 +128,14 @@ impl InputsTask {
                     .graphics_cmd_sender
                     .send(GraphicsCommand::CopyToClipboard);
             }
+            KeyCode::Char('k') | KeyCode::Char('K') if key.modifiers == KeyModifiers::CONTROL => {
+                let _ = private
+                    .plugin_engine_cmd_sender
+                    .send(PluginEngineCommand::StopPlugin {
+                        plugin_name: None,
+                        command: None,
+                    });
+            }
             KeyCode::Char('l') | KeyCode::Char('L') if key.modifiers == KeyModifiers::CONTROL => {
                 let _ = private.graphics_cmd_sender.send(GraphicsCommand::Clear);
             }
@@ -548,6 +558,7 @@ impl InputsTask {
                         };
 
                         sw.tag_list.clear();
+                        sw.command_completion.clear();
                         private.history.reset_index();
                         sw.cursor = 0;
 
@@ -561,9 +572,8 @@ impl InputsTask {
 
                             Self::handle_user_command(command_line_split, private);
                         } else {
-                            let command_line =
-                                Self::replace_tag_sequence(command_line, &sw.tag_list);
-                            let mut command_line = Self::replace_hex_sequence(command_line);
+                            let (command_line, has_plugin_tags) =
+                                sw.tag_list.replace_tags(command_line);
 
                             let end_bytes = if let KeyModifiers::ALT = key.modifiers {
                                 b"".as_slice()
@@ -571,6 +581,17 @@ impl InputsTask {
                                 b"\r\n".as_slice()
                             };
 
+                            if has_plugin_tags {
+                                let _ = private.plugin_engine_cmd_sender.send(
+                                    PluginEngineCommand::SendWithTags {
+                                        command_line,
+                                        end_bytes: end_bytes.to_vec(),
+                                    },
+                                );
+                                return LoopStatus::Continue;
+                            }
+
+                            let mut command_line = Self::replace_hex_sequence(command_line);
                             command_line.extend_from_slice(end_bytes);
 
                             private.tx.produce(Arc::new(TimedBytes {
@@ -605,24 +626,29 @@ impl InputsTask {
     fn handle_tab_input(private: &mut InputsConnections, shared: Arc<RwLock<InputsShared>>) {
         let mut sw = shared.write().expect("Cannot get input lock for write");
 
-        let Some(first_entry) = sw.tag_list.get_first_autocomplete_list() else {
-            return;
-        };
-
-        let first_entry = format!("@{}", first_entry);
-        let first_entry_len = first_entry.chars().count();
-
         let InputMode::Normal = sw.mode else {
             return;
         };
 
-        let pattern = sw.tag_list.pattern();
-        let pattern_len = pattern.chars().count();
         let cursor = sw.cursor;
-        let start = cursor.saturating_sub(pattern_len);
+        let (first_entry, start) =
+            if let Some(first_entry) = sw.tag_list.get_first_autocomplete_list() {
+                let pattern_len = sw.tag_list.pattern().chars().count();
+
+                (
+                    format!("@{}", first_entry),
+                    cursor.saturating_sub(pattern_len),
+                )
+            } else if let Some(first_entry) = sw.command_completion.first_candidate() {
+                ((*first_entry).clone(), sw.command_completion.start())
+            } else {
+                return;
+            };
+
+        let first_entry_len = first_entry.chars().count();
 
         sw.command_line = Self::replace_range_chars(&sw.command_line, start..cursor, &first_entry);
-        sw.cursor += first_entry_len - pattern_len;
+        sw.cursor = start + first_entry_len;
 
         let command_line_len = sw.command_line.chars().count();
         let cursor_after = sw.cursor;
@@ -977,15 +1003,38 @@ impl InputsTask {
                 }
             }
             "plugin" => {
-                if command_line_split.len() < 3 {
+                let Some(command) = command_line_split.get(1).map(|command| command.as_str())
+                else {
                     error!(
                         private.logger,
                         "Insufficient arguments for \"!plugin\" command"
                     );
                     return;
+                };
+
+                if command == "list" {
+                    let _ = private
+                        .plugin_engine_cmd_sender
+                        .send(PluginEngineCommand::ListPlugins);
+                    return;
+                }
+
+                if command == "stats" {
+                    let plugin_name = command_line_split.get(2).cloned();
+
+                    let _ = private
+                        .plugin_engine_cmd_sender
+                        .send(PluginEngineCommand::PluginStats { plugin_name });
+                    return;
                 }
 
-                let command = command_line_split[1].as_str();
+                if command_line_split.len() < 3 {
+                    error!(
+                        private.logger,
+                        "Insufficient arguments for \"!plugin\" command"
+                    );
+                    return;
+                }
 
                 match command {
                     "load" | "reload" => {
@@ -1002,10 +1051,56 @@ impl InputsTask {
                             .plugin_engine_cmd_sender
                             .send(PluginEngineCommand::UnloadPlugin { plugin_name });
                     }
+                    "help" => {
+                        let plugin_name = command_line_split[2].clone();
+
+                        let _ = private
+                            .plugin_engine_cmd_sender
+                            .send(PluginEngineCommand::PluginHelp { plugin_name });
+                    }
+                    "stop" => {
+                        let plugin_name = command_line_split[2].clone();
+                        let command = command_line_split.get(3).cloned();
+
+                        let _ = private.plugin_engine_cmd_sender.send(
+                            PluginEngineCommand::StopPlugin {
+                                plugin_name: Some(plugin_name),
+                                command,
+                            },
+                        );
+                    }
+                    "allow" | "deny" => {
+                        let plugin_name = command_line_split[2].clone();
+                        let Some(capability) = command_line_split.get(3).cloned() else {
+                            error!(
+                                private.logger,
+                                "Insufficient arguments for \"!plugin {}\" command", command
+                            );
+                            return;
+                        };
+
+                        let _ = private.plugin_engine_cmd_sender.send(
+                            PluginEngineCommand::SetPermission {
+                                plugin_name,
+                                capability,
+                                allow: command == "allow",
+                            },
+                        );
+                    }
+                    "watch" | "unwatch" => {
+                        let plugin_name = command_line_split[2].clone();
+
+                        let _ = private.plugin_engine_cmd_sender.send(
+                            PluginEngineCommand::WatchPlugin {
+                                plugin_name,
+                                enable: command == "watch",
+                            },
+                        );
+                    }
                     _ => {
                         error!(
                             private.logger,
-                            "Invalid command. Please, choose one of these options: load, reload, unload"
+                            "Invalid command. Please, choose one of these options: load, reload, unload, stop, list, stats, help, watch, unwatch, allow, deny"
                         );
                     }
                 }
@@ -1259,7 +1354,7 @@ impl InputsTask {
         *current_hint = Some(hints.choose(&mut rand::thread_rng()).unwrap().to_string());
     }
 
-    fn replace_hex_sequence(command_line: String) -> Vec<u8> {
+    pub fn replace_hex_sequence(command_line: String) -> Vec<u8> {
         let mut output = vec![];
         let mut in_hex_seq = false;
         let valid = "0123456789abcdefABCDEF,_-. ";
@@ -1319,25 +1414,32 @@ impl InputsTask {
         output
     }
 
-    fn replace_tag_sequence(command_line: String, tag_list: &TagList) -> String {
-        let mut res = String::with_capacity(command_line.len());
-
-        for item in command_line.to_special_char(|string| tag_list.tag_filter(string)) {
-            match item {
-                SpecialCharItem::Plain(s) => {
-                    res.push_str(&s);
-                }
-                SpecialCharItem::Special(s, _column) => {
-                    let tag_value = tag_list.get_tagged_key(&s);
-                    res.push_str(&tag_value);
-                }
-            }
+    fn update_tag_list(sw: &mut InputsShared, private: &mut InputsConnections) {
+        let plugin_engine_shared = private
+            .plugin_engine_shared
+            .read()
+            .expect("Cannot get plugin engine lock for read");
+
+        sw.tag_list.set_plugin_tags(
+            plugin_engine_shared
+                .plugins
+                .iter()
+                .flat_map(|plugin| plugin.tags.iter().cloned())
+                .collect(),
+        );
+
+        if sw.mode == InputMode::Normal {
+            sw.command_completion.update(
+                &sw.command_line,
+                sw.cursor,
+                &plugin_engine_shared.plugins,
+                &private.if_type,
+            );
+        } else {
+            sw.command_completion.clear();
         }
+        drop(plugin_engine_shared);
 
-        res
-    }
-
-    fn update_tag_list(sw: &mut InputsShared, private: &mut InputsConnections) {
         let (buffer, cursor) = if sw.mode == InputMode::Normal {
             (&sw.command_line, sw.cursor)
         } else {
@@ -1361,12 +1463,14 @@ impl InputsTask {
 }
 
 impl InputsConnections {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         logger: Logger,
         tx: Producer<Arc<TimedBytes>>,
         graphics_cmd_sender: Sender<GraphicsCommand>,
         interface_cmd_sender: Sender<InterfaceCommand>,
         plugin_engine_cmd_sender: Sender<PluginEngineCommand>,
+        plugin_engine_shared: Shared<PluginEngineShared>,
         rx_channel: Producer<Arc<TimedBytes>>,
         if_type: InterfaceType,
     ) -> Self {
@@ -1387,6 +1491,7 @@ impl InputsConnections {
             graphics_cmd_sender,
             interface_cmd_sender,
             plugin_engine_cmd_sender,
+            plugin_engine_shared,
             hints: vec![
                 "Type @ to place a tag",
                 "Type $ to start a hex sequence",
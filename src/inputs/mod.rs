@@ -1,2 +1,3 @@
+pub mod completion;
 pub mod history;
 pub mod inputs_task;
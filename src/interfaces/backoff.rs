@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+/// Delay between reconnection attempts. It doubles after each failure, up to
+/// `MAX_DELAY`, so a missing device isn't polled in a tight loop.
+pub struct Backoff {
+    delay: Duration,
+    next_attempt: Instant,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: Self::MIN_DELAY,
+            next_attempt: Instant::now(),
+        }
+    }
+}
+
+impl Backoff {
+    pub const MIN_DELAY: Duration = Duration::from_millis(100);
+    pub const MAX_DELAY: Duration = Duration::from_secs(1);
+
+    pub fn is_ready(&self, now: Instant) -> bool {
+        now >= self.next_attempt
+    }
+
+    /// Schedules the next attempt after a failed one at `now`.
+    pub fn failed(&mut self, now: Instant) {
+        self.next_attempt = now + self.delay;
+        self.delay = (self.delay * 2).min(Self::MAX_DELAY);
+    }
+
+    /// The next attempt happens right away, with the shortest delay again.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Backoff;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::default();
+        let now = Instant::now();
+        assert!(backoff.is_ready(now));
+
+        backoff.failed(now);
+        assert!(!backoff.is_ready(now + Duration::from_millis(99)));
+        assert!(backoff.is_ready(now + Duration::from_millis(100)));
+
+        backoff.failed(now);
+        assert!(!backoff.is_ready(now + Duration::from_millis(199)));
+        for _ in 0..10 {
+            backoff.failed(now);
+        }
+        assert!(backoff.is_ready(now + Backoff::MAX_DELAY));
+
+        backoff.reset();
+        assert!(backoff.is_ready(Instant::now()));
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::{Duration, Instant};
+
+/// Splits the bytes read from an interface in lines. A partial line is
+/// flushed once the interface is idle for `IDLE_FLUSH`, so prompts without a
+/// line break still show up.
+pub struct LineFramer {
+    line: Vec<u8>,
+    last_byte_at: Instant,
+}
+
+impl Default for LineFramer {
+    fn default() -> Self {
+        Self {
+            line: vec![],
+            last_byte_at: Instant::now(),
+        }
+    }
+}
+
+impl LineFramer {
+    pub const IDLE_FLUSH: Duration = Duration::from_millis(250);
+
+    /// Appends `bytes` read at `now` and returns the completed lines, with
+    /// their `\n`.
+    pub fn push(&mut self, bytes: &[u8], now: Instant) -> Vec<Vec<u8>> {
+        if bytes.is_empty() {
+            return vec![];
+        }
+
+        self.last_byte_at = now;
+        let mut lines = vec![];
+        for &byte in bytes {
+            self.line.push(byte);
+            if byte == b'\n' {
+                lines.push(std::mem::take(&mut self.line));
+            }
+        }
+
+        lines
+    }
+
+    /// The partial line, when nothing was read since `IDLE_FLUSH` before `now`.
+    pub fn flush_idle(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if now.saturating_duration_since(self.last_byte_at) < Self::IDLE_FLUSH {
+            return None;
+        }
+
+        self.flush()
+    }
+
+    /// The partial line, if any. Used when the interface disconnects.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.line.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.line))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LineFramer;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_push_splits_lines() {
+        let mut framer = LineFramer::default();
+        let now = Instant::now();
+
+        assert_eq!(framer.push(b"ok\r\nboot", now), vec![b"ok\r\n".to_vec()]);
+        assert_eq!(
+            framer.push(b": done\n\n", now),
+            vec![b"boot: done\n".to_vec(), b"\n".to_vec()]
+        );
+        assert!(framer.push(b"", now).is_empty());
+        assert_eq!(framer.flush(), None);
+    }
+
+    #[test]
+    fn test_flush_idle() {
+        let mut framer = LineFramer::default();
+        let now = Instant::now();
+
+        assert!(framer.push(b"> ", now).is_empty());
+        assert_eq!(framer.flush_idle(now + Duration::from_millis(100)), None);
+        assert!(
+            framer
+                .push(b"$", now + Duration::from_millis(200))
+                .is_empty()
+        );
+        assert_eq!(framer.flush_idle(now + Duration::from_millis(400)), None);
+        assert_eq!(
+            framer.flush_idle(now + Duration::from_millis(450)),
+            Some(b"> $".to_vec())
+        );
+        assert_eq!(framer.flush_idle(now + Duration::from_secs(10)), None);
+    }
+}
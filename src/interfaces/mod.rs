@@ -1,13 +1,22 @@
 use std::sync::mpsc::{Receiver, Sender};
 
 use crate::{
-    infra::task::Task,
+    info,
+    infra::{
+        logger::{LogLevel, Logger},
+        task::Task,
+    },
     interfaces::{
-        rtt_if::{RttCommand, RttConnections, RttInterface, RttSetup, RttShared},
-        serial_if::{SerialCommand, SerialConnections, SerialInterface, SerialSetup, SerialShared},
+        rtt_if::{RttCommand, RttConnections, RttInterface, RttMode, RttSetup, RttShared},
+        serial_if::{
+            SerialCommand, SerialConnections, SerialInterface, SerialMode, SerialSetup,
+            SerialShared,
+        },
     },
 };
 
+pub mod backoff;
+pub mod framer;
 pub mod rtt_if;
 pub mod serial_if;
 
@@ -28,7 +37,90 @@ pub enum InterfaceType {
     Serial,
 }
 
+pub enum InterfaceSetup {
+    Rtt(RttSetup),
+    Serial(SerialSetup),
+}
+
+/// Connection state of an interface, whatever its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterfaceStatus {
+    Disconnected,
+    /// Trying to connect, until it succeeds or is told to disconnect.
+    Connecting,
+    Connected,
+}
+
+/// What a front-end needs to drive an interface task. The data goes through
+/// the tx and rx channels given to the task.
+pub trait Interface {
+    /// Changes the settings. The interface reconnects when any of them changed.
+    fn setup(&self, setup: InterfaceSetup);
+    fn connect(&self);
+    fn disconnect(&self);
+    fn exit(&self);
+    fn status(&self) -> InterfaceStatus;
+    /// The connected target, like `/dev/ttyUSB0 @ 115200bps`.
+    fn description(&self) -> String;
+}
+
+impl InterfaceShared {
+    pub fn status(&self) -> InterfaceStatus {
+        match self {
+            InterfaceShared::Serial(serial) => match serial.mode {
+                SerialMode::DoNotConnect => InterfaceStatus::Disconnected,
+                SerialMode::Reconnecting => InterfaceStatus::Connecting,
+                SerialMode::Connected => InterfaceStatus::Connected,
+            },
+            InterfaceShared::Rtt(rtt) => match rtt.mode {
+                RttMode::DoNotConnect => InterfaceStatus::Disconnected,
+                RttMode::Reconnecting => InterfaceStatus::Connecting,
+                RttMode::Connected => InterfaceStatus::Connected,
+            },
+        }
+    }
+
+    /// Whether to reconnect after the connection is lost while connected,
+    /// which is logged. Changing the auto-reconnect only affects the next
+    /// lost connection, so there's no need to reconnect for it.
+    pub fn reconnects_on_lost_connection(&self, logger: &Logger) -> bool {
+        let (auto_reconnect, target) = match self {
+            InterfaceShared::Serial(serial) => (serial.auto_reconnect, &serial.port),
+            InterfaceShared::Rtt(rtt) => (rtt.auto_reconnect, &rtt.target),
+        };
+
+        if auto_reconnect {
+            info!(logger, "Reconnecting to \"{}\"...", target);
+        }
+        auto_reconnect
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            InterfaceShared::Serial(serial) => format!("{} @ {}bps", serial.port, serial.baudrate),
+            InterfaceShared::Rtt(rtt) => format!("{} on channel {}", rtt.target, rtt.channel),
+        }
+    }
+}
+
 impl InterfaceTask {
+    fn send(&self, serial: SerialCommand, rtt: RttCommand) {
+        let is_serial = matches!(
+            *self
+                .shared_ref()
+                .read()
+                .expect("Cannot get interface lock for read"),
+            InterfaceShared::Serial(_)
+        );
+        let cmd = if is_serial {
+            InterfaceCommand::Serial(serial)
+        } else {
+            InterfaceCommand::Rtt(rtt)
+        };
+
+        let _ = self.cmd_sender().send(cmd);
+    }
+
     pub fn spawn_serial_interface(
         connections: SerialConnections,
         cmd_sender: Sender<InterfaceCommand>,
@@ -59,3 +151,103 @@ impl InterfaceTask {
         )
     }
 }
+
+impl Interface for InterfaceTask {
+    fn setup(&self, setup: InterfaceSetup) {
+        let cmd = match setup {
+            InterfaceSetup::Serial(setup) => InterfaceCommand::Serial(SerialCommand::Setup(setup)),
+            InterfaceSetup::Rtt(setup) => InterfaceCommand::Rtt(RttCommand::Setup(setup)),
+        };
+
+        let _ = self.cmd_sender().send(cmd);
+    }
+
+    fn connect(&self) {
+        self.send(SerialCommand::Connect, RttCommand::Connect);
+    }
+
+    fn disconnect(&self) {
+        self.send(SerialCommand::Disconnect, RttCommand::Disconnect);
+    }
+
+    fn exit(&self) {
+        self.send(SerialCommand::Exit, RttCommand::Exit);
+    }
+
+    fn status(&self) -> InterfaceStatus {
+        self.shared_ref()
+            .read()
+            .expect("Cannot get interface lock for read")
+            .status()
+    }
+
+    fn description(&self) -> String {
+        self.shared_ref()
+            .read()
+            .expect("Cannot get interface lock for read")
+            .description()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InterfaceShared, InterfaceStatus};
+    use crate::interfaces::{
+        rtt_if::{RttSetup, RttShared},
+        serial_if::{SerialMode, SerialSetup, SerialShared},
+    };
+
+    #[test]
+    fn test_status_and_description() {
+        let mut serial = InterfaceShared::Serial(SerialShared::new(SerialSetup {
+            port: Some("/dev/ttyUSB0".to_string()),
+            baudrate: Some(115200),
+            ..SerialSetup::default()
+        }));
+        assert_eq!(serial.status(), InterfaceStatus::Connecting);
+        assert_eq!(serial.description(), "/dev/ttyUSB0 @ 115200bps");
+
+        if let InterfaceShared::Serial(shared) = &mut serial {
+            shared.mode = SerialMode::Connected;
+        }
+        assert_eq!(serial.status(), InterfaceStatus::Connected);
+
+        let rtt = InterfaceShared::Rtt(RttShared::new(RttSetup {
+            channel: Some(1),
+            ..RttSetup::default()
+        }));
+        assert_eq!(rtt.status(), InterfaceStatus::Disconnected);
+        assert_eq!(rtt.description(), " on channel 1");
+    }
+
+    #[test]
+    fn test_setup_has_changes() {
+        let auto_reconnect = SerialSetup {
+            auto_reconnect: Some(false),
+            ..SerialSetup::default()
+        };
+        assert!(!auto_reconnect.has_changes());
+        assert!(
+            SerialSetup {
+                baudrate: Some(9600),
+                ..auto_reconnect
+            }
+            .has_changes()
+        );
+
+        assert!(
+            !RttSetup {
+                auto_reconnect: Some(true),
+                ..RttSetup::default()
+            }
+            .has_changes()
+        );
+        assert!(
+            RttSetup {
+                channel: Some(0),
+                ..RttSetup::default()
+            }
+            .has_changes()
+        );
+    }
+}
@@ -5,20 +5,21 @@ use crate::{
         messages::TimedBytes,
         mpmc::{Consumer, Producer},
     },
-    interfaces::{InterfaceCommand, InterfaceShared},
+    interfaces::{InterfaceCommand, InterfaceShared, backoff::Backoff, framer::LineFramer},
     plugin::engine::PluginEngineCommand,
     success, warning,
 };
 use chrono::Local;
 use probe_rs::{
     Core, MemoryInterface, Permissions, Session,
-    probe::list::Lister,
+    config::Registry,
+    probe::{DebugProbeSelector, list::Lister},
     rtt::{Rtt, ScanRegion},
 };
 use std::{
     ops::{Deref, DerefMut},
     sync::{
-        Arc, RwLock,
+        Arc, OnceLock, RwLock,
         mpsc::{Receiver, Sender},
     },
     thread::{sleep, yield_now},
@@ -29,6 +30,11 @@ pub struct RttShared {
     pub target: String,
     pub mode: RttMode,
     pub channel: usize,
+    /// Selector (`VID:PID[:SERIAL]`) of the probe to use. Empty uses the
+    /// first probe found.
+    pub probe: String,
+    /// Whether a lost or failed connection is retried.
+    pub auto_reconnect: bool,
 }
 
 pub struct RttConnections {
@@ -46,6 +52,17 @@ pub struct RttConnections {
 pub struct RttSetup {
     pub target: Option<String>,
     pub channel: Option<usize>,
+    pub probe: Option<String>,
+    pub auto_reconnect: Option<bool>,
+}
+
+impl RttSetup {
+    /// Whether the connection settings change, which reattaches to the
+    /// target. The auto-reconnect alone doesn't, see
+    /// [`InterfaceShared::reconnects_on_lost_connection`].
+    pub fn has_changes(&self) -> bool {
+        self.target.is_some() || self.channel.is_some() || self.probe.is_some()
+    }
 }
 
 pub enum RttCommand {
@@ -86,14 +103,14 @@ impl RttShared {
         Self {
             target,
             channel: setup.channel.unwrap_or(0),
+            probe: setup.probe.unwrap_or_default(),
+            auto_reconnect: setup.auto_reconnect.unwrap_or(true),
             mode,
         }
     }
 }
 
 impl RttInterface {
-    const NEW_LINE_TIMEOUT_MS: u128 = 1_000;
-
     pub fn task(
         shared: Arc<RwLock<InterfaceShared>>,
         connections: RttConnections,
@@ -109,44 +126,63 @@ impl RttInterface {
             mut probe_speed_message,
             mut fail_to_attach_message,
         } = connections;
-        let mut line = vec![];
+        let mut framer = LineFramer::default();
         let mut buffer = [0u8; 1024];
         let mut session = None;
         let mut rtt = None;
-        let mut now = Instant::now();
+        let mut backoff = Backoff::default();
+        let produce = |message: Vec<u8>| {
+            rx.produce(Arc::new(TimedBytes {
+                timestamp: Local::now(),
+                message,
+            }));
+        };
 
         'task_loop: loop {
             if let Ok(InterfaceCommand::Rtt(cmd)) = cmd_receiver.try_recv() {
                 let new_mode = match cmd {
-                    RttCommand::Connect => Self::connect(
-                        shared.clone(),
-                        &mut session,
-                        &mut rtt,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                        &mut last_address,
-                        &mut probe_speed_message,
-                        &mut fail_to_attach_message,
-                    ),
-                    RttCommand::Disconnect => Self::disconnect(
-                        shared.clone(),
-                        &mut session,
-                        &mut rtt,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                        &mut probe_speed_message,
-                        &mut fail_to_attach_message,
-                    ),
-                    RttCommand::Setup(setup) => Self::setup(
-                        shared.clone(),
-                        setup,
-                        &mut session,
-                        &mut rtt,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                        &mut probe_speed_message,
-                        &mut fail_to_attach_message,
-                    ),
+                    RttCommand::Connect => {
+                        backoff.reset();
+                        Self::connect(
+                            shared.clone(),
+                            &mut session,
+                            &mut rtt,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut last_address,
+                            &mut probe_speed_message,
+                            &mut fail_to_attach_message,
+                        )
+                    }
+                    RttCommand::Disconnect => {
+                        backoff.reset();
+                        Self::disconnect(
+                            shared.clone(),
+                            &mut session,
+                            &mut rtt,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut probe_speed_message,
+                            &mut fail_to_attach_message,
+                        )
+                    }
+                    RttCommand::Setup(setup) => {
+                        // Changing only the auto-reconnect keeps the pace of
+                        // the attempts.
+                        if setup.has_changes() {
+                            backoff.reset();
+                        }
+                        Self::setup(
+                            shared.clone(),
+                            setup,
+                            &mut session,
+                            &mut rtt,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut probe_speed_message,
+                            &mut fail_to_attach_message,
+                        )
+                    }
                     RttCommand::Read { address, size } => {
                         match Self::read_memory(session.as_mut(), address, size) {
                             Ok(data) => {
@@ -203,10 +239,20 @@ impl RttInterface {
 
                 match mode {
                     RttMode::DoNotConnect => {
+                        if let Some(line) = framer.flush() {
+                            produce(line);
+                        }
                         Self::wait(latency);
                         continue 'task_loop;
                     }
                     RttMode::Reconnecting => {
+                        let now = Instant::now();
+                        if !backoff.is_ready(now) {
+                            drop(sr);
+                            Self::wait(latency);
+                            continue 'task_loop;
+                        }
+
                         let new_mode = Self::connect(
                             shared.clone(),
                             &mut session,
@@ -217,6 +263,11 @@ impl RttInterface {
                             &mut probe_speed_message,
                             &mut fail_to_attach_message,
                         );
+                        if rtt.is_some() {
+                            backoff.reset();
+                        } else {
+                            backoff.failed(now);
+                        }
                         drop(sr);
                         Self::set_mode(shared.clone(), new_mode);
                     }
@@ -260,7 +311,7 @@ impl RttInterface {
                         &mut probe_speed_message,
                         &mut fail_to_attach_message,
                     );
-                    Self::set_mode(shared.clone(), Some(RttMode::Reconnecting));
+                    Self::on_lost_connection(shared.clone(), &logger);
                     Self::wait(latency);
                     continue 'task_loop;
                 };
@@ -285,35 +336,16 @@ impl RttInterface {
                         &mut probe_speed_message,
                         &mut fail_to_attach_message,
                     );
-                    Self::set_mode(shared.clone(), Some(RttMode::Reconnecting));
+                    Self::on_lost_connection(shared.clone(), &logger);
                     Self::wait(latency);
                     continue 'task_loop;
                 };
 
                 match input.read(&mut core, &mut buffer) {
                     Ok(size) => {
-                        if size > 0 {
-                            received_data = true;
-                            let mut parts = buffer[..size].split(|byte| *byte == b'\n').rev();
-                            let last = parts.next().unwrap_or(&[]);
-                            let parts = parts.rev();
-
-                            for part in parts {
-                                let mut part = part.to_vec();
-                                part.push(b'\n');
-
-                                rx.produce(Arc::new(TimedBytes {
-                                    timestamp: Local::now(),
-                                    message: part,
-                                }));
-
-                                now = Instant::now();
-                            }
-
-                            if last.len() > 0 {
-                                line.extend_from_slice(last);
-                                now = Instant::now();
-                            }
+                        received_data = size > 0;
+                        for line in framer.push(&buffer[..size], Instant::now()) {
+                            produce(line);
                         }
                     }
                     Err(e) => {
@@ -322,15 +354,8 @@ impl RttInterface {
                 }
             }
 
-            if now.elapsed().as_millis() > Self::NEW_LINE_TIMEOUT_MS {
-                now = Instant::now();
-
-                if !line.is_empty() {
-                    rx.produce(Arc::new(TimedBytes {
-                        timestamp: Local::now(),
-                        message: std::mem::take(&mut line),
-                    }));
-                }
+            if let Some(line) = framer.flush_idle(Instant::now()) {
+                produce(line);
             }
 
             rtt = Some(rtt_if);
@@ -359,6 +384,21 @@ impl RttInterface {
         sw.mode = mode;
     }
 
+    /// Reconnects or stops after losing the connection, following the
+    /// auto-reconnect.
+    fn on_lost_connection(shared: Arc<RwLock<InterfaceShared>>, logger: &Logger) {
+        let reconnects = shared
+            .read()
+            .expect("Failed to acquire read lock on RTT shared state")
+            .reconnects_on_lost_connection(logger);
+        let mode = if reconnects {
+            RttMode::Reconnecting
+        } else {
+            RttMode::DoNotConnect
+        };
+        Self::set_mode(shared, Some(mode));
+    }
+
     fn wait(latency: u64) {
         if latency > 0 {
             sleep(Duration::from_millis(latency));
@@ -456,27 +496,29 @@ impl RttInterface {
 
         let lister = Lister::new();
         let probes = lister.list_all();
-        let Some(new_session) =
+        let probe = if sr.probe.is_empty() {
+            probes.first()
+        } else {
             probes
-                .get(0)
-                .and_then(|probe| probe.open().ok())
-                .and_then(|mut probe| {
-                    let Ok(speed) = probe.set_speed(4_000) else {
-                        error!(logger, "Failed to set probe speed");
-                        return None;
-                    };
-                    Self::log_probe_speed(logger, probe_speed_message, speed);
-                    let res = probe.attach(&target, Permissions::default());
-                    Self::log_fail_to_attach(logger, fail_to_attach_message, &res);
-                    res.ok()
-                })
+                .iter()
+                .find(|probe| DebugProbeSelector::from(*probe).to_string() == sr.probe)
+        };
+        let Some(new_session) = probe
+            .and_then(|probe| probe.open().ok())
+            .and_then(|mut probe| {
+                let Ok(speed) = probe.set_speed(4_000) else {
+                    error!(logger, "Failed to set probe speed");
+                    return None;
+                };
+                Self::log_probe_speed(logger, probe_speed_message, speed);
+                let res = probe.attach(&target, Permissions::default());
+                Self::log_fail_to_attach(logger, fail_to_attach_message, &res);
+                res.ok()
+            })
         else {
             let _ = rtt.take();
             let _ = session.take();
-            return match sr.mode {
-                RttMode::Reconnecting => None,
-                _ => Some(RttMode::Reconnecting),
-            };
+            return Self::failed_connection_mode(sr);
         };
         *session = Some(new_session);
 
@@ -491,10 +533,7 @@ impl RttInterface {
         else {
             let _ = rtt.take();
             let _ = session.take();
-            return match sr.mode {
-                RttMode::Reconnecting => None,
-                _ => Some(RttMode::Reconnecting),
-            };
+            return Self::failed_connection_mode(sr);
         };
         *rtt = Some(new_rtt);
 
@@ -511,6 +550,15 @@ impl RttInterface {
         Some(RttMode::Connected)
     }
 
+    /// Mode after a failed attempt to connect, if it changes.
+    fn failed_connection_mode(sr: &RttShared) -> Option<RttMode> {
+        match (sr.mode, sr.auto_reconnect) {
+            (RttMode::Reconnecting, true) | (RttMode::DoNotConnect, false) => None,
+            (_, true) => Some(RttMode::Reconnecting),
+            (_, false) => Some(RttMode::DoNotConnect),
+        }
+    }
+
     fn disconnect(
         shared: Arc<RwLock<InterfaceShared>>,
         session: &mut Option<Session>,
@@ -563,7 +611,7 @@ impl RttInterface {
         probe_speed_message: &mut Option<String>,
         fail_to_attach_message: &mut Option<String>,
     ) -> Option<RttMode> {
-        let mut has_changes = false;
+        let has_changes = setup.has_changes();
         let mut sw = shared
             .write()
             .expect("Failed to acquire write lock on RTT shared state");
@@ -576,12 +624,18 @@ impl RttInterface {
 
         if let Some(target) = setup.target {
             sw_ref.target = target;
-            has_changes = true;
         }
 
         if let Some(channel) = setup.channel {
             sw_ref.channel = channel;
-            has_changes = true;
+        }
+
+        if let Some(probe) = setup.probe {
+            sw_ref.probe = probe;
+        }
+
+        if let Some(auto_reconnect) = setup.auto_reconnect {
+            sw_ref.auto_reconnect = auto_reconnect;
         }
 
         let last_mode = sw_ref.mode;
@@ -638,6 +692,31 @@ impl RttInterface {
     }
 }
 
+/// Connected debug probes, as (selector, description) pairs.
+pub fn list_probes() -> Vec<(String, String)> {
+    Lister::new()
+        .list_all()
+        .iter()
+        .map(|probe| {
+            (
+                DebugProbeSelector::from(probe).to_string(),
+                probe.to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Names of the known target chips starting with `name`.
+pub fn search_targets(name: &str) -> Vec<String> {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+    let mut targets = REGISTRY
+        .get_or_init(Registry::from_builtin_families)
+        .search_chips(name);
+    targets.dedup();
+    targets
+}
+
 impl RttConnections {
     pub fn new(
         logger: Logger,
@@ -5,7 +5,7 @@ use crate::{
         messages::TimedBytes,
         mpmc::{Consumer, Producer},
     },
-    interfaces::{InterfaceCommand, InterfaceShared},
+    interfaces::{InterfaceCommand, InterfaceShared, backoff::Backoff, framer::LineFramer},
     plugin::engine::PluginEngineCommand,
     success, warning,
 };
@@ -35,6 +35,8 @@ pub struct SerialShared {
     pub flow_control: FlowControl,
     pub parity: Parity,
     pub stop_bits: StopBits,
+    /// Whether a lost or failed connection is retried.
+    pub auto_reconnect: bool,
 }
 
 #[derive(Default)]
@@ -45,6 +47,21 @@ pub struct SerialSetup {
     pub flow_control: Option<FlowControl>,
     pub parity: Option<Parity>,
     pub stop_bits: Option<StopBits>,
+    pub auto_reconnect: Option<bool>,
+}
+
+impl SerialSetup {
+    /// Whether the connection settings change, which reconnects the port.
+    /// The auto-reconnect alone doesn't, see
+    /// [`InterfaceShared::reconnects_on_lost_connection`].
+    pub fn has_changes(&self) -> bool {
+        self.port.is_some()
+            || self.baudrate.is_some()
+            || self.data_bits.is_some()
+            || self.flow_control.is_some()
+            || self.parity.is_some()
+            || self.stop_bits.is_some()
+    }
 }
 
 pub struct SerialConnections {
@@ -80,6 +97,7 @@ impl SerialShared {
             flow_control: setup.flow_control.unwrap_or(FlowControl::None),
             parity: setup.parity.unwrap_or(Parity::None),
             stop_bits: setup.stop_bits.unwrap_or(StopBits::One),
+            auto_reconnect: setup.auto_reconnect.unwrap_or(true),
             mode: if !setup.port.unwrap_or("".to_string()).is_empty()
                 && setup.baudrate.unwrap_or(0) != 0
             {
@@ -92,8 +110,8 @@ impl SerialShared {
 }
 
 impl SerialInterface {
-    const NEW_LINE_TIMEOUT_MS: u128 = 1_000;
     const SERIAL_TIMEOUT_MS: u64 = 100;
+    const READ_BUFFER_SIZE: usize = 1024;
 
     fn set_mode(shared: Arc<RwLock<InterfaceShared>>, mode: Option<SerialMode>) {
         let Some(mode) = mode else {
@@ -111,6 +129,21 @@ impl SerialInterface {
         sw.mode = mode;
     }
 
+    /// Reconnects or stops after losing the connection, following the
+    /// auto-reconnect.
+    fn on_lost_connection(shared: Arc<RwLock<InterfaceShared>>, logger: &Logger) {
+        let reconnects = shared
+            .read()
+            .expect("Cannot get serial lock for read")
+            .reconnects_on_lost_connection(logger);
+        let mode = if reconnects {
+            SerialMode::Reconnecting
+        } else {
+            SerialMode::DoNotConnect
+        };
+        Self::set_mode(shared, Some(mode));
+    }
+
     fn wait(latency: u64) {
         if latency > 0 {
             sleep(Duration::from_micros(latency));
@@ -131,34 +164,57 @@ impl SerialInterface {
             plugin_engine_cmd_sender,
             latency,
         } = connections;
-        let mut line = vec![];
-        let mut buffer = [0u8];
+        let mut framer = LineFramer::default();
+        let mut buffer = [0u8; Self::READ_BUFFER_SIZE];
         let mut serial = None;
-        let mut now = Instant::now();
+        let mut fail_to_open_message = None;
+        let mut backoff = Backoff::default();
+        let produce = |message: Vec<u8>| {
+            rx.produce(Arc::new(TimedBytes {
+                timestamp: Local::now(),
+                message,
+            }));
+        };
 
         'task_loop: loop {
             if let Ok(InterfaceCommand::Serial(cmd)) = cmd_receiver.try_recv() {
                 let new_mode = match cmd {
-                    SerialCommand::Connect => Self::connect(
-                        shared.clone(),
-                        &mut serial,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                    ),
-                    SerialCommand::Disconnect => Self::disconnect(
-                        shared.clone(),
-                        &mut serial,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                    ),
+                    SerialCommand::Connect => {
+                        backoff.reset();
+                        Self::connect(
+                            shared.clone(),
+                            &mut serial,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut fail_to_open_message,
+                        )
+                    }
+                    SerialCommand::Disconnect => {
+                        backoff.reset();
+                        Self::disconnect(
+                            shared.clone(),
+                            &mut serial,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut fail_to_open_message,
+                        )
+                    }
                     SerialCommand::Exit => break 'task_loop,
-                    SerialCommand::Setup(setup) => Self::setup(
-                        shared.clone(),
-                        setup,
-                        &mut serial,
-                        &logger,
-                        &plugin_engine_cmd_sender,
-                    ),
+                    SerialCommand::Setup(setup) => {
+                        // Changing only the auto-reconnect keeps the pace of
+                        // the attempts.
+                        if setup.has_changes() {
+                            backoff.reset();
+                        }
+                        Self::setup(
+                            shared.clone(),
+                            setup,
+                            &mut serial,
+                            &logger,
+                            &plugin_engine_cmd_sender,
+                            &mut fail_to_open_message,
+                        )
+                    }
                 };
                 Self::set_mode(shared.clone(), new_mode);
             }
@@ -175,16 +231,32 @@ impl SerialInterface {
 
                 match mode {
                     SerialMode::DoNotConnect => {
+                        if let Some(line) = framer.flush() {
+                            produce(line);
+                        }
                         Self::wait(latency);
                         continue 'task_loop;
                     }
                     SerialMode::Reconnecting => {
+                        let now = Instant::now();
+                        if !backoff.is_ready(now) {
+                            drop(sr);
+                            Self::wait(latency);
+                            continue 'task_loop;
+                        }
+
                         let new_mode = Self::connect(
                             shared.clone(),
                             &mut serial,
                             &logger,
                             &plugin_engine_cmd_sender,
+                            &mut fail_to_open_message,
                         );
+                        if serial.is_some() {
+                            backoff.reset();
+                        } else {
+                            backoff.failed(now);
+                        }
                         drop(sr);
                         Self::set_mode(shared.clone(), new_mode);
                     }
@@ -204,15 +276,9 @@ impl SerialInterface {
             }
 
             match ser.read(&mut buffer) {
-                Ok(_) => {
-                    now = Instant::now();
-                    line.push(buffer[0]);
-                    if buffer[0] == b'\n' {
-                        rx.produce(Arc::new(TimedBytes {
-                            timestamp: Local::now(),
-                            message: std::mem::take(&mut line),
-                        }));
-                        now = Instant::now();
+                Ok(size) => {
+                    for line in framer.push(&buffer[..size], Instant::now()) {
+                        produce(line);
                     }
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
@@ -225,23 +291,20 @@ impl SerialInterface {
                         &mut Some(ser),
                         &logger,
                         &plugin_engine_cmd_sender,
+                        &mut fail_to_open_message,
                     );
-                    Self::set_mode(shared.clone(), Some(SerialMode::Reconnecting));
+                    if let Some(line) = framer.flush() {
+                        produce(line);
+                    }
+                    Self::on_lost_connection(shared.clone(), &logger);
                     Self::wait(latency);
                     continue 'task_loop;
                 }
                 Err(_) => {}
             }
 
-            if now.elapsed().as_millis() > Self::NEW_LINE_TIMEOUT_MS {
-                now = Instant::now();
-
-                if !line.is_empty() {
-                    rx.produce(Arc::new(TimedBytes {
-                        timestamp: Local::now(),
-                        message: std::mem::take(&mut line),
-                    }));
-                }
+            if let Some(line) = framer.flush_idle(Instant::now()) {
+                produce(line);
             }
 
             serial = Some(ser);
@@ -255,6 +318,7 @@ impl SerialInterface {
         serial: &mut Option<SerialPort>,
         logger: &Logger,
         plugin_engine_cmd_sender: &Sender<PluginEngineCommand>,
+        fail_to_open_message: &mut Option<String>,
     ) -> Option<SerialMode> {
         let sr = shared
             .read()
@@ -281,6 +345,7 @@ impl SerialInterface {
         match connect_res {
             Ok(ser) => {
                 *serial = Some(ser);
+                let _ = fail_to_open_message.take();
                 success!(
                     logger,
                     "Connected at \"{}\" with {}bps",
@@ -293,11 +358,18 @@ impl SerialInterface {
                 });
                 Some(SerialMode::Connected)
             }
-            Err(_) => {
+            Err(err) => {
                 let _ = serial.take();
-                match sr.mode {
-                    SerialMode::Reconnecting => None,
-                    _ => Some(SerialMode::Reconnecting),
+                // Reconnecting retries in a loop, so only a new error is logged.
+                let message = format!("Cannot open \"{}\": {}", sr.port, err);
+                if fail_to_open_message.as_ref() != Some(&message) {
+                    error!(logger, "{}", message);
+                    *fail_to_open_message = Some(message);
+                }
+                match (sr.mode, sr.auto_reconnect) {
+                    (SerialMode::Reconnecting, true) | (SerialMode::DoNotConnect, false) => None,
+                    (_, true) => Some(SerialMode::Reconnecting),
+                    (_, false) => Some(SerialMode::DoNotConnect),
                 }
             }
         }
@@ -308,8 +380,10 @@ impl SerialInterface {
         serial: &mut Option<SerialPort>,
         logger: &Logger,
         plugin_engine_cmd_sender: &Sender<PluginEngineCommand>,
+        fail_to_open_message: &mut Option<String>,
     ) -> Option<SerialMode> {
         let _ = serial.take();
+        let _ = fail_to_open_message.take();
         let sr = shared.read().expect("Cannot get serial lock for read");
         let sr = match sr.deref() {
             InterfaceShared::Serial(sr) => sr,
@@ -343,8 +417,9 @@ impl SerialInterface {
         serial: &mut Option<SerialPort>,
         logger: &Logger,
         plugin_engine_cmd_sender: &Sender<PluginEngineCommand>,
+        fail_to_open_message: &mut Option<String>,
     ) -> Option<SerialMode> {
-        let mut has_changes = false;
+        let has_changes = setup.has_changes();
         let mut sw = shared
             .write()
             .expect("Cannot get serial shared lock for write");
@@ -357,38 +432,42 @@ impl SerialInterface {
 
         if let Some(port) = setup.port {
             sw_ref.port = port;
-            has_changes = true;
         }
 
         if let Some(baudrate) = setup.baudrate {
             sw_ref.baudrate = baudrate;
-            has_changes = true;
         }
 
         if let Some(databits) = setup.data_bits {
             sw_ref.data_bits = databits;
-            has_changes = true;
         }
 
         if let Some(flow_control) = setup.flow_control {
             sw_ref.flow_control = flow_control;
-            has_changes = true;
         }
 
         if let Some(parity) = setup.parity {
             sw_ref.parity = parity;
-            has_changes = true;
         }
 
         if let Some(stop_bits) = setup.stop_bits {
             sw_ref.stop_bits = stop_bits;
-            has_changes = true;
+        }
+
+        if let Some(auto_reconnect) = setup.auto_reconnect {
+            sw_ref.auto_reconnect = auto_reconnect;
         }
 
         let last_mode = sw_ref.mode;
         if has_changes {
             drop(sw);
-            let _ = Self::disconnect(shared.clone(), serial, logger, plugin_engine_cmd_sender);
+            let _ = Self::disconnect(
+                shared.clone(),
+                serial,
+                logger,
+                plugin_engine_cmd_sender,
+                fail_to_open_message,
+            );
 
             match last_mode {
                 SerialMode::Reconnecting => None,
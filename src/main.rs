@@ -39,22 +39,23 @@ fn attach_parent_console() {
 }
 
 use crate::infra::tags::TagList;
-use crate::interfaces::rtt_if::{RttCommand, RttConnections, RttSetup};
-use crate::interfaces::serial_if::SerialCommand;
-use crate::interfaces::{InterfaceCommand, InterfaceTask, InterfaceType};
+use crate::interfaces::rtt_if::{RttConnections, RttSetup};
+use crate::interfaces::{Interface, InterfaceSetup, InterfaceTask, InterfaceType};
 use chrono::Local;
 use clap::{Parser, Subcommand};
+use graphics::buffer::Buffer;
 use graphics::graphics_task::{GraphicsConnections, GraphicsTask};
 use infra::logger::Logger;
 use infra::mpmc::Channel;
+use infra::task::Shared;
 use inputs::inputs_task::{InputsConnections, InputsTask};
 use interfaces::serial_if::{SerialConnections, SerialSetup};
 use list::list_serial_ports;
 use plugin::engine::{PluginEngine, PluginEngineConnections};
 use std::path::PathBuf;
 use std::process::exit;
-use std::sync::Arc;
 use std::sync::mpsc::channel;
+use std::sync::{Arc, RwLock};
 
 const DEFAULT_CAPACITY: usize = 2000;
 const DEFAULT_TAG_FILE: &str = "tags.yml";
@@ -127,14 +128,6 @@ fn app_serial(
     let (graphics_cmd_sender, graphics_cmd_receiver) = channel();
     let (plugin_engine_cmd_sender, plugin_engine_cmd_receiver) = channel();
 
-    let _ = serial_if_cmd_sender.send(InterfaceCommand::Serial(SerialCommand::Setup(
-        SerialSetup {
-            port,
-            baudrate,
-            ..SerialSetup::default()
-        },
-    )));
-
     let serial_connections = SerialConnections::new(
         logger.clone().with_source("serial".to_string()),
         tx_channel_consumers.pop().unwrap(),
@@ -142,15 +135,6 @@ fn app_serial(
         plugin_engine_cmd_sender.clone(),
         latency,
     );
-    let inputs_connections = InputsConnections::new(
-        logger.clone().with_source("inputs".to_string()),
-        tx_channel.clone().new_producer(),
-        graphics_cmd_sender.clone(),
-        serial_if_cmd_sender.clone(),
-        plugin_engine_cmd_sender.clone(),
-        rx_channel.clone().new_producer(),
-        InterfaceType::Serial,
-    );
 
     let serial_if = InterfaceTask::spawn_serial_interface(
         serial_connections,
@@ -158,17 +142,42 @@ fn app_serial(
         serial_if_cmd_receiver,
         SerialSetup::default(),
     );
+    serial_if.setup(InterfaceSetup::Serial(SerialSetup {
+        port,
+        baudrate,
+        ..SerialSetup::default()
+    }));
     let serial_shared = serial_if.shared_ref();
 
+    let buffer = Arc::new(RwLock::new(Buffer::new(capacity)));
+
     let plugin_engine_connections = PluginEngineConnections::new(
         logger.clone().with_source("plugin".to_string()),
-        tx_channel.new_producer(),
+        tx_channel.clone().new_producer(),
         tx_channel_consumers.pop().unwrap(),
         rx_channel_consumers.pop().unwrap(),
         serial_shared,
+        Shared::new(buffer.clone()),
         latency,
         InterfaceType::Serial,
+        serial_if_cmd_sender.clone(),
+    );
+
+    let plugin_engine = PluginEngine::spawn_plugin_engine(
+        plugin_engine_connections,
+        plugin_engine_cmd_sender.clone(),
+        plugin_engine_cmd_receiver,
+    );
+
+    let inputs_connections = InputsConnections::new(
+        logger.clone().with_source("inputs".to_string()),
+        tx_channel.new_producer(),
+        graphics_cmd_sender.clone(),
         serial_if_cmd_sender,
+        plugin_engine_cmd_sender,
+        plugin_engine.shared_ref(),
+        rx_channel.clone().new_producer(),
+        InterfaceType::Serial,
     );
 
     let inputs_task = InputsTask::spawn_inputs_task(
@@ -185,7 +194,6 @@ fn app_serial(
     let storage_base_filename = format!("{}.txt", now_str);
     let graphics_config = graphics::graphics_task::GraphicsConfig {
         storage_base_filename,
-        capacity,
         latency,
     };
     let graphics_connections = GraphicsConnections::new(
@@ -195,6 +203,7 @@ fn app_serial(
         rx_channel_consumers.pop().unwrap(),
         inputs_shared,
         serial_shared,
+        buffer,
         graphics_config,
     );
     let text_view = GraphicsTask::spawn_graphics_task(
@@ -202,11 +211,6 @@ fn app_serial(
         graphics_cmd_sender,
         graphics_cmd_receiver,
     );
-    let plugin_engine = PluginEngine::spawn_plugin_engine(
-        plugin_engine_connections,
-        plugin_engine_cmd_sender,
-        plugin_engine_cmd_receiver,
-    );
 
     serial_if.join();
     inputs_task.join();
@@ -250,12 +254,6 @@ fn app_rtt(
     let (graphics_cmd_sender, graphics_cmd_receiver) = channel();
     let (plugin_engine_cmd_sender, plugin_engine_cmd_receiver) = channel();
 
-    let _ = rtt_if_cmd_sender.send(InterfaceCommand::Rtt(RttCommand::Setup(RttSetup {
-        target,
-        channel: channel_num,
-        ..RttSetup::default()
-    })));
-
     let rtt_connections = RttConnections::new(
         logger.clone().with_source("rtt".to_string()),
         tx_channel_consumers.pop().unwrap(),
@@ -263,15 +261,6 @@ fn app_rtt(
         plugin_engine_cmd_sender.clone(),
         latency,
     );
-    let inputs_connections = InputsConnections::new(
-        logger.clone().with_source("inputs".to_string()),
-        tx_channel.clone().new_producer(),
-        graphics_cmd_sender.clone(),
-        rtt_if_cmd_sender.clone(),
-        plugin_engine_cmd_sender.clone(),
-        rx_channel.clone().new_producer(),
-        InterfaceType::Rtt,
-    );
 
     let rtt_if = InterfaceTask::spawn_rtt_interface(
         rtt_connections,
@@ -279,17 +268,42 @@ fn app_rtt(
         rtt_if_cmd_receiver,
         RttSetup::default(),
     );
+    rtt_if.setup(InterfaceSetup::Rtt(RttSetup {
+        target,
+        channel: channel_num,
+        ..RttSetup::default()
+    }));
     let rtt_shared = rtt_if.shared_ref();
 
+    let buffer = Arc::new(RwLock::new(Buffer::new(capacity)));
+
     let plugin_engine_connections = PluginEngineConnections::new(
         logger.clone().with_source("plugin".to_string()),
-        tx_channel.new_producer(),
+        tx_channel.clone().new_producer(),
         tx_channel_consumers.pop().unwrap(),
         rx_channel_consumers.pop().unwrap(),
         rtt_shared,
+        Shared::new(buffer.clone()),
         latency,
         InterfaceType::Rtt,
+        rtt_if_cmd_sender.clone(),
+    );
+
+    let plugin_engine = PluginEngine::spawn_plugin_engine(
+        plugin_engine_connections,
+        plugin_engine_cmd_sender.clone(),
+        plugin_engine_cmd_receiver,
+    );
+
+    let inputs_connections = InputsConnections::new(
+        logger.clone().with_source("inputs".to_string()),
+        tx_channel.new_producer(),
+        graphics_cmd_sender.clone(),
         rtt_if_cmd_sender,
+        plugin_engine_cmd_sender,
+        plugin_engine.shared_ref(),
+        rx_channel.clone().new_producer(),
+        InterfaceType::Rtt,
     );
 
     let inputs_task = InputsTask::spawn_inputs_task(
@@ -306,7 +320,6 @@ fn app_rtt(
     let storage_base_filename = format!("{}.txt", now_str);
     let graphics_config = graphics::graphics_task::GraphicsConfig {
         storage_base_filename,
-        capacity,
         latency,
     };
     let graphics_connections = GraphicsConnections::new(
@@ -316,6 +329,7 @@ fn app_rtt(
         rx_channel_consumers.pop().unwrap(),
         inputs_shared,
         rtt_shared,
+        buffer,
         graphics_config,
     );
     let text_view = GraphicsTask::spawn_graphics_task(
@@ -323,11 +337,6 @@ fn app_rtt(
         graphics_cmd_sender,
         graphics_cmd_receiver,
     );
-    let plugin_engine = PluginEngine::spawn_plugin_engine(
-        plugin_engine_connections,
-        plugin_engine_cmd_sender,
-        plugin_engine_cmd_receiver,
-    );
 
     rtt_if.join();
     inputs_task.join();
@@ -367,7 +376,7 @@ fn main() -> Result<(), String> {
             target,
             channel_num,
         } => app_rtt(capacity, tag_file, target, channel_num, latency),
-        Commands::Gui => gui::run_gui(),
+        Commands::Gui => gui::run_gui(tag_file),
     };
 
     if let Err(err) = result {
@@ -0,0 +1,215 @@
+use mlua::{Lua, Table};
+use std::fmt::Display;
+
+/// Version of the plugin API: the requests handled by `Scope` and the native
+/// modules. The minor version grows with each new feature, the major one
+/// only when a feature changes or is removed.
+pub const API_VERSION: ApiVersion = ApiVersion::new(1, 5);
+
+/// Features of the plugin API, by request id without the `:` or native
+/// module name, with the version that introduced them.
+const FEATURES: [(&str, ApiVersion); 45] = [
+    ("log.debug", ApiVersion::new(1, 0)),
+    ("log.info", ApiVersion::new(1, 0)),
+    ("log.success", ApiVersion::new(1, 0)),
+    ("log.warning", ApiVersion::new(1, 0)),
+    ("log.error", ApiVersion::new(1, 0)),
+    ("serial.info", ApiVersion::new(1, 0)),
+    ("serial.send", ApiVersion::new(1, 0)),
+    ("serial.recv", ApiVersion::new(1, 0)),
+    ("rtt.info", ApiVersion::new(1, 0)),
+    ("rtt.send", ApiVersion::new(1, 0)),
+    ("rtt.recv", ApiVersion::new(1, 0)),
+    ("rtt.read", ApiVersion::new(1, 0)),
+    ("sys.sleep", ApiVersion::new(1, 0)),
+    ("shell.run", ApiVersion::new(1, 0)),
+    ("shell.exist", ApiVersion::new(1, 0)),
+    ("re.literal", ApiVersion::new(1, 0)),
+    ("re.matches", ApiVersion::new(1, 0)),
+    ("re.match", ApiVersion::new(1, 0)),
+    ("permission.check", ApiVersion::new(1, 1)),
+    ("store.get", ApiVersion::new(1, 1)),
+    ("store.set", ApiVersion::new(1, 1)),
+    ("store.keys", ApiVersion::new(1, 1)),
+    ("store.shared.get", ApiVersion::new(1, 1)),
+    ("store.shared.set", ApiVersion::new(1, 1)),
+    ("store.shared.keys", ApiVersion::new(1, 1)),
+    ("bus.publish", ApiVersion::new(1, 1)),
+    ("bus.subscribe", ApiVersion::new(1, 1)),
+    ("bus.unsubscribe", ApiVersion::new(1, 1)),
+    ("shell.spawn", ApiVersion::new(1, 1)),
+    ("shell.read_line", ApiVersion::new(1, 1)),
+    ("shell.write", ApiVersion::new(1, 1)),
+    ("shell.kill", ApiVersion::new(1, 1)),
+    ("shell.wait", ApiVersion::new(1, 1)),
+    ("buffer.len", ApiVersion::new(1, 2)),
+    ("buffer.lines", ApiVersion::new(1, 2)),
+    ("buffer.search", ApiVersion::new(1, 2)),
+    ("codec", ApiVersion::new(1, 2)),
+    ("tags", ApiVersion::new(1, 2)),
+    ("net.tcp_connect", ApiVersion::new(1, 3)),
+    ("net.tcp_send", ApiVersion::new(1, 3)),
+    ("net.tcp_recv", ApiVersion::new(1, 3)),
+    ("net.tcp_close", ApiVersion::new(1, 3)),
+    ("net.udp_send", ApiVersion::new(1, 3)),
+    ("net.http_post", ApiVersion::new(1, 3)),
+    ("shell.close_stdin", ApiVersion::new(1, 4)),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parses `<major>.<minor>`. The minor version is optional.
+    pub fn parse(version: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid API version \"{}\", expected \"<major>.<minor>\"",
+                version
+            )
+        };
+        let (major, minor) = version.trim().split_once('.').unwrap_or((version, "0"));
+
+        Ok(Self {
+            major: major.trim().parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Version that introduced `feature`. A namespace, like `store`, is the
+/// version of its first feature.
+pub fn introduced_in(feature: &str) -> Option<ApiVersion> {
+    let feature = feature.strip_prefix(':').unwrap_or(feature);
+
+    FEATURES
+        .iter()
+        .filter(|(name, _)| {
+            *name == feature
+                || name
+                    .strip_prefix(feature)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .map(|(_, version)| *version)
+        .min()
+}
+
+pub fn has(feature: &str) -> bool {
+    introduced_in(feature).is_some_and(|version| version <= API_VERSION)
+}
+
+/// Checks the `required_api` of a plugin. Returns a warning when the plugin
+/// can be loaded but may not work, and an error when it can't be loaded.
+pub fn check_required(required: &str) -> Result<Option<String>, String> {
+    let required = ApiVersion::parse(required)?;
+
+    if required > API_VERSION {
+        return Err(format!(
+            "Plugin requires API v{}, but this Scope provides v{}. Update Scope to load it",
+            required, API_VERSION
+        ));
+    }
+
+    if required.major < API_VERSION.major {
+        return Ok(Some(format!(
+            "Plugin was written for API v{}, but this Scope provides v{}. It may not work",
+            required, API_VERSION
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Error of a request id that isn't handled by this Scope.
+pub fn unknown_request(req_id: &str) -> String {
+    match introduced_in(req_id) {
+        Some(version) => format!(
+            "Request \"{}\" was introduced in API v{}, but this Scope provides v{}",
+            req_id, version, API_VERSION
+        ),
+        None => format!(
+            "Unknown request \"{}\". This Scope provides API v{}, check the plugin required_api",
+            req_id, API_VERSION
+        ),
+    }
+}
+
+/// Makes the `scope.api` module, used by `scope.version()` and `scope.has()`,
+/// available to the plugin.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    let preload: Table = package.get("preload")?;
+
+    preload.set(
+        "scope.api",
+        lua.create_function(|lua, ()| {
+            let api = lua.create_table()?;
+
+            api.set(
+                "version",
+                lua.create_function(|_, ()| {
+                    Ok((
+                        API_VERSION.to_string(),
+                        API_VERSION.major,
+                        API_VERSION.minor,
+                    ))
+                })?,
+            )?;
+            api.set(
+                "has",
+                lua.create_function(|_, feature: String| Ok(has(&feature)))?,
+            )?;
+
+            Ok(api)
+        })?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{API_VERSION, ApiVersion, check_required, has, introduced_in, unknown_request};
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(ApiVersion::parse("1.2").unwrap(), ApiVersion::new(1, 2));
+        assert_eq!(ApiVersion::parse("2").unwrap(), ApiVersion::new(2, 0));
+        assert!(ApiVersion::parse("1.x").is_err());
+        assert!(ApiVersion::parse("").is_err());
+    }
+
+    #[test]
+    fn test_features() {
+        assert!(has("rtt.read"));
+        assert!(has(":store.shared.get"));
+        assert!(has("store"));
+        assert!(has("codec"));
+        assert!(!has("sto"));
+        assert!(has("net"));
+        assert!(!has("net.tcp"));
+        assert_eq!(introduced_in("bus"), Some(ApiVersion::new(1, 1)));
+        assert!(unknown_request(":serial.flush").contains(&format!("v{}", API_VERSION)));
+    }
+
+    #[test]
+    fn test_check_required() {
+        assert_eq!(check_required("1.0").unwrap(), None);
+        assert_eq!(check_required(&API_VERSION.to_string()).unwrap(), None);
+        assert!(check_required("0.9").unwrap().is_some());
+        assert!(check_required("1.99").is_err());
+        assert!(check_required("2.0").is_err());
+        assert!(check_required("latest").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    graphics::buffer::{Buffer, BufferLine},
+    infra::{logger::LogLevel, task::Shared},
+};
+use mlua::{IntoLua, Lua, Value};
+use regex::bytes::Regex;
+use std::ops::Range;
+
+/// Lines searched on each read of the buffer. The lock is released between
+/// them, so the screen keeps being updated during long searches.
+const SEARCH_CHUNK: usize = 1024;
+
+/// A copy of a line on the screen buffer, as seen by the plugins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PluginBufferLine {
+    /// 1-based, like Lua indexes.
+    pub line: usize,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    pub kind: &'static str,
+    pub level: Option<&'static str>,
+    pub message: Vec<u8>,
+}
+
+impl From<&BufferLine<Vec<u8>>> for PluginBufferLine {
+    fn from(line: &BufferLine<Vec<u8>>) -> Self {
+        let kind = match (line.level, line.is_tx) {
+            (Some(_), _) => "log",
+            (None, true) => "tx",
+            (None, false) => "rx",
+        };
+        let level = line.level.map(|level| match level {
+            LogLevel::Error => "error",
+            LogLevel::Warning => "warning",
+            LogLevel::Success => "success",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        });
+
+        Self {
+            line: line.line + 1,
+            timestamp: line.timestamp.timestamp_millis(),
+            kind,
+            level,
+            message: line.message.clone(),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for PluginBufferLine {
+    fn into_lua(self, lua: &'lua Lua) -> mlua::Result<Value<'lua>> {
+        let table = lua.create_table()?;
+
+        table.set("line", self.line)?;
+        table.set("timestamp", self.timestamp)?;
+        table.set("kind", self.kind)?;
+        table.set("level", self.level)?;
+        table.set("message", lua.create_string(&self.message)?)?;
+
+        Ok(Value::Table(table))
+    }
+}
+
+/// Lines `from` to `to`, both included. Like `string.sub`, negative indexes
+/// count from the last line.
+pub fn lines(buffer: &Buffer, from: i64, to: i64) -> Vec<PluginBufferLine> {
+    let Range { start, end } = lua_range(from, to, buffer.len());
+
+    buffer
+        .get_range(start, end)
+        .iter()
+        .map(PluginBufferLine::from)
+        .collect()
+}
+
+/// Lines `from` to `to` whose message matches `pattern`, up to `max` of them.
+/// The indexes are resolved once, so lines dropped during the search, when
+/// the buffer is full, shift the remaining ones.
+pub fn search(
+    buffer: &Shared<Buffer>,
+    pattern: &str,
+    from: i64,
+    to: i64,
+    max: usize,
+) -> Result<Vec<PluginBufferLine>, String> {
+    let regex = Regex::new(pattern).map_err(|err| err.to_string())?;
+    let Range { mut start, end } = {
+        let buffer = buffer.read().expect("Cannot get buffer lock for read");
+        lua_range(from, to, buffer.len())
+    };
+    let mut found = vec![];
+
+    while start < end && found.len() < max {
+        let chunk_end = end.min(start + SEARCH_CHUNK);
+        let buffer = buffer.read().expect("Cannot get buffer lock for read");
+        let lines = buffer.get_range(start, chunk_end);
+        if lines.is_empty() {
+            break;
+        }
+
+        found.extend(
+            lines
+                .iter()
+                .filter(|line| regex.is_match(&line.message))
+                .take(max - found.len())
+                .map(PluginBufferLine::from),
+        );
+        start = chunk_end;
+    }
+
+    Ok(found)
+}
+
+fn lua_range(from: i64, to: i64, len: usize) -> Range<usize> {
+    let index = |i: i64| {
+        if i < 0 {
+            (len as i64 + i + 1).max(0) as usize
+        } else {
+            (i as usize).min(len)
+        }
+    };
+
+    let start = index(from).max(1);
+    let end = index(to);
+
+    if start > end { 0..0 } else { start - 1..end }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PluginBufferLine, SEARCH_CHUNK, lines, lua_range, search};
+    use crate::{
+        graphics::buffer::{Buffer, BufferLine},
+        infra::{logger::LogLevel, task::Shared},
+    };
+    use chrono::Local;
+    use std::sync::{Arc, RwLock};
+
+    fn buffer() -> Buffer {
+        let mut buffer = Buffer::new(10);
+
+        buffer += BufferLine::new_tx(Local::now(), b"reset".to_vec());
+        buffer += BufferLine::new_rx(Local::now(), b"boot: ok".to_vec());
+        buffer += BufferLine::new_log(Local::now(), LogLevel::Warning, b"slow".to_vec());
+        buffer += BufferLine::new_rx(Local::now(), b"boot: reset by watchdog".to_vec());
+
+        buffer
+    }
+
+    #[test]
+    fn test_lua_range() {
+        assert_eq!(lua_range(1, 4, 4), 0..4);
+        assert_eq!(lua_range(2, -1, 4), 1..4);
+        assert_eq!(lua_range(-2, -1, 4), 2..4);
+        assert_eq!(lua_range(0, 100, 4), 0..4);
+        assert_eq!(lua_range(3, 2, 4), 0..0);
+        assert_eq!(lua_range(1, -1, 0), 0..0);
+    }
+
+    #[test]
+    fn test_lines() {
+        let buffer = buffer();
+        let lines = lines(&buffer, -2, -1);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].line, 3);
+        assert_eq!(lines[0].kind, "log");
+        assert_eq!(lines[0].level, Some("warning"));
+        assert_eq!(lines[1].message, b"boot: reset by watchdog");
+    }
+
+    #[test]
+    fn test_search() {
+        let buffer = Shared::new(Arc::new(RwLock::new(buffer())));
+        let found = |pattern, from, to, max| {
+            search(&buffer, pattern, from, to, max)
+                .unwrap()
+                .into_iter()
+                .map(|line: PluginBufferLine| (line.line, line.kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(found("^boot: .*reset", 1, -1, usize::MAX), [(4, "rx")]);
+        assert_eq!(found("boot", 1, -1, usize::MAX), [(2, "rx"), (4, "rx")]);
+        assert_eq!(found("boot", 1, -1, 1), [(2, "rx")]);
+        assert_eq!(found("boot", 3, -1, usize::MAX), [(4, "rx")]);
+        assert_eq!(found("boot", 1, 3, 0), []);
+        assert!(search(&buffer, "(", 1, -1, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_search_chunks() {
+        let mut lines = Buffer::new(3 * SEARCH_CHUNK);
+        for i in 0..3 * SEARCH_CHUNK {
+            lines += BufferLine::new_rx(Local::now(), format!("line {}", i).into_bytes());
+        }
+        let buffer = Shared::new(Arc::new(RwLock::new(lines)));
+
+        let found = search(&buffer, "0$", 1, -1, usize::MAX).unwrap();
+        assert_eq!(found.len(), 3 * SEARCH_CHUNK / 10 + 1);
+        assert_eq!(found.last().unwrap().message, b"line 3070");
+
+        let found = search(&buffer, "line", -(SEARCH_CHUNK as i64) - 2, -1, 5).unwrap();
+        assert_eq!(found.len(), 5);
+        assert_eq!(found[0].line, 2 * SEARCH_CHUNK - 1);
+    }
+}
@@ -0,0 +1,615 @@
+use mlua::{Lua, MultiValue, Table, Value, Variadic};
+
+const SLIP_END: u8 = 0xC0;
+const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrcParams {
+    pub width: u32,
+    pub poly: u64,
+    pub init: u64,
+    pub refin: bool,
+    pub refout: bool,
+    pub xorout: u64,
+}
+
+/// CRC-8/SMBUS
+pub const CRC8: CrcParams = CrcParams {
+    width: 8,
+    poly: 0x07,
+    init: 0x00,
+    refin: false,
+    refout: false,
+    xorout: 0x00,
+};
+
+/// CRC-16/CCITT-FALSE
+pub const CRC16: CrcParams = CrcParams {
+    width: 16,
+    poly: 0x1021,
+    init: 0xFFFF,
+    refin: false,
+    refout: false,
+    xorout: 0x0000,
+};
+
+/// CRC-32/ISO-HDLC, the one used by Ethernet and zlib
+pub const CRC32: CrcParams = CrcParams {
+    width: 32,
+    poly: 0x04C11DB7,
+    init: 0xFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFF,
+};
+
+pub fn crc(params: &CrcParams, data: &[u8]) -> u64 {
+    let mask = u64::MAX >> (64 - params.width);
+    let top = 1 << (params.width - 1);
+    let mut crc = params.init & mask;
+
+    for &byte in data {
+        let byte = if params.refin {
+            byte.reverse_bits()
+        } else {
+            byte
+        };
+        crc ^= (byte as u64) << (params.width - 8);
+
+        for _ in 0..8 {
+            crc = if crc & top != 0 {
+                (crc << 1) ^ params.poly
+            } else {
+                crc << 1
+            } & mask;
+        }
+    }
+
+    if params.refout {
+        crc = crc.reverse_bits() >> (64 - params.width);
+    }
+
+    (crc ^ params.xorout) & mask
+}
+
+/// Encodes a COBS frame, without the trailing zero delimiter.
+pub fn cobs_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0];
+    let mut code_idx = 0;
+    let mut code = 1u8;
+
+    for &byte in data {
+        if byte != 0 {
+            out.push(byte);
+            code += 1;
+        }
+
+        if byte == 0 || code == 0xFF {
+            out[code_idx] = code;
+            code_idx = out.len();
+            out.push(0);
+            code = 1;
+        }
+    }
+
+    out[code_idx] = code;
+    out
+}
+
+/// Decodes a COBS frame. The trailing zero delimiter is optional.
+pub fn cobs_decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+
+    while i < data.len() {
+        let code = data[i] as usize;
+        if code == 0 {
+            return Err(format!("Unexpected zero byte at {}", i));
+        }
+
+        let end = i + code;
+        let Some(block) = data.get(i + 1..end) else {
+            return Err("Truncated COBS frame".to_string());
+        };
+        if block.contains(&0) {
+            return Err("Unexpected zero byte inside COBS block".to_string());
+        }
+
+        out.extend_from_slice(block);
+        i = end;
+
+        if code < 0xFF && i < data.len() {
+            out.push(0);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Encodes a SLIP frame, with an `END` byte on both sides.
+pub fn slip_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 2);
+    out.push(SLIP_END);
+
+    for &byte in data {
+        match byte {
+            SLIP_END => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            byte => out.push(byte),
+        }
+    }
+
+    out.push(SLIP_END);
+    out
+}
+
+/// Decodes a SLIP frame. `END` bytes are skipped.
+pub fn slip_decode(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            SLIP_END => {}
+            SLIP_ESC => match bytes.next() {
+                Some(&SLIP_ESC_END) => out.push(SLIP_END),
+                Some(&SLIP_ESC_ESC) => out.push(SLIP_ESC),
+                Some(byte) => return Err(format!("Invalid SLIP escape 0x{:02X}", byte)),
+                None => return Err("Truncated SLIP escape".to_string()),
+            },
+            byte => out.push(byte),
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+/// Decodes base64, ignoring whitespace. The `=` padding is optional.
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for c in text.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let Some(value) = BASE64_ALPHABET.iter().position(|&a| a as char == c) else {
+            return Err(format!("Invalid base64 character '{}'", c));
+        };
+
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Offset, hex bytes and printable ASCII, 16 bytes per line.
+pub fn hex_dump(data: &[u8]) -> String {
+    data.chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = chunk
+                .iter()
+                .map(|&byte| {
+                    if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+
+            format!("{:08x}  {:<47}  |{}|", i * 16, hex, ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackValue {
+    Int(i64),
+    Float(f64),
+}
+
+/// Parses a `struct` like format: `<` little-endian (default), `>` big-endian,
+/// `b`/`B` 8 bits, `h`/`H` 16 bits, `i`/`I` 32 bits and `q`/`Q` 64 bits
+/// integers (lowercase is signed), `f` float and `d` double.
+fn pack_format(format: &str) -> Result<Vec<(char, bool)>, String> {
+    let mut little_endian = true;
+    let mut items = vec![];
+
+    for c in format.chars().filter(|c| !c.is_whitespace()) {
+        match c {
+            '<' => little_endian = true,
+            '>' => little_endian = false,
+            'b' | 'B' | 'h' | 'H' | 'i' | 'I' | 'q' | 'Q' | 'f' | 'd' => {
+                items.push((c, little_endian))
+            }
+            c => return Err(format!("Invalid format character '{}'", c)),
+        }
+    }
+
+    Ok(items)
+}
+
+fn pack_size(item: char) -> usize {
+    match item {
+        'b' | 'B' => 1,
+        'h' | 'H' => 2,
+        'i' | 'I' | 'f' => 4,
+        _ => 8,
+    }
+}
+
+pub fn pack(format: &str, values: &[PackValue]) -> Result<Vec<u8>, String> {
+    let items = pack_format(format)?;
+    if items.len() != values.len() {
+        return Err(format!(
+            "Format expects {} values, got {}",
+            items.len(),
+            values.len()
+        ));
+    }
+
+    let mut out = vec![];
+    for ((item, little_endian), value) in items.into_iter().zip(values) {
+        let (int, float) = match *value {
+            PackValue::Int(int) => (int, int as f64),
+            PackValue::Float(float) => (float as i64, float),
+        };
+
+        let bytes = match item {
+            'f' => (float as f32).to_le_bytes().to_vec(),
+            'd' => float.to_le_bytes().to_vec(),
+            item => int.to_le_bytes()[..pack_size(item)].to_vec(),
+        };
+
+        if little_endian {
+            out.extend(bytes);
+        } else {
+            out.extend(bytes.into_iter().rev());
+        }
+    }
+
+    Ok(out)
+}
+
+/// Unpacks the values starting at `offset` and returns them with the offset
+/// right after the last one.
+pub fn unpack(format: &str, data: &[u8], offset: usize) -> Result<(Vec<PackValue>, usize), String> {
+    let mut offset = offset;
+    let mut values = vec![];
+
+    for (item, little_endian) in pack_format(format)? {
+        let size = pack_size(item);
+        let bytes = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end));
+        let Some(bytes) = bytes else {
+            return Err(format!(
+                "Not enough data to unpack '{}' at {}",
+                item,
+                offset.saturating_add(1)
+            ));
+        };
+
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if !little_endian {
+            buf[..size].reverse();
+        }
+
+        let value = match item {
+            'b' => PackValue::Int(buf[0] as i8 as i64),
+            'B' => PackValue::Int(buf[0] as i64),
+            'h' => PackValue::Int(i16::from_le_bytes([buf[0], buf[1]]) as i64),
+            'H' => PackValue::Int(u16::from_le_bytes([buf[0], buf[1]]) as i64),
+            'i' => PackValue::Int(i32::from_le_bytes(buf[..4].try_into().unwrap()) as i64),
+            'I' => PackValue::Int(u32::from_le_bytes(buf[..4].try_into().unwrap()) as i64),
+            'f' => PackValue::Float(f32::from_le_bytes(buf[..4].try_into().unwrap()) as f64),
+            'd' => PackValue::Float(f64::from_le_bytes(buf)),
+            _ => PackValue::Int(i64::from_le_bytes(buf)),
+        };
+
+        values.push(value);
+        offset += size;
+    }
+
+    Ok((values, offset))
+}
+
+/// Bytes from a Lua string or a table of bytes, like the messages received
+/// by the plugins.
+pub fn bytes(value: Value) -> mlua::Result<Vec<u8>> {
+    match value {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Table(table) => table
+            .sequence_values::<i64>()
+            .map(|byte| byte.map(|byte| (byte & 0xFF) as u8))
+            .collect(),
+        value => Err(mlua::Error::runtime(format!(
+            "Expected a string or a table of bytes, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+fn crc_params(default: CrcParams, opts: Option<Table>) -> mlua::Result<CrcParams> {
+    let Some(opts) = opts else {
+        return Ok(default);
+    };
+
+    Ok(CrcParams {
+        width: default.width,
+        poly: opts.get::<_, Option<u64>>("poly")?.unwrap_or(default.poly),
+        init: opts.get::<_, Option<u64>>("init")?.unwrap_or(default.init),
+        refin: opts
+            .get::<_, Option<bool>>("refin")?
+            .unwrap_or(default.refin),
+        refout: opts
+            .get::<_, Option<bool>>("refout")?
+            .unwrap_or(default.refout),
+        xorout: opts
+            .get::<_, Option<u64>>("xorout")?
+            .unwrap_or(default.xorout),
+    })
+}
+
+fn result_to_lua<T>(res: Result<T, String>) -> (Option<T>, Option<String>) {
+    match res {
+        Ok(value) => (Some(value), None),
+        Err(err) => (None, Some(err)),
+    }
+}
+
+fn module(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let codec = lua.create_table()?;
+
+    for (name, default) in [("crc8", CRC8), ("crc16", CRC16), ("crc32", CRC32)] {
+        codec.set(
+            name,
+            lua.create_function(move |_, (data, opts): (Value, Option<Table>)| {
+                Ok(crc(&crc_params(default, opts)?, &bytes(data)?))
+            })?,
+        )?;
+    }
+
+    codec.set(
+        "cobs_encode",
+        lua.create_function(|_, data: Value| Ok(cobs_encode(&bytes(data)?)))?,
+    )?;
+    codec.set(
+        "cobs_decode",
+        lua.create_function(|_, data: Value| Ok(result_to_lua(cobs_decode(&bytes(data)?))))?,
+    )?;
+    codec.set(
+        "slip_encode",
+        lua.create_function(|_, data: Value| Ok(slip_encode(&bytes(data)?)))?,
+    )?;
+    codec.set(
+        "slip_decode",
+        lua.create_function(|_, data: Value| Ok(result_to_lua(slip_decode(&bytes(data)?))))?,
+    )?;
+    codec.set(
+        "base64_encode",
+        lua.create_function(|_, data: Value| Ok(base64_encode(&bytes(data)?)))?,
+    )?;
+    codec.set(
+        "base64_decode",
+        lua.create_function(|_, text: String| Ok(result_to_lua(base64_decode(&text))))?,
+    )?;
+    codec.set(
+        "hex_dump",
+        lua.create_function(|_, data: Value| Ok(hex_dump(&bytes(data)?)))?,
+    )?;
+    codec.set(
+        "pack",
+        lua.create_function(|_, (format, values): (String, Variadic<Value>)| {
+            let values = values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Integer(int) => Ok(PackValue::Int(int)),
+                    Value::Number(float) => Ok(PackValue::Float(float)),
+                    value => Err(mlua::Error::runtime(format!(
+                        "Cannot pack a value of type {}",
+                        value.type_name()
+                    ))),
+                })
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            pack(&format, &values).map_err(mlua::Error::runtime)
+        })?,
+    )?;
+    codec.set(
+        "unpack",
+        lua.create_function(|_, (format, data, pos): (String, Value, Option<usize>)| {
+            let data = bytes(data)?;
+            let offset = pos.unwrap_or(1).max(1) - 1;
+            let (values, offset) = unpack(&format, &data, offset).map_err(mlua::Error::runtime)?;
+
+            let mut ret = values
+                .into_iter()
+                .map(|value| match value {
+                    PackValue::Int(int) => Value::Integer(int),
+                    PackValue::Float(float) => Value::Number(float),
+                })
+                .collect::<Vec<_>>();
+            ret.push(Value::Integer(offset as i64 + 1));
+
+            Ok(MultiValue::from_vec(ret))
+        })?,
+    )?;
+
+    Ok(codec)
+}
+
+/// Makes the native `codec` module available through `require("codec")`.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    let preload: Table = package.get("preload")?;
+
+    preload.set("codec", lua.create_function(|lua, ()| module(lua))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc(&CRC8, CHECK), 0xF4);
+        assert_eq!(crc(&CRC16, CHECK), 0x29B1);
+        assert_eq!(crc(&CRC32, CHECK), 0xCBF43926);
+
+        let modbus = CrcParams {
+            width: 16,
+            poly: 0x8005,
+            init: 0xFFFF,
+            refin: true,
+            refout: true,
+            xorout: 0,
+        };
+        assert_eq!(crc(&modbus, CHECK), 0x4B37);
+    }
+
+    #[test]
+    fn test_cobs() {
+        assert_eq!(cobs_encode(&[]), [0x01]);
+        assert_eq!(cobs_encode(&[0x00]), [0x01, 0x01]);
+        assert_eq!(
+            cobs_encode(&[0x11, 0x22, 0x00, 0x33]),
+            [0x03, 0x11, 0x22, 0x02, 0x33]
+        );
+
+        let long = (1..=255).map(|b| b as u8).collect::<Vec<_>>();
+        assert_eq!(cobs_decode(&cobs_encode(&long)).unwrap(), long);
+        assert_eq!(
+            cobs_decode(&[0x03, 0x11, 0x22, 0x02, 0x33, 0x00]).unwrap(),
+            [0x11, 0x22, 0x00, 0x33]
+        );
+        assert!(cobs_decode(&[0x05, 0x11]).is_err());
+    }
+
+    #[test]
+    fn test_slip() {
+        let data = [0x01, SLIP_END, 0x02, SLIP_ESC];
+        let encoded = slip_encode(&data);
+
+        assert_eq!(
+            encoded,
+            [
+                SLIP_END,
+                0x01,
+                SLIP_ESC,
+                SLIP_ESC_END,
+                0x02,
+                SLIP_ESC,
+                SLIP_ESC_ESC,
+                SLIP_END
+            ]
+        );
+        assert_eq!(slip_decode(&encoded).unwrap(), data);
+        assert!(slip_decode(&[SLIP_ESC, 0x01]).is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64_encode(b"Scope"), "U2NvcGU=");
+        assert_eq!(base64_encode(b"Sc"), "U2M=");
+        assert_eq!(base64_decode("U2NvcGU=").unwrap(), b"Scope");
+        assert_eq!(base64_decode("U2M").unwrap(), b"Sc");
+        assert!(base64_decode("U2*=").is_err());
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        assert_eq!(
+            hex_dump(b"Hello\x00"),
+            format!("00000000  {:<47}  |Hello.|", "48 65 6c 6c 6f 00")
+        );
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let values = [
+            PackValue::Int(-2),
+            PackValue::Int(0xBEEF),
+            PackValue::Float(1.5),
+        ];
+        let packed = pack("<b >H <f", &values).unwrap();
+
+        assert_eq!(packed, [0xFE, 0xBE, 0xEF, 0x00, 0x00, 0xC0, 0x3F]);
+        assert_eq!(
+            unpack("<b >H <f", &packed, 0).unwrap(),
+            (values.to_vec(), 7)
+        );
+        assert!(unpack("I", &packed, 5).is_err());
+        assert!(unpack("I", &packed, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_lua_module() {
+        let lua = Lua::new();
+        register(&lua).unwrap();
+
+        let (crc, a, b, pos): (u64, i64, f64, usize) = lua
+            .load(
+                "local codec = require('codec')
+                 local bytes = codec.pack('<Hf', 258, 0.25)
+                 local a, b, pos = codec.unpack('<Hf', bytes)
+                 return codec.crc32('123456789'), a, b, pos",
+            )
+            .eval()
+            .unwrap();
+
+        assert_eq!(crc, 0xCBF43926);
+        assert_eq!((a, b, pos), (258, 0.25, 7));
+
+        let err = lua
+            .load("require('codec').unpack('<I', 'abcd', math.maxinteger)")
+            .exec()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Not enough data"), "{}", err);
+    }
+}
@@ -1,23 +1,33 @@
 use super::{
-    Plugin, PluginUnloadMode,
+    Plugin, PluginCommand, PluginUnloadMode,
     bridge::{PluginEngineGate, PluginMethodCallGate},
+    buffer, errors,
     messages::{self, PluginExternalRequest, PluginMethodMessage, PluginResponse},
+    permissions::{Capability, PermissionState},
+    store::{LuaData, PluginStore},
+    wasm,
 };
 use crate::{
-    error,
+    debug, error,
+    graphics::{
+        buffer::Buffer,
+        special_char::{SpecialCharItem, ToSpecialChar},
+    },
+    info,
     infra::{
         logger::{LogLevel, Logger},
         messages::TimedBytes,
         mpmc::{Consumer, Producer},
+        tags,
         task::{Shared, Task},
     },
+    inputs::inputs_task::InputsTask,
     interfaces::{InterfaceCommand, InterfaceShared, InterfaceType, rtt_if::RttCommand},
     success, warning,
 };
 use chrono::Local;
-use regex::Regex;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     ops::Deref,
     path::PathBuf,
     str::FromStr,
@@ -30,7 +40,13 @@ use tokio::{
     task::{self, yield_now},
     time::sleep,
 };
-pub type PluginEngine = Task<(), PluginEngineCommand>;
+pub type PluginEngine = Task<PluginEngineShared, PluginEngineCommand>;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Max tx or rx messages dispatched to the plugins on each engine loop, so a
+/// burst of messages is handled without a sleep between each of them. It's
+/// also the max length of the lists given to the `_batch` hooks.
+const MAX_HOOK_BATCH: usize = 32;
 
 pub enum PluginEngineCommand {
     SetLogLevel {
@@ -43,6 +59,26 @@ pub enum PluginEngineCommand {
     UnloadPlugin {
         plugin_name: String,
     },
+    WatchPlugin {
+        plugin_name: String,
+        enable: bool,
+    },
+    StopPlugin {
+        plugin_name: Option<String>,
+        command: Option<String>,
+    },
+    SetPermission {
+        plugin_name: String,
+        capability: String,
+        allow: bool,
+    },
+    ListPlugins,
+    PluginStats {
+        plugin_name: Option<String>,
+    },
+    PluginHelp {
+        plugin_name: String,
+    },
     UserCommand {
         plugin_name: String,
         command: String,
@@ -70,15 +106,33 @@ pub enum PluginEngineCommand {
         err: String,
         data: Vec<u8>,
     },
+    /// A message with plugin tags, sent once their values are computed.
+    SendWithTags {
+        command_line: String,
+        end_bytes: Vec<u8>,
+    },
     Exit,
 }
 
+#[derive(Default)]
+pub struct PluginEngineShared {
+    pub plugins: Vec<PluginInfo>,
+}
+
+#[derive(Clone)]
+pub struct PluginInfo {
+    pub name: Arc<String>,
+    pub commands: Vec<PluginCommand>,
+    pub tags: Vec<String>,
+}
+
 pub struct PluginEngineConnections {
     logger: Logger,
     tx_producer: Producer<Arc<TimedBytes>>,
     tx_consumer: Consumer<Arc<TimedBytes>>,
     rx: Consumer<Arc<TimedBytes>>,
     interface_shared: Shared<InterfaceShared>,
+    buffer: Shared<Buffer>,
     latency: u64,
     interface_type: InterfaceType,
     interface_cmd_sender: Sender<InterfaceCommand>,
@@ -90,11 +144,17 @@ impl PluginEngine {
         sender: std::sync::mpsc::Sender<PluginEngineCommand>,
         receiver: std::sync::mpsc::Receiver<PluginEngineCommand>,
     ) -> Self {
-        Self::new((), connections, Self::task, sender, receiver)
+        Self::new(
+            PluginEngineShared::default(),
+            connections,
+            Self::task,
+            sender,
+            receiver,
+        )
     }
 
     pub fn task(
-        shared: Arc<RwLock<()>>,
+        shared: Arc<RwLock<PluginEngineShared>>,
         private: PluginEngineConnections,
         cmd_receiver: Receiver<PluginEngineCommand>,
     ) {
@@ -112,18 +172,29 @@ impl PluginEngine {
     }
 
     pub async fn task_async(
-        _shared: Arc<RwLock<()>>,
+        shared: Arc<RwLock<PluginEngineShared>>,
         private: PluginEngineConnections,
         cmd_receiver: Receiver<PluginEngineCommand>,
     ) {
         let mut plugin_list: HashMap<Arc<String>, Plugin> = HashMap::new();
+        let mut watched_plugins: HashSet<String> = HashSet::new();
+        let mut last_watch_check = Instant::now();
+        let mut is_shared_outdated = false;
         let mut engine_gate = PluginEngineGate::new(32);
         let mut interface_recv_reqs = vec![];
         let mut rtt_read_reqs = vec![];
-        let err_regex = Regex::new(r#".*: \[string ".*"]:"#).unwrap();
+        let mut permission_reqs: Vec<PluginMethodMessage<PluginExternalRequest>> = vec![];
+        let store = PluginStore::shared();
 
         'plugin_engine_loop: loop {
+            if is_shared_outdated {
+                is_shared_outdated = false;
+                Self::update_shared(&shared, &plugin_list);
+            }
+
             if let Ok(cmd) = cmd_receiver.try_recv() {
+                is_shared_outdated = true;
+
                 match cmd {
                     PluginEngineCommand::Exit => break 'plugin_engine_loop,
                     PluginEngineCommand::SetLogLevel {
@@ -175,7 +246,9 @@ impl PluginEngine {
                         .await
                         {
                             Ok(_) => success!(private.logger, "Plugin \"{}\" loaded", plugin_name),
-                            Err(err) => error!(private.logger, "{}", err_regex.replace(&err, "")),
+                            Err(err) => {
+                                Self::report_load_error(&private.logger, &plugin_name, &err)
+                            }
                         }
                     }
                     PluginEngineCommand::UnloadPlugin { plugin_name } => {
@@ -191,6 +264,32 @@ impl PluginEngine {
                             false,
                         );
                         plugin.set_unload_mode(PluginUnloadMode::Unload);
+                        watched_plugins.remove(&plugin_name);
+                    }
+                    PluginEngineCommand::WatchPlugin {
+                        plugin_name,
+                        enable,
+                    } => {
+                        if !plugin_list.contains_key(&plugin_name) {
+                            error!(private.logger, "Plugin \"{}\" not loaded", plugin_name);
+                            continue 'plugin_engine_loop;
+                        }
+
+                        if enable {
+                            watched_plugins.insert(plugin_name.clone());
+                            success!(
+                                private.logger,
+                                "Watching plugin \"{}\" for changes",
+                                plugin_name
+                            );
+                        } else {
+                            watched_plugins.remove(&plugin_name);
+                            warning!(
+                                private.logger,
+                                "Stopped watching plugin \"{}\"",
+                                plugin_name
+                            );
+                        }
                     }
                     PluginEngineCommand::UserCommand {
                         plugin_name,
@@ -217,8 +316,219 @@ impl PluginEngine {
                             true,
                         );
                     }
+                    PluginEngineCommand::StopPlugin {
+                        plugin_name,
+                        command,
+                    } => {
+                        if let Some(plugin_name) = &plugin_name
+                            && !plugin_list.contains_key(plugin_name)
+                        {
+                            error!(private.logger, "Plugin \"{}\" not loaded", plugin_name);
+                            continue 'plugin_engine_loop;
+                        }
+
+                        let mut stopped = 0;
+
+                        for (name, plugin) in plugin_list.iter_mut().filter(|(name, _)| {
+                            plugin_name
+                                .as_ref()
+                                .is_none_or(|plugin_name| plugin_name == name.as_str())
+                        }) {
+                            let cancelled = plugin.cancel_method_calls(command.as_deref());
+                            let is_cancelled =
+                                |req: &PluginMethodMessage<PluginExternalRequest>| {
+                                    req.plugin_name == *name
+                                        && cancelled.iter().any(|(id, _)| *id == req.method_id)
+                                };
+
+                            interface_recv_reqs.retain(|req| !is_cancelled(req));
+                            rtt_read_reqs.retain(|req| !is_cancelled(req));
+                            permission_reqs.retain(|req| !is_cancelled(req));
+
+                            for (_, fn_name) in &cancelled {
+                                plugin.spawn_method_call(
+                                    engine_gate.new_method_call_gate(),
+                                    "on_cancel",
+                                    fn_name.to_string(),
+                                    false,
+                                );
+                            }
+
+                            stopped += cancelled.len();
+                        }
+
+                        if stopped == 0 {
+                            info!(private.logger, "No running plugin commands to stop");
+                        } else {
+                            warning!(
+                                private.logger,
+                                "Stopped {} running plugin command(s)",
+                                stopped
+                            );
+                        }
+                    }
+                    PluginEngineCommand::SetPermission {
+                        plugin_name,
+                        capability,
+                        allow,
+                    } => {
+                        let Some((name, plugin)) = plugin_list.get_key_value(&plugin_name) else {
+                            error!(private.logger, "Plugin \"{}\" not loaded", plugin_name);
+                            continue 'plugin_engine_loop;
+                        };
+
+                        let Some(capability) = Capability::from_name(&capability) else {
+                            error!(
+                                private.logger,
+                                "Invalid capability. Please, choose one of these options: {}",
+                                Capability::ALL
+                                    .map(|capability| capability.name())
+                                    .join(", ")
+                            );
+                            continue 'plugin_engine_loop;
+                        };
+
+                        plugin
+                            .permissions()
+                            .lock()
+                            .expect("Cannot get plugin permissions lock")
+                            .decide(capability, allow);
+
+                        for PluginMethodMessage { method_id, .. } in permission_reqs.extract_if(
+                            ..,
+                            |req| {
+                                *req.plugin_name == plugin_name
+                                    && matches!(
+                                        req.data,
+                                        PluginExternalRequest::Permission { capability: c, .. } if c == capability
+                                    )
+                            },
+                        ) {
+                            let _ = engine_gate.sender.send(PluginMethodMessage {
+                                plugin_name: name.clone(),
+                                method_id,
+                                data: PluginResponse::Permission { granted: allow },
+                            });
+                        }
+
+                        if allow {
+                            success!(
+                                private.logger,
+                                "Plugin \"{}\" allowed to use \"{}\" until it's reloaded",
+                                plugin_name,
+                                capability.name()
+                            );
+                        } else {
+                            warning!(
+                                private.logger,
+                                "Plugin \"{}\" denied to use \"{}\" until it's reloaded",
+                                plugin_name,
+                                capability.name()
+                            );
+                        }
+                    }
+                    PluginEngineCommand::ListPlugins => {
+                        if plugin_list.is_empty() {
+                            info!(private.logger, "No plugins loaded");
+                            continue 'plugin_engine_loop;
+                        }
+
+                        let mut plugins = plugin_list.iter().collect::<Vec<_>>();
+                        plugins.sort_by_key(|(plugin_name, _)| *plugin_name);
+
+                        let list = plugins
+                            .into_iter()
+                            .map(|(plugin_name, plugin)| {
+                                format!(
+                                    "{} [log: {:?}] [{}]{}",
+                                    plugin_name,
+                                    plugin.log_level(),
+                                    match plugin.unload_mode() {
+                                        PluginUnloadMode::None => "loaded",
+                                        PluginUnloadMode::Unload => "unloading",
+                                        PluginUnloadMode::Reload => "reloading",
+                                    },
+                                    if watched_plugins.contains(plugin_name.as_str()) {
+                                        " [watching]"
+                                    } else {
+                                        ""
+                                    }
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n");
+
+                        info!(private.logger, "Loaded plugins:\n{}", list);
+                    }
+                    PluginEngineCommand::PluginStats { plugin_name } => {
+                        if let Some(plugin_name) = &plugin_name
+                            && !plugin_list.contains_key(plugin_name)
+                        {
+                            error!(private.logger, "Plugin \"{}\" not loaded", plugin_name);
+                            continue 'plugin_engine_loop;
+                        }
+
+                        let mut plugins = plugin_list
+                            .iter()
+                            .filter(|(name, _)| {
+                                plugin_name
+                                    .as_ref()
+                                    .is_none_or(|plugin_name| plugin_name == name.as_str())
+                            })
+                            .collect::<Vec<_>>();
+                        plugins.sort_by_key(|(name, _)| *name);
+
+                        if plugins.is_empty() {
+                            info!(private.logger, "No plugins loaded");
+                            continue 'plugin_engine_loop;
+                        }
+
+                        let stats = plugins
+                            .into_iter()
+                            .map(|(name, plugin)| Self::format_stats(name, plugin))
+                            .collect::<Vec<_>>()
+                            .join("\n");
+
+                        info!(private.logger, "Plugin stats:\n{}", stats);
+                    }
+                    PluginEngineCommand::PluginHelp { plugin_name } => {
+                        let Some(plugin) = plugin_list.get(&plugin_name) else {
+                            error!(private.logger, "Plugin \"{}\" not loaded", plugin_name);
+                            continue 'plugin_engine_loop;
+                        };
+
+                        if plugin.commands().is_empty() {
+                            info!(
+                                private.logger,
+                                "Plugin \"{}\" doesn't have commands", plugin_name
+                            );
+                            continue 'plugin_engine_loop;
+                        }
+
+                        let help = plugin
+                            .commands()
+                            .iter()
+                            .map(|command| {
+                                let mut lines = vec![format!(
+                                    "!{} {}({})",
+                                    plugin_name, command.name, command.args
+                                )];
+                                lines.extend(command.doc.iter().map(|doc| format!("    {}", doc)));
+                                lines.join("\n")
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n");
+
+                        info!(
+                            private.logger,
+                            "Plugin \"{}\" commands:\n{}", plugin_name, help
+                        );
+                    }
                     PluginEngineCommand::SerialConnected { port, baudrate } => {
-                        for plugin in plugin_list.values_mut() {
+                        for plugin in plugin_list
+                            .values_mut()
+                            .filter(|plugin| plugin.has_hook("on_serial_connect"))
+                        {
                             plugin.spawn_method_call(
                                 engine_gate.new_method_call_gate(),
                                 "on_serial_connect",
@@ -228,7 +538,10 @@ impl PluginEngine {
                         }
                     }
                     PluginEngineCommand::SerialDisconnected { port, baudrate } => {
-                        for plugin in plugin_list.values_mut() {
+                        for plugin in plugin_list
+                            .values_mut()
+                            .filter(|plugin| plugin.has_hook("on_serial_disconnect"))
+                        {
                             plugin.spawn_method_call(
                                 engine_gate.new_method_call_gate(),
                                 "on_serial_disconnect",
@@ -238,7 +551,10 @@ impl PluginEngine {
                         }
                     }
                     PluginEngineCommand::RttConnected { target, channel } => {
-                        for plugin in plugin_list.values_mut() {
+                        for plugin in plugin_list
+                            .values_mut()
+                            .filter(|plugin| plugin.has_hook("on_rtt_connect"))
+                        {
                             plugin.spawn_method_call(
                                 engine_gate.new_method_call_gate(),
                                 "on_rtt_connect",
@@ -248,7 +564,10 @@ impl PluginEngine {
                         }
                     }
                     PluginEngineCommand::RttDisconnected { target, channel } => {
-                        for plugin in plugin_list.values_mut() {
+                        for plugin in plugin_list
+                            .values_mut()
+                            .filter(|plugin| plugin.has_hook("on_rtt_disconnect"))
+                        {
                             plugin.spawn_method_call(
                                 engine_gate.new_method_call_gate(),
                                 "on_rtt_disconnect",
@@ -294,6 +613,20 @@ impl PluginEngine {
                             });
                         }
                     }
+                    PluginEngineCommand::SendWithTags {
+                        command_line,
+                        end_bytes,
+                    } => match Self::replace_plugin_tags(command_line, &plugin_list) {
+                        Ok(mut message) => {
+                            message.extend(end_bytes);
+
+                            private.tx_producer.produce(Arc::new(TimedBytes {
+                                timestamp: Local::now(),
+                                message,
+                            }));
+                        }
+                        Err(err) => error!(private.logger, "{}", err),
+                    },
                 }
             }
 
@@ -303,7 +636,7 @@ impl PluginEngine {
                 data,
             }) = engine_gate.receiver.try_recv()
             {
-                let Some(plugin) = plugin_list.remove(&plugin_name) else {
+                let Some(mut plugin) = plugin_list.remove(&plugin_name) else {
                     continue;
                 };
 
@@ -540,8 +873,148 @@ impl PluginEngine {
 
                         Some(PluginResponse::Log)
                     }
-                    messages::PluginExternalRequest::Finish { fn_name } => {
+                    PluginExternalRequest::Permission { capability, detail } => {
+                        let state = plugin
+                            .permissions()
+                            .lock()
+                            .expect("Cannot get plugin permissions lock")
+                            .check(capability, &detail);
+
+                        match state {
+                            PermissionState::Allowed => {
+                                Some(PluginResponse::Permission { granted: true })
+                            }
+                            PermissionState::Denied => {
+                                Some(PluginResponse::Permission { granted: false })
+                            }
+                            PermissionState::Undeclared => {
+                                let is_asking = permission_reqs.iter().any(|req| {
+                                    req.plugin_name == plugin_name
+                                        && matches!(
+                                            req.data,
+                                            PluginExternalRequest::Permission { capability: c, .. } if c == capability
+                                        )
+                                });
+
+                                if !is_asking {
+                                    warning!(
+                                        private.logger,
+                                        "Plugin \"{}\" wants to use \"{}\" ({}), which isn't declared on its header. Type \"!plugin allow {} {}\" or \"!plugin deny {} {}\"",
+                                        plugin_name,
+                                        capability.name(),
+                                        detail,
+                                        plugin_name,
+                                        capability.name(),
+                                        plugin_name,
+                                        capability.name()
+                                    );
+                                }
+
+                                permission_reqs.push(PluginMethodMessage {
+                                    plugin_name: plugin_name.clone(),
+                                    method_id,
+                                    data: PluginExternalRequest::Permission { capability, detail },
+                                });
+
+                                None
+                            }
+                        }
+                    }
+                    PluginExternalRequest::StoreGet { shared, key } => {
+                        let (err, value) = match store
+                            .lock()
+                            .expect("Cannot get plugin store lock")
+                            .get(&plugin_name, shared, &key)
+                        {
+                            Ok(value) => (String::new(), value),
+                            Err(err) => (err, Default::default()),
+                        };
+
+                        Some(PluginResponse::StoreGet { err, value })
+                    }
+                    PluginExternalRequest::StoreSet { shared, key, value } => {
+                        let err = store
+                            .lock()
+                            .expect("Cannot get plugin store lock")
+                            .set(&plugin_name, shared, key, value)
+                            .err()
+                            .unwrap_or_default();
+
+                        Some(PluginResponse::StoreSet { err })
+                    }
+                    PluginExternalRequest::StoreKeys { shared } => {
+                        let (err, keys) = match store
+                            .lock()
+                            .expect("Cannot get plugin store lock")
+                            .keys(&plugin_name, shared)
+                        {
+                            Ok(keys) => (String::new(), keys),
+                            Err(err) => (err, vec![]),
+                        };
+
+                        Some(PluginResponse::StoreKeys { err, keys })
+                    }
+                    PluginExternalRequest::BusPublish { topic, value } => {
+                        /* The publisher isn't on plugin_list here, so it never gets its own events. */
+                        for subscriber in plugin_list
+                            .values_mut()
+                            .filter(|subscriber| subscriber.is_subscribed(&topic))
+                        {
+                            subscriber.spawn_method_call(
+                                engine_gate.new_method_call_gate(),
+                                "on_event",
+                                vec![LuaData(topic.clone().into()), LuaData(value.clone())],
+                                true,
+                            );
+                        }
+
+                        Some(PluginResponse::BusPublish)
+                    }
+                    PluginExternalRequest::BusSubscribe { topic, enable } => {
+                        plugin.set_subscription(topic, enable);
+
+                        Some(PluginResponse::BusSubscribe)
+                    }
+                    PluginExternalRequest::BufferLen => {
+                        let buffer = private
+                            .buffer
+                            .read()
+                            .expect("Cannot get buffer lock for read");
+
+                        Some(PluginResponse::BufferLen { len: buffer.len() })
+                    }
+                    PluginExternalRequest::BufferLines { from, to } => {
+                        let buffer = private
+                            .buffer
+                            .read()
+                            .expect("Cannot get buffer lock for read");
+
+                        Some(PluginResponse::BufferLines {
+                            lines: buffer::lines(&buffer, from, to),
+                        })
+                    }
+                    PluginExternalRequest::BufferSearch {
+                        pattern,
+                        from,
+                        to,
+                        max,
+                    } => {
+                        let (err, lines) =
+                            match buffer::search(&private.buffer, &pattern, from, to, max) {
+                                Ok(lines) => (String::new(), lines),
+                                Err(err) => (err, vec![]),
+                            };
+
+                        Some(PluginResponse::BufferSearch { err, lines })
+                    }
+                    messages::PluginExternalRequest::Finish {
+                        fn_name,
+                        lua_time,
+                        failed,
+                    } => {
                         if fn_name.as_str() == "on_unload" {
+                            is_shared_outdated = true;
+
                             if let PluginUnloadMode::Reload = plugin.unload_mode() {
                                 match Self::load_plugin(
                                     engine_gate.new_method_call_gate(),
@@ -558,13 +1031,31 @@ impl PluginEngine {
                                         plugin_name
                                     ),
                                     Err(err) => {
-                                        error!(private.logger, "{}", err_regex.replace(&err, ""));
+                                        Self::report_load_error(
+                                            &private.logger,
+                                            &plugin_name,
+                                            &err,
+                                        );
                                     }
                                 }
                             } else {
                                 warning!(private.logger, "Plugin \"{}\" unloaded", plugin_name);
                             }
                         } else {
+                            if let Some(hook) =
+                                plugin.method_call_finished(method_id, lua_time, failed)
+                            {
+                                warning!(
+                                    private.logger,
+                                    "\"{}\" of plugin \"{}\" disabled after {} consecutive errors. Reload the plugin to enable it again",
+                                    hook,
+                                    plugin_name,
+                                    errors::MAX_CONSECUTIVE_ERRORS
+                                );
+                            }
+                            if plugin.update_tags() {
+                                is_shared_outdated = true;
+                            }
                             plugin_list.insert(plugin_name.clone(), plugin);
                         }
 
@@ -587,21 +1078,47 @@ impl PluginEngine {
                 });
             }
 
-            if let Ok(tx_msg) = private.tx_consumer.try_recv() {
-                for plugin in plugin_list.values_mut() {
-                    let fn_name = match private.interface_type {
-                        InterfaceType::Rtt => "on_rtt_send",
-                        InterfaceType::Serial => "on_serial_send",
-                    };
+            if last_watch_check.elapsed() >= WATCH_INTERVAL {
+                last_watch_check = Instant::now();
+
+                for (plugin_name, plugin) in plugin_list.iter_mut() {
+                    if !watched_plugins.contains(plugin_name.as_str())
+                        || !matches!(plugin.unload_mode(), PluginUnloadMode::None)
+                        || !plugin.has_changed()
+                    {
+                        continue;
+                    }
+
+                    if let Err(err) = plugin.check_syntax() {
+                        error!(
+                            private.logger,
+                            "Plugin \"{}\" not reloaded: {}", plugin_name, err
+                        );
+                        continue;
+                    }
+
                     plugin.spawn_method_call(
                         engine_gate.new_method_call_gate(),
-                        fn_name,
-                        tx_msg.message.clone(),
+                        "on_unload",
+                        (),
                         false,
                     );
+                    plugin.set_unload_mode(PluginUnloadMode::Reload);
+                    is_shared_outdated = true;
                 }
             }
 
+            let tx_fn_name = match private.interface_type {
+                InterfaceType::Rtt => "on_rtt_send",
+                InterfaceType::Serial => "on_serial_send",
+            };
+
+            let tx_msgs = (0..MAX_HOOK_BATCH)
+                .map_while(|_| private.tx_consumer.try_recv().ok())
+                .map(|tx_msg| tx_msg.message.clone())
+                .collect::<Vec<_>>();
+            Self::dispatch_messages(&mut plugin_list, &engine_gate, tx_fn_name, &tx_msgs);
+
             interface_recv_reqs.retain(
                 |PluginMethodMessage {
                      data,
@@ -717,21 +1234,18 @@ impl PluginEngine {
                 },
             );
 
-            if let Ok(rx_msg) = private.rx.try_recv() {
-                let fn_name = match private.interface_type {
-                    InterfaceType::Serial => "on_serial_recv",
-                    InterfaceType::Rtt => "on_rtt_recv",
-                };
+            let rx_fn_name = match private.interface_type {
+                InterfaceType::Serial => "on_serial_recv",
+                InterfaceType::Rtt => "on_rtt_recv",
+            };
 
-                for plugin in plugin_list.values_mut() {
-                    plugin.spawn_method_call(
-                        engine_gate.new_method_call_gate(),
-                        fn_name,
-                        rx_msg.message.clone(),
-                        false,
-                    );
-                }
+            let rx_msgs = (0..MAX_HOOK_BATCH)
+                .map_while(|_| private.rx.try_recv().ok())
+                .map(|rx_msg| rx_msg.message.clone())
+                .collect::<Vec<_>>();
+            Self::dispatch_messages(&mut plugin_list, &engine_gate, rx_fn_name, &rx_msgs);
 
+            if let Some(rx_msg) = rx_msgs.first() {
                 for interface_recv_req in interface_recv_reqs.drain(..) {
                     let PluginMethodMessage {
                         plugin_name,
@@ -742,11 +1256,11 @@ impl PluginEngine {
                     let rsp = match private.interface_type {
                         InterfaceType::Serial => PluginResponse::SerialRecv {
                             err: "".to_string(),
-                            message: rx_msg.message.clone(),
+                            message: rx_msg.clone(),
                         },
                         InterfaceType::Rtt => PluginResponse::RttRecv {
                             err: "".to_string(),
-                            message: rx_msg.message.clone(),
+                            message: rx_msg.clone(),
                         },
                     };
 
@@ -766,6 +1280,84 @@ impl PluginEngine {
         }
     }
 
+    /// Calls the `<fn_name>_batch` hook of each plugin once with the list of
+    /// messages, or else its `fn_name` hook once per message.
+    fn dispatch_messages(
+        plugin_list: &mut HashMap<Arc<String>, Plugin>,
+        engine_gate: &PluginEngineGate,
+        fn_name: &str,
+        messages: &[Vec<u8>],
+    ) {
+        if messages.is_empty() {
+            return;
+        }
+
+        let batch_fn_name = format!("{}_batch", fn_name);
+        for plugin in plugin_list.values_mut() {
+            if plugin.has_hook(&batch_fn_name) {
+                plugin.spawn_method_call(
+                    engine_gate.new_method_call_gate(),
+                    &batch_fn_name,
+                    messages.to_vec(),
+                    false,
+                );
+            } else if plugin.has_hook(fn_name) {
+                for message in messages {
+                    plugin.spawn_method_call(
+                        engine_gate.new_method_call_gate(),
+                        fn_name,
+                        message.clone(),
+                        false,
+                    );
+                }
+            }
+        }
+    }
+
+    fn update_shared(
+        shared: &Arc<RwLock<PluginEngineShared>>,
+        plugin_list: &HashMap<Arc<String>, Plugin>,
+    ) {
+        let mut plugins = plugin_list
+            .iter()
+            .map(|(plugin_name, plugin)| PluginInfo {
+                name: plugin_name.clone(),
+                commands: plugin.commands().to_vec(),
+                tags: plugin.tags().to_vec(),
+            })
+            .collect::<Vec<_>>();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut sw = shared
+            .write()
+            .expect("Cannot get plugin engine lock for write");
+        sw.plugins = plugins;
+    }
+
+    fn format_stats(plugin_name: &str, plugin: &Plugin) -> String {
+        let uptime = plugin.uptime().as_secs_f64().max(1.0);
+        let mut metrics = plugin.metrics().iter().collect::<Vec<_>>();
+        metrics.sort_by_key(|(fn_name, _)| *fn_name);
+
+        let mut lines = vec![format!(
+            "{} [uptime: {:.0}s]",
+            plugin_name,
+            plugin.uptime().as_secs_f64()
+        )];
+        lines.extend(metrics.into_iter().map(|(fn_name, metrics)| {
+            format!(
+                "    {}: {} calls, {:.1} calls/s, avg {:?}, {} errors",
+                fn_name,
+                metrics.calls,
+                metrics.calls as f64 / uptime,
+                metrics.avg_lua_time(),
+                metrics.errors
+            )
+        }));
+
+        lines.join("\n")
+    }
+
     fn get_plugin_name(filepath: &str) -> Option<String> {
         Path::new(filepath)
             .with_extension("")
@@ -774,6 +1366,48 @@ impl PluginEngine {
             .map(|filename| filename.to_string())
     }
 
+    /// Replaces the plugin tags of `command_line` by the values returned by
+    /// their plugins.
+    /// Replaces the hex sequences of the text around the tags. The tag values
+    /// are kept as they are, since they may hold any byte.
+    fn replace_plugin_tags(
+        command_line: String,
+        plugin_list: &HashMap<Arc<String>, Plugin>,
+    ) -> Result<Vec<u8>, String> {
+        let owner = |tag_name: &str| {
+            plugin_list
+                .values()
+                .find(|plugin| plugin.tags().iter().any(|tag| tag == tag_name))
+        };
+        let mut res = Vec::with_capacity(command_line.len());
+
+        for item in command_line
+            .to_special_char(|string| tags::find_tag(string, |tag_name| owner(tag_name).is_some()))
+        {
+            match item {
+                SpecialCharItem::Plain(s) => res.extend(InputsTask::replace_hex_sequence(s)),
+                SpecialCharItem::Special(s, _column) => {
+                    let tag_name = s.strip_prefix('@').unwrap_or_default();
+                    let plugin =
+                        owner(tag_name).ok_or_else(|| format!("Tag \"{}\" not found", s))?;
+
+                    res.extend(plugin.tag_value(tag_name)?);
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Logs the error, with its traceback, and appends it to the plugin's
+    /// error log, like the errors of the plugin calls.
+    fn report_load_error(logger: &Logger, plugin_name: &str, err: &str) {
+        error!(logger, "Plugin \"{}\" failed to load: {}", plugin_name, err);
+        if let Err(log_err) = errors::write_log(plugin_name, "load", err) {
+            debug!(logger, "Cannot write the plugin error log: {}", log_err);
+        }
+    }
+
     async fn load_plugin(
         gate: PluginMethodCallGate,
         plugin_name: Arc<String>,
@@ -782,7 +1416,7 @@ impl PluginEngine {
         logger: Logger,
     ) -> Result<(), String> {
         let filepath = match filepath.extension() {
-            Some(extension) if extension.as_encoded_bytes() != b"lua" => {
+            Some(extension) if extension != "lua" && extension != wasm::EXTENSION => {
                 return Err(format!("Invalid plugin extension: {:?}", extension));
             }
             Some(_extension) => filepath,
@@ -813,6 +1447,7 @@ impl PluginEngineConnections {
         tx_consumer: Consumer<Arc<TimedBytes>>,
         rx: Consumer<Arc<TimedBytes>>,
         interface_shared: Shared<InterfaceShared>,
+        buffer: Shared<Buffer>,
         latency: u64,
         interface_type: InterfaceType,
         interface_cmd_sender: Sender<InterfaceCommand>,
@@ -823,9 +1458,88 @@ impl PluginEngineConnections {
             tx_consumer,
             rx,
             interface_shared,
+            buffer,
             latency,
             interface_type,
             interface_cmd_sender,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PluginEngine;
+    use crate::plugin::{
+        bridge::PluginEngineGate,
+        messages::PluginExternalRequest,
+        tests::{TempDir, load_test_plugin, new_test_plugin},
+    };
+    use std::{collections::HashMap, sync::Arc};
+
+    #[test]
+    fn test_replace_plugin_tags_keeps_bytes() {
+        let (plugin, _dir) = load_test_plugin(
+            "crc",
+            "require('tags').register('crc', function() return '\\xA5\\x01' end)\nreturn {}",
+        );
+        let plugin_list = HashMap::from([(Arc::new("crc".to_string()), plugin)]);
+
+        assert_eq!(
+            PluginEngine::replace_plugin_tags("$02 @crc $0D".to_string(), &plugin_list).unwrap(),
+            [0x02, 0xA5, 0x01, 0x20, 0x0D]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_messages_batch() {
+        let dir = TempDir::new();
+        let mut plugin_list = HashMap::new();
+        for (name, code) in [
+            (
+                "batch",
+                "local log = require('scope').log\n\
+                 local M = {}\n\
+                 function M.on_serial_recv() end\n\
+                 function M.on_serial_recv_batch(msgs) log.info(tostring(#msgs)) end\n\
+                 return M",
+            ),
+            ("single", "return { on_serial_recv = function() end }"),
+        ] {
+            let plugin = new_test_plugin(dir.write(&format!("{}.lua", name), code)).unwrap();
+            plugin_list.insert(Arc::new(name.to_string()), plugin);
+        }
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let mut engine_gate = PluginEngineGate::new(8);
+            let messages = vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()];
+            PluginEngine::dispatch_messages(
+                &mut plugin_list,
+                &engine_gate,
+                "on_serial_recv",
+                &messages,
+            );
+
+            /* The calls of the other plugin may finish first. */
+            loop {
+                let message = engine_gate.receiver.recv().await.unwrap();
+                if let PluginExternalRequest::Log { message, .. } = message.data {
+                    assert_eq!(message, "3");
+                    break;
+                }
+            }
+        });
+
+        let calls = |plugin: &str, fn_name: &str| {
+            plugin_list[&plugin.to_string()]
+                .metrics()
+                .get(&fn_name.to_string())
+                .map_or(0, |metrics| metrics.calls)
+        };
+        assert_eq!(calls("batch", "on_serial_recv_batch"), 1);
+        assert_eq!(calls("batch", "on_serial_recv"), 0);
+        assert_eq!(calls("single", "on_serial_recv"), 3);
+    }
+}
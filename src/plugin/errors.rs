@@ -0,0 +1,184 @@
+use chrono::Local;
+use mlua::{FromLua, Function, Lua, Table, Value};
+use std::{io::Write, path::PathBuf};
+
+const TRACEBACK_REGISTRY: &str = "scope.traceback";
+
+/// Name of the chunk that wraps each plugin call. Its frames are left out of
+/// the tracebacks.
+pub const CALL_CHUNK_NAME: &str = "=[call]";
+
+/// Consecutive errors after which a hook is disabled, until the plugin is
+/// reloaded.
+pub const MAX_CONSECUTIVE_ERRORS: u32 = 5;
+
+/// Registers the message handler used by the plugin calls to add a
+/// traceback to their errors. The `debug` library isn't available to the
+/// plugins, so the stack is walked from Rust.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    lua.set_named_registry_value(TRACEBACK_REGISTRY, lua.create_function(traceback)?)
+}
+
+pub fn traceback_fn(lua: &Lua) -> mlua::Result<Function<'_>> {
+    lua.named_registry_value(TRACEBACK_REGISTRY)
+}
+
+/// Evaluates the main chunk of a plugin, which returns the plugin table,
+/// adding a traceback to its errors like the plugin calls do.
+pub fn eval_chunk<'lua>(lua: &'lua Lua, chunk: Function<'lua>) -> Result<Table<'lua>, String> {
+    let (ok, res): (bool, Value) = lua
+        .load("local traceback, fn = ...\nreturn xpcall(fn, traceback)")
+        .set_name(CALL_CHUNK_NAME)
+        .call((traceback_fn(lua).map_err(load_error)?, chunk))
+        .map_err(load_error)?;
+
+    if !ok {
+        return Err(String::from_lua(res, lua).map_err(load_error)?);
+    }
+
+    Table::from_lua(res, lua).map_err(|err| err.to_string())
+}
+
+/// Error of a plugin that failed to load, without the kind `mlua` puts
+/// before the message.
+pub fn load_error(err: mlua::Error) -> String {
+    match err {
+        mlua::Error::SyntaxError { message, .. } | mlua::Error::RuntimeError(message) => message,
+        err => err.to_string(),
+    }
+}
+
+fn traceback(lua: &Lua, msg: Value) -> mlua::Result<String> {
+    let msg = match msg {
+        Value::String(s) => s.to_string_lossy().to_string(),
+        msg => lua
+            .globals()
+            .get::<_, Function>("tostring")?
+            .call::<_, String>(msg)?,
+    };
+    let mut lines = vec![msg];
+
+    for level in 1.. {
+        let Some(debug) = lua.inspect_stack(level) else {
+            break;
+        };
+
+        let source = debug.source();
+        if source.what == "C" || source.source.as_deref() == Some(CALL_CHUNK_NAME) {
+            continue;
+        }
+
+        let short_src = source.short_src.as_deref().unwrap_or("?");
+        let location = match debug.names().name {
+            Some(name) => format!("in function '{}'", name),
+            None if source.what == "main" => "in main chunk".to_string(),
+            None => format!(
+                "in function <{}:{}>",
+                short_src,
+                source.line_defined.unwrap_or_default()
+            ),
+        };
+
+        if lines.len() == 1 {
+            lines.push("stack traceback:".to_string());
+        }
+        lines.push(format!("{}:{}: {}", short_src, debug.curr_line(), location));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+pub fn default_log_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("scope").join("logs"))
+}
+
+/// Appends the error of a plugin call to `<plugin>.errors.log`, so errors
+/// aren't lost when the screen buffer is cleared.
+pub fn write_log(plugin_name: &str, fn_name: &str, err: &str) -> Result<PathBuf, String> {
+    let dir = default_log_dir().ok_or_else(|| "cannot determine data directory".to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    let file_path = dir.join(format!("{}.errors.log", plugin_name));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file_path)
+        .map_err(|err| format!("cannot open {}: {}", file_path.display(), err))?;
+
+    writeln!(
+        file,
+        "[{}][{}] {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+        fn_name,
+        err
+    )
+    .map_err(|err| format!("cannot write {}: {}", file_path.display(), err))?;
+
+    Ok(file_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CALL_CHUNK_NAME, eval_chunk, load_error, register, traceback_fn};
+    use mlua::{Function, Lua};
+
+    #[test]
+    fn test_traceback() {
+        let lua = Lua::new();
+        register(&lua).unwrap();
+
+        let plugin: Function = lua
+            .load(
+                "local function decode(msg)\n  error('bad frame ' .. msg)\nend\n\nreturn function(msg)\n  decode(msg)\nend",
+            )
+            .set_name("@plugins/decoder.lua")
+            .eval()
+            .unwrap();
+        let call: Function = lua
+            .load("local traceback, fn = ...\nreturn xpcall(fn, traceback, 'x')")
+            .set_name(CALL_CHUNK_NAME)
+            .into_function()
+            .unwrap();
+
+        let (ok, err): (bool, String) = call.call((traceback_fn(&lua).unwrap(), plugin)).unwrap();
+        let lines = err.lines().collect::<Vec<_>>();
+
+        assert!(!ok);
+        assert_eq!(lines[0], "plugins/decoder.lua:2: bad frame x");
+        assert_eq!(lines[1], "stack traceback:");
+        assert_eq!(lines[2], "plugins/decoder.lua:2: in function 'decode'");
+        assert!(lines[3].starts_with("plugins/decoder.lua:6: "));
+        assert!(!err.contains("[call]"));
+    }
+
+    #[test]
+    fn test_load_error() {
+        let lua = Lua::new();
+        register(&lua).unwrap();
+
+        let err = lua
+            .load("local plugin = {}\nplugin.on_load(\nreturn plugin")
+            .set_name("@plugins/broken.lua")
+            .into_function()
+            .map(|_| ())
+            .map_err(load_error)
+            .unwrap_err();
+        assert!(err.starts_with("plugins/broken.lua:3: "));
+
+        let chunk = lua
+            .load("local plugin = {}\nplugin.setup()\nreturn plugin")
+            .set_name("@plugins/failing.lua")
+            .into_function()
+            .unwrap();
+        let err = eval_chunk(&lua, chunk).unwrap_err();
+        let lines = err.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("plugins/failing.lua:2: "));
+        assert_eq!(lines[1], "stack traceback:");
+        assert_eq!(lines[2], "plugins/failing.lua:2: in main chunk");
+        assert!(!err.contains("[call]"));
+
+        let chunk = lua.load("return { name = 'ok' }").into_function().unwrap();
+        let plugin = eval_chunk(&lua, chunk).unwrap();
+        assert_eq!(plugin.get::<_, String>("name").unwrap(), "ok");
+    }
+}
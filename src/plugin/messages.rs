@@ -3,7 +3,9 @@ use std::{sync::Arc, time::Instant};
 use mlua::Table;
 use std::time::Duration;
 
+use super::{api, buffer::PluginBufferLine, codec, permissions::Capability, store};
 use crate::infra::LogLevel;
+use serde_yaml::Value as YamlValue;
 
 #[derive(Clone)]
 pub struct PluginMethodMessage<T: Clone> {
@@ -22,6 +24,8 @@ pub enum PluginRequest {
 pub enum PluginExternalRequest {
     Finish {
         fn_name: Arc<String>,
+        lua_time: Duration,
+        failed: bool,
     },
     SerialInfo,
     SerialSend {
@@ -50,6 +54,41 @@ pub enum PluginExternalRequest {
         plugin_name: String,
         id: String,
     },
+    Permission {
+        capability: Capability,
+        detail: String,
+    },
+    StoreGet {
+        shared: bool,
+        key: String,
+    },
+    StoreSet {
+        shared: bool,
+        key: String,
+        value: YamlValue,
+    },
+    StoreKeys {
+        shared: bool,
+    },
+    BusPublish {
+        topic: String,
+        value: YamlValue,
+    },
+    BusSubscribe {
+        topic: String,
+        enable: bool,
+    },
+    BufferLen,
+    BufferLines {
+        from: i64,
+        to: i64,
+    },
+    BufferSearch {
+        pattern: String,
+        from: i64,
+        to: i64,
+        max: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -74,24 +113,173 @@ pub enum PluginInternalRequest {
     ShellExist {
         program: String,
     },
+    ShellSpawn {
+        cmd: String,
+        log: bool,
+    },
+    ShellReadLine {
+        id: u64,
+        timeout: Option<Duration>,
+    },
+    ShellWrite {
+        id: u64,
+        data: Vec<u8>,
+    },
+    ShellCloseStdin {
+        id: u64,
+    },
+    ShellKill {
+        id: u64,
+    },
+    ShellWait {
+        id: u64,
+    },
+    PermissionCheck {
+        capability: Capability,
+        detail: String,
+    },
+    NetTcpConnect {
+        host: String,
+        port: u16,
+        timeout: Option<Duration>,
+    },
+    NetTcpSend {
+        id: u64,
+        data: Vec<u8>,
+    },
+    NetTcpRecv {
+        id: u64,
+        size: Option<usize>,
+        timeout: Option<Duration>,
+    },
+    NetTcpClose {
+        id: u64,
+    },
+    NetUdpSend {
+        host: String,
+        port: u16,
+        data: Vec<u8>,
+    },
+    NetHttpPost {
+        url: String,
+        body: Vec<u8>,
+        headers: Vec<(String, String)>,
+        timeout: Option<Duration>,
+    },
 }
 
 #[derive(Clone, Debug)]
 pub enum PluginResponse {
     Log,
-    SerialInfo { port: String, baudrate: u32 },
+    SerialInfo {
+        port: String,
+        baudrate: u32,
+    },
     SerialSend,
-    SerialRecv { err: String, message: Vec<u8> },
-    RttInfo { target: String, channel: usize },
+    SerialRecv {
+        err: String,
+        message: Vec<u8>,
+    },
+    RttInfo {
+        target: String,
+        channel: usize,
+    },
     RttSend,
-    RttRecv { err: String, message: Vec<u8> },
-    RttRead { err: String, data: Vec<u8> },
+    RttRecv {
+        err: String,
+        message: Vec<u8>,
+    },
+    RttRead {
+        err: String,
+        data: Vec<u8>,
+    },
     SysSleep,
-    ReLiteral { literal: String },
-    ReMatches { pattern: Option<String> },
-    ReMatch { is_match: bool },
-    ShellRun { stdout: String, stderr: String },
-    ShellExist { exist: bool },
+    ReLiteral {
+        literal: String,
+    },
+    ReMatches {
+        pattern: Option<String>,
+    },
+    ReMatch {
+        is_match: bool,
+    },
+    ShellRun {
+        stdout: String,
+        stderr: String,
+    },
+    ShellExist {
+        exist: bool,
+    },
+    ShellSpawn {
+        err: String,
+        id: u64,
+    },
+    ShellReadLine {
+        err: String,
+        line: String,
+    },
+    ShellWrite {
+        err: String,
+    },
+    ShellCloseStdin {
+        err: String,
+    },
+    ShellKill {
+        err: String,
+    },
+    ShellWait {
+        err: String,
+        status: Option<i32>,
+    },
+    PermissionCheck,
+    Permission {
+        granted: bool,
+    },
+    StoreGet {
+        err: String,
+        value: YamlValue,
+    },
+    StoreSet {
+        err: String,
+    },
+    StoreKeys {
+        err: String,
+        keys: Vec<String>,
+    },
+    BusPublish,
+    BusSubscribe,
+    BufferLen {
+        len: usize,
+    },
+    BufferLines {
+        lines: Vec<PluginBufferLine>,
+    },
+    BufferSearch {
+        err: String,
+        lines: Vec<PluginBufferLine>,
+    },
+    NetTcpConnect {
+        err: String,
+        id: u64,
+    },
+    NetTcpSend {
+        err: String,
+    },
+    NetTcpRecv {
+        err: String,
+        data: Vec<u8>,
+    },
+    NetTcpClose {
+        err: String,
+    },
+    NetUdpSend {
+        err: String,
+    },
+    NetHttpPost {
+        err: String,
+        status: Option<u16>,
+        body: Vec<u8>,
+    },
 }
 
 impl PluginRequest {
@@ -107,6 +295,42 @@ impl PluginRequest {
         Instant::now().checked_add(Duration::from_millis(timeout_ms))
     }
 
+    fn timeout_from_opts(opts: Option<Table>) -> Option<Duration> {
+        opts.and_then(|opts| opts.get::<_, Option<u64>>("timeout_ms").ok().flatten())
+            .map(Duration::from_millis)
+    }
+
+    /// The capability the request needs, with the detail shown to the user
+    /// when it isn't declared by the plugin.
+    pub fn capability(&self) -> Option<(Capability, String)> {
+        match self {
+            PluginRequest::External(PluginExternalRequest::SerialSend { .. }) => {
+                Some((Capability::Send, "serial.send".to_string()))
+            }
+            PluginRequest::External(PluginExternalRequest::RttSend { .. }) => {
+                Some((Capability::Send, "rtt.send".to_string()))
+            }
+            PluginRequest::Internal(PluginInternalRequest::ShellRun { cmd })
+            | PluginRequest::Internal(PluginInternalRequest::ShellSpawn { cmd, .. }) => {
+                Some((Capability::Shell, cmd.clone()))
+            }
+            PluginRequest::Internal(PluginInternalRequest::PermissionCheck {
+                capability,
+                detail,
+            }) => Some((*capability, detail.clone())),
+            PluginRequest::Internal(PluginInternalRequest::NetTcpConnect {
+                host, port, ..
+            })
+            | PluginRequest::Internal(PluginInternalRequest::NetUdpSend { host, port, .. }) => {
+                Some((Capability::Network, format!("{}:{}", host, port)))
+            }
+            PluginRequest::Internal(PluginInternalRequest::NetHttpPost { url, .. }) => {
+                Some((Capability::Network, url.clone()))
+            }
+            _ => None,
+        }
+    }
+
     pub fn from_table<'lua>(
         value: Table<'lua>,
         plugin_name: String,
@@ -266,6 +490,272 @@ impl PluginRequest {
 
                 PluginRequest::Internal(PluginInternalRequest::ShellExist { program })
             }
+            ":store.get" | ":store.shared.get" => {
+                let key: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+
+                PluginRequest::External(PluginExternalRequest::StoreGet {
+                    shared: req_id == ":store.shared.get",
+                    key,
+                })
+            }
+            ":store.set" | ":store.shared.set" => {
+                let key: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let stored = store::lua_to_yaml(
+                    value
+                        .get(3)
+                        .map_err(|_| "Cannot get third table entry".to_string())?,
+                )?;
+
+                PluginRequest::External(PluginExternalRequest::StoreSet {
+                    shared: req_id == ":store.shared.set",
+                    key,
+                    value: stored,
+                })
+            }
+            ":store.keys" | ":store.shared.keys" => {
+                PluginRequest::External(PluginExternalRequest::StoreKeys {
+                    shared: req_id == ":store.shared.keys",
+                })
+            }
+            ":bus.publish" => {
+                let topic: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let published = store::lua_to_yaml(
+                    value
+                        .get(3)
+                        .map_err(|_| "Cannot get third table entry".to_string())?,
+                )?;
+
+                PluginRequest::External(PluginExternalRequest::BusPublish {
+                    topic,
+                    value: published,
+                })
+            }
+            ":bus.subscribe" | ":bus.unsubscribe" => {
+                let topic: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+
+                PluginRequest::External(PluginExternalRequest::BusSubscribe {
+                    topic,
+                    enable: req_id == ":bus.subscribe",
+                })
+            }
+            ":buffer.len" => PluginRequest::External(PluginExternalRequest::BufferLen),
+            ":buffer.lines" => {
+                let from: Option<i64> = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Integer".to_string())?;
+                let to: Option<i64> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as Integer".to_string())?;
+
+                PluginRequest::External(PluginExternalRequest::BufferLines {
+                    from: from.unwrap_or(1),
+                    to: to.unwrap_or(-1),
+                })
+            }
+            ":buffer.search" => {
+                let pattern: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let from: Option<i64> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as Integer".to_string())?;
+                let to: Option<i64> = value
+                    .get(4)
+                    .map_err(|_| "Cannot get fourth table entry as Integer".to_string())?;
+                let max: Option<i64> = value
+                    .get(5)
+                    .map_err(|_| "Cannot get fifth table entry as Integer".to_string())?;
+
+                PluginRequest::External(PluginExternalRequest::BufferSearch {
+                    pattern,
+                    from: from.unwrap_or(1),
+                    to: to.unwrap_or(-1),
+                    max: max.map_or(usize::MAX, |max| max.max(0) as usize),
+                })
+            }
+            ":permission.check" => {
+                let name: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let detail: String = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as String".to_string())?;
+                let Some(capability) = Capability::from_name(&name) else {
+                    return Err(format!("Unknown capability \"{}\"", name));
+                };
+
+                PluginRequest::Internal(PluginInternalRequest::PermissionCheck {
+                    capability,
+                    detail,
+                })
+            }
+            ":shell.spawn" => {
+                let cmd: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let opts: Option<Table> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as Table".to_string())?;
+                let log = opts
+                    .and_then(|opts| opts.get::<_, Option<bool>>("log").ok().flatten())
+                    .unwrap_or(true);
+
+                PluginRequest::Internal(PluginInternalRequest::ShellSpawn { cmd, log })
+            }
+            ":shell.read_line" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+                let opts: Option<Table> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as Table".to_string())?;
+                let timeout = opts
+                    .and_then(|opts| opts.get::<_, Option<u64>>("timeout_ms").ok().flatten())
+                    .map(Duration::from_millis);
+
+                PluginRequest::Internal(PluginInternalRequest::ShellReadLine { id, timeout })
+            }
+            ":shell.write" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+                let data: Vec<u8> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as bytes".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::ShellWrite { id, data })
+            }
+            ":shell.close_stdin" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::ShellCloseStdin { id })
+            }
+            ":shell.kill" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::ShellKill { id })
+            }
+            ":shell.wait" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::ShellWait { id })
+            }
+            ":net.tcp_connect" => {
+                let host: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let port: u16 = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as port Number".to_string())?;
+                let opts: Option<Table> = value
+                    .get(4)
+                    .map_err(|_| "Cannot get fourth table entry as Table".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::NetTcpConnect {
+                    host,
+                    port,
+                    timeout: Self::timeout_from_opts(opts),
+                })
+            }
+            ":net.tcp_send" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+                let data = codec::bytes(
+                    value
+                        .get(3)
+                        .map_err(|_| "Cannot get third table entry".to_string())?,
+                )
+                .map_err(|err| err.to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::NetTcpSend { id, data })
+            }
+            ":net.tcp_recv" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+                let opts: Option<Table> = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as Table".to_string())?;
+                let size = opts
+                    .as_ref()
+                    .and_then(|opts| opts.get::<_, Option<usize>>("size").ok().flatten());
+
+                PluginRequest::Internal(PluginInternalRequest::NetTcpRecv {
+                    id,
+                    size,
+                    timeout: Self::timeout_from_opts(opts),
+                })
+            }
+            ":net.tcp_close" => {
+                let id: u64 = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as Number".to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::NetTcpClose { id })
+            }
+            ":net.udp_send" => {
+                let host: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let port: u16 = value
+                    .get(3)
+                    .map_err(|_| "Cannot get third table entry as port Number".to_string())?;
+                let data = codec::bytes(
+                    value
+                        .get(4)
+                        .map_err(|_| "Cannot get fourth table entry".to_string())?,
+                )
+                .map_err(|err| err.to_string())?;
+
+                PluginRequest::Internal(PluginInternalRequest::NetUdpSend { host, port, data })
+            }
+            ":net.http_post" => {
+                let url: String = value
+                    .get(2)
+                    .map_err(|_| "Cannot get second table entry as String".to_string())?;
+                let body = codec::bytes(
+                    value
+                        .get(3)
+                        .map_err(|_| "Cannot get third table entry".to_string())?,
+                )
+                .map_err(|err| err.to_string())?;
+                let opts: Option<Table> = value
+                    .get(4)
+                    .map_err(|_| "Cannot get fourth table entry as Table".to_string())?;
+                let headers = opts
+                    .as_ref()
+                    .and_then(|opts| opts.get::<_, Option<Table>>("headers").ok().flatten())
+                    .map(|headers| {
+                        headers
+                            .pairs::<String, String>()
+                            .collect::<mlua::Result<Vec<_>>>()
+                    })
+                    .transpose()
+                    .map_err(|_| "Cannot get 'headers' field as a Table of Strings".to_string())?
+                    .unwrap_or_default();
+
+                PluginRequest::Internal(PluginInternalRequest::NetHttpPost {
+                    url,
+                    body,
+                    headers,
+                    timeout: Self::timeout_from_opts(opts),
+                })
+            }
             ":re.literal" => {
                 let string: String = value
                     .get(2)
@@ -301,7 +791,7 @@ impl PluginRequest {
 
                 PluginRequest::Internal(PluginInternalRequest::ReMatch { string, pattern })
             }
-            _ => return Err("Invalid Plugin Request ID".to_string()),
+            _ => return Err(api::unknown_request(&req_id)),
         };
 
         Ok(req)
@@ -1,14 +1,19 @@
-#[allow(unused)]
-use crate::debug;
 use crate::{
-    error,
+    debug, error,
     infra::logger::{LogLevel, Logger},
 };
 
 use super::{
     bridge::PluginMethodCallGate,
-    messages::{PluginInternalRequest, PluginMethodMessage, PluginRequest, PluginResponse},
-    shell::Shell,
+    errors,
+    messages::{
+        PluginExternalRequest, PluginInternalRequest, PluginMethodMessage, PluginRequest,
+        PluginResponse,
+    },
+    net::{self, NetConnection, SharedNetConnections},
+    permissions::{Capability, PermissionState, PluginPermissions, SharedPermissions},
+    shell::{SharedShellProcesses, Shell, ShellProcess},
+    store,
 };
 use mlua::{Function, IntoLuaMulti, Lua, Table, Thread, Value};
 use regex::Regex;
@@ -16,7 +21,9 @@ use std::{
     hash::{DefaultHasher, Hash, Hasher},
     rc::Rc,
     sync::Arc,
+    time::{Duration, Instant},
 };
+use tokio::task::AbortHandle;
 
 pub struct PluginMethodCallArgs<T> {
     pub plugin_name: Arc<String>,
@@ -27,6 +34,11 @@ pub struct PluginMethodCallArgs<T> {
     pub gate: PluginMethodCallGate,
     pub logger: Logger,
     pub has_unpack: bool,
+    pub permissions: SharedPermissions,
+    pub shell_processes: SharedShellProcesses,
+    pub net_connections: SharedNetConnections,
+    /// Held during the whole call, so calls sharing it run one at a time.
+    pub call_lock: Option<Rc<tokio::sync::Mutex<()>>>,
 }
 
 pub struct PluginMethodCall {
@@ -34,12 +46,19 @@ pub struct PluginMethodCall {
     fn_name: Arc<String>,
     id: u64,
     gate: PluginMethodCallGate,
-    #[allow(unused)]
     logger: Logger,
+    lua_time: Duration,
+    permissions: SharedPermissions,
+    shell_processes: SharedShellProcesses,
+    net_connections: SharedNetConnections,
 }
 
 impl PluginMethodCall {
-    pub fn spawn<T: for<'a> IntoLuaMulti<'a> + 'static>(args: PluginMethodCallArgs<T>) {
+    /// Spawns the method call coroutine. Returns its id and a handle to
+    /// cancel it.
+    pub fn spawn<T: for<'a> IntoLuaMulti<'a> + 'static>(
+        args: PluginMethodCallArgs<T>,
+    ) -> (u64, AbortHandle) {
         let PluginMethodCallArgs {
             plugin_name,
             fn_name,
@@ -49,6 +68,10 @@ impl PluginMethodCall {
             gate,
             logger,
             has_unpack,
+            permissions,
+            shell_processes,
+            net_connections,
+            call_lock,
         } = args;
         let mut hasher = DefaultHasher::new();
         plugin_name.hash(&mut hasher);
@@ -60,31 +83,53 @@ impl PluginMethodCall {
         let fn_name = Arc::new(fn_name);
 
         let sender = gate.sender.clone();
-        let pmc = Self {
+        let mut pmc = Self {
             plugin_name: plugin_name.clone(),
             fn_name: fn_name.clone(),
             id,
             gate,
             logger: logger.clone(),
+            lua_time: Duration::ZERO,
+            permissions,
+            shell_processes,
+            net_connections,
         };
 
-        tokio::task::spawn_local(async move {
-            if let Err(err) = pmc.call_fn(&lua, initial_args, has_unpack).await {
-                error!(logger, "{}", err);
-            }
+        let handle = tokio::task::spawn_local(async move {
+            let _call_guard = match &call_lock {
+                Some(call_lock) => Some(call_lock.lock().await),
+                None => None,
+            };
+            let failed = match pmc.call_fn(&lua, initial_args, has_unpack).await {
+                Ok(()) => false,
+                Err(err) => {
+                    error!(logger, "\"{}\" failed: {}", fn_name, err);
+                    if let Err(log_err) = errors::write_log(&plugin_name, &fn_name, &err) {
+                        debug!(logger, "Cannot write the plugin error log: {}", log_err);
+                    }
+
+                    true
+                }
+            };
 
             let _ = sender
                 .send(PluginMethodMessage {
                     plugin_name,
                     method_id: id,
-                    data: super::messages::PluginExternalRequest::Finish { fn_name },
+                    data: super::messages::PluginExternalRequest::Finish {
+                        fn_name,
+                        lua_time: pmc.lua_time,
+                        failed,
+                    },
                 })
                 .await;
         });
+
+        (id, handle.abort_handle())
     }
 
     async fn call_fn<'a>(
-        mut self,
+        &mut self,
         lua: &'a Lua,
         initial_args: impl IntoLuaMulti<'a>,
         has_unpack: bool,
@@ -103,11 +148,16 @@ impl PluginMethodCall {
         } else {
             format!(include_str!("thread.lua"), self.fn_name)
         };
+        let traceback = errors::traceback_fn(lua).map_err(|err| err.to_string())?;
+        let start = Instant::now();
         let thread: Thread = lua
             .load(thread_code)
-            .eval_async()
+            .set_name(errors::CALL_CHUNK_NAME)
+            .call_async(traceback)
             .await
             .map_err(|err| err.to_string())?;
+        self.lua_time += start.elapsed();
+        PluginPermissions::mark_call_thread(lua, &thread).map_err(|err| err.to_string())?;
 
         let Some(mut table) = self.call_fn_inner(lua, &thread, initial_args).await? else {
             return Ok(());
@@ -128,10 +178,21 @@ impl PluginMethodCall {
         thread: &Thread<'a>,
         plugin_fn_args: impl IntoLuaMulti<'a>,
     ) -> Result<Option<Table<'a>>, String> {
-        let plugin_req: Table = match thread.resume(plugin_fn_args) {
+        let start = Instant::now();
+        let resume = thread.resume(plugin_fn_args);
+        self.lua_time += start.elapsed();
+
+        let plugin_req: Table = match resume {
             Ok(plugin_req) => plugin_req,
             Err(mlua::Error::CoroutineInactive) => return Ok(None),
             Err(mlua::Error::FromLuaConversionError { .. }) => return Ok(None),
+            Err(mlua::Error::RuntimeError(err)) => {
+                /* The error already has the plugin traceback. Drop the one of the wrapper coroutine. */
+                let err = err
+                    .split_once("\nstack traceback:\n\t")
+                    .map_or(err.as_str(), |(err, _)| err);
+                return Err(err.to_string());
+            }
             Err(err) => return Err(err.to_string()),
         };
 
@@ -143,45 +204,92 @@ impl PluginMethodCall {
         )
         .map_err(|err: String| err.to_string())?;
 
+        if let Some((capability, detail)) = plugin_req.capability() {
+            self.check_permission(capability, detail).await?;
+        }
+
         let rsp = match plugin_req {
             PluginRequest::Internal(internal_req) => {
                 self.handle_internal_plugin_request(internal_req).await
             }
-            PluginRequest::External(external_req) => {
-                self.gate
-                    .sender
-                    .send(PluginMethodMessage {
-                        plugin_name: self.plugin_name.clone(),
-                        method_id: self.id,
-                        data: external_req,
-                    })
-                    .await
-                    .map_err(|err| err.to_string())?;
+            PluginRequest::External(external_req) => self.external_request(external_req).await?,
+        };
 
-                'rsp_loop: loop {
-                    let PluginMethodMessage {
-                        plugin_name: _plugin_name,
-                        method_id,
-                        data,
-                    } = self
-                        .gate
-                        .receiver
-                        .recv()
-                        .await
-                        .map_err(|err| err.to_string())?;
+        let next_table = self.rsp_decode(lua, rsp)?;
 
-                    if method_id != self.id {
-                        continue 'rsp_loop;
-                    }
+        Ok(Some(next_table))
+    }
 
-                    break 'rsp_loop data;
-                }
+    async fn external_request(
+        &mut self,
+        req: PluginExternalRequest,
+    ) -> Result<PluginResponse, String> {
+        self.gate
+            .sender
+            .send(PluginMethodMessage {
+                plugin_name: self.plugin_name.clone(),
+                method_id: self.id,
+                data: req,
+            })
+            .await
+            .map_err(|err| err.to_string())?;
+
+        loop {
+            let PluginMethodMessage {
+                plugin_name: _plugin_name,
+                method_id,
+                data,
+            } = self
+                .gate
+                .receiver
+                .recv()
+                .await
+                .map_err(|err| err.to_string())?;
+
+            if method_id == self.id {
+                return Ok(data);
             }
-        };
+        }
+    }
 
-        let next_table = self.rsp_decode(lua, rsp)?;
+    /// Asks the engine, and so the user, when the capability isn't declared
+    /// on the plugin header. The call is aborted if it's denied.
+    async fn check_permission(
+        &mut self,
+        capability: Capability,
+        detail: String,
+    ) -> Result<(), String> {
+        let state = self
+            .permissions
+            .lock()
+            .expect("Cannot get plugin permissions lock")
+            .check(capability, &detail);
 
-        Ok(Some(next_table))
+        let granted = match state {
+            PermissionState::Allowed => true,
+            PermissionState::Denied => false,
+            PermissionState::Undeclared => {
+                let rsp = self
+                    .external_request(PluginExternalRequest::Permission {
+                        capability,
+                        detail: detail.clone(),
+                    })
+                    .await?;
+
+                matches!(rsp, PluginResponse::Permission { granted: true })
+            }
+        };
+
+        if granted {
+            Ok(())
+        } else {
+            Err(format!(
+                "Plugin \"{}\" isn't allowed to use \"{}\" ({})",
+                self.plugin_name,
+                capability.name(),
+                detail
+            ))
+        }
     }
 
     async fn handle_internal_plugin_request(&self, req: PluginInternalRequest) -> PluginResponse {
@@ -240,6 +348,165 @@ impl PluginMethodCall {
 
                 PluginResponse::ShellExist { exist }
             }
+            PluginInternalRequest::ShellSpawn { cmd, log } => {
+                match ShellProcess::spawn(cmd, log.then(|| self.logger.clone())) {
+                    Ok(process) => PluginResponse::ShellSpawn {
+                        err: "".to_string(),
+                        id: self.shell_processes.borrow_mut().insert(process),
+                    },
+                    Err(err) => PluginResponse::ShellSpawn { err, id: 0 },
+                }
+            }
+            PluginInternalRequest::ShellReadLine { id, timeout } => {
+                let process = self.shell_processes.borrow().get(id);
+                let res = match &process {
+                    Ok(process) => process.read_line(timeout).await,
+                    Err(err) => Err(err.clone()),
+                };
+
+                if res.as_ref().is_err_and(|err| err == "eof")
+                    && process.is_ok_and(|process| process.has_exited())
+                {
+                    self.shell_processes.borrow_mut().remove(id);
+                }
+
+                match res {
+                    Ok(line) => PluginResponse::ShellReadLine {
+                        err: "".to_string(),
+                        line,
+                    },
+                    Err(err) => PluginResponse::ShellReadLine {
+                        err,
+                        line: "".to_string(),
+                    },
+                }
+            }
+            PluginInternalRequest::ShellWrite { id, data } => {
+                let process = self.shell_processes.borrow().get(id);
+                let res = match process {
+                    Ok(process) => process.write(&data).await,
+                    Err(err) => Err(err),
+                };
+
+                PluginResponse::ShellWrite {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::ShellCloseStdin { id } => {
+                let process = self.shell_processes.borrow().get(id);
+                let res = match process {
+                    Ok(process) => {
+                        process.close_stdin().await;
+                        Ok(())
+                    }
+                    Err(err) => Err(err),
+                };
+
+                PluginResponse::ShellCloseStdin {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::ShellKill { id } => {
+                let res = self
+                    .shell_processes
+                    .borrow()
+                    .get(id)
+                    .and_then(|process| process.kill());
+                if res.is_ok() {
+                    self.shell_processes.borrow_mut().remove(id);
+                }
+
+                PluginResponse::ShellKill {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::ShellWait { id } => {
+                let process = self.shell_processes.borrow().get(id);
+                let res = match process {
+                    Ok(process) => process.wait().await,
+                    Err(err) => Err(err),
+                };
+                if res.is_ok() {
+                    self.shell_processes.borrow_mut().remove(id);
+                }
+
+                match res {
+                    Ok(status) => PluginResponse::ShellWait {
+                        err: "".to_string(),
+                        status,
+                    },
+                    Err(err) => PluginResponse::ShellWait { err, status: None },
+                }
+            }
+            PluginInternalRequest::PermissionCheck { .. } => PluginResponse::PermissionCheck,
+            PluginInternalRequest::NetTcpConnect {
+                host,
+                port,
+                timeout,
+            } => match NetConnection::connect(&host, port, timeout).await {
+                Ok(connection) => PluginResponse::NetTcpConnect {
+                    err: "".to_string(),
+                    id: self.net_connections.borrow_mut().insert(connection),
+                },
+                Err(err) => PluginResponse::NetTcpConnect { err, id: 0 },
+            },
+            PluginInternalRequest::NetTcpSend { id, data } => {
+                let connection = self.net_connections.borrow().get(id);
+                let res = match connection {
+                    Ok(connection) => connection.send(&data).await,
+                    Err(err) => Err(err),
+                };
+
+                PluginResponse::NetTcpSend {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::NetTcpRecv { id, size, timeout } => {
+                let connection = self.net_connections.borrow().get(id);
+                let res = match connection {
+                    Ok(connection) => connection.recv(size, timeout).await,
+                    Err(err) => Err(err),
+                };
+
+                match res {
+                    Ok(data) => PluginResponse::NetTcpRecv {
+                        err: "".to_string(),
+                        data,
+                    },
+                    Err(err) => PluginResponse::NetTcpRecv { err, data: vec![] },
+                }
+            }
+            PluginInternalRequest::NetTcpClose { id } => {
+                let res = self.net_connections.borrow_mut().remove(id);
+
+                PluginResponse::NetTcpClose {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::NetUdpSend { host, port, data } => {
+                let res = net::udp_send(&host, port, &data).await;
+
+                PluginResponse::NetUdpSend {
+                    err: res.err().unwrap_or_default(),
+                }
+            }
+            PluginInternalRequest::NetHttpPost {
+                url,
+                body,
+                headers,
+                timeout,
+            } => match net::http_post(&url, &body, &headers, timeout).await {
+                Ok(response) => PluginResponse::NetHttpPost {
+                    err: "".to_string(),
+                    status: Some(response.status),
+                    body: response.body,
+                },
+                Err(err) => PluginResponse::NetHttpPost {
+                    err,
+                    status: None,
+                    body: vec![],
+                },
+            },
         }
     }
 
@@ -250,7 +517,11 @@ impl PluginMethodCall {
             PluginResponse::Log
             | PluginResponse::SerialSend
             | PluginResponse::RttSend
-            | PluginResponse::SysSleep => {}
+            | PluginResponse::SysSleep
+            | PluginResponse::PermissionCheck
+            | PluginResponse::BusPublish
+            | PluginResponse::BusSubscribe
+            | PluginResponse::Permission { .. } => {}
             PluginResponse::ReMatches { pattern } => {
                 table
                     .set(
@@ -311,6 +582,71 @@ impl PluginMethodCall {
                 }
                 table.set("data", message).map_err(|err| err.to_string())?;
             }
+            PluginResponse::StoreGet { err, value } => {
+                Self::set_err(&table, err)?;
+                table
+                    .set(
+                        "value",
+                        store::yaml_to_lua(lua, &value).map_err(|err| err.to_string())?,
+                    )
+                    .map_err(|err| err.to_string())?;
+            }
+            PluginResponse::StoreSet { err } => Self::set_err(&table, err)?,
+            PluginResponse::ShellSpawn { err, id } => {
+                Self::set_err(&table, err)?;
+                table.set("id", id).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::ShellReadLine { err, line } => {
+                Self::set_err(&table, err)?;
+                table.set("line", line).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::ShellWrite { err }
+            | PluginResponse::ShellCloseStdin { err }
+            | PluginResponse::ShellKill { err }
+            | PluginResponse::NetTcpSend { err }
+            | PluginResponse::NetTcpClose { err }
+            | PluginResponse::NetUdpSend { err } => Self::set_err(&table, err)?,
+            PluginResponse::NetTcpConnect { err, id } => {
+                Self::set_err(&table, err)?;
+                table.set("id", id).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::NetTcpRecv { err, data } => {
+                Self::set_err(&table, err)?;
+                table
+                    .set(
+                        "data",
+                        lua.create_string(data).map_err(|err| err.to_string())?,
+                    )
+                    .map_err(|err| err.to_string())?;
+            }
+            PluginResponse::NetHttpPost { err, status, body } => {
+                Self::set_err(&table, err)?;
+                table.set("status", status).map_err(|err| err.to_string())?;
+                table
+                    .set(
+                        "body",
+                        lua.create_string(body).map_err(|err| err.to_string())?,
+                    )
+                    .map_err(|err| err.to_string())?;
+            }
+            PluginResponse::ShellWait { err, status } => {
+                Self::set_err(&table, err)?;
+                table.set("status", status).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::StoreKeys { err, keys } => {
+                Self::set_err(&table, err)?;
+                table.set("keys", keys).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::BufferLen { len } => {
+                table.set("len", len).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::BufferLines { lines } => {
+                table.set("lines", lines).map_err(|err| err.to_string())?;
+            }
+            PluginResponse::BufferSearch { err, lines } => {
+                Self::set_err(&table, err)?;
+                table.set("lines", lines).map_err(|err| err.to_string())?;
+            }
             PluginResponse::RttRead { err, data } => {
                 if err.is_empty() {
                     table
@@ -325,4 +661,13 @@ impl PluginMethodCall {
 
         Ok(table)
     }
+
+    fn set_err(table: &Table, err: String) -> Result<(), String> {
+        if err.is_empty() {
+            table.set("err", Value::Nil)
+        } else {
+            table.set("err", err)
+        }
+        .map_err(|err| err.to_string())
+    }
 }
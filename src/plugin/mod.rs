@@ -1,18 +1,36 @@
+pub mod api;
 pub mod bridge;
+pub mod buffer;
+pub mod codec;
 pub mod engine;
+pub mod errors;
 pub mod messages;
 pub mod method_call;
+pub mod net;
+pub mod permissions;
 pub mod shell;
+pub mod stdlib;
+pub mod store;
+pub mod tags;
+pub mod wasm;
 
 use crate::infra::LogLevel;
 use crate::infra::logger::Logger;
 use crate::plugin::method_call::PluginMethodCallArgs;
+use crate::warning;
 use bridge::PluginMethodCallGate;
 use method_call::PluginMethodCall;
-use mlua::{Function, IntoLuaMulti, Lua, LuaOptions, Table};
+use mlua::{Function, IntoLuaMulti, Lua, LuaOptions, Table, Value};
+use net::SharedNetConnections;
+use permissions::{PluginPermissions, SharedPermissions};
+use regex::Regex;
+use shell::SharedShellProcesses;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::AbortHandle;
 
 pub struct Plugin {
     name: Arc<String>,
@@ -22,8 +40,25 @@ pub struct Plugin {
     index: u128,
     unload_mode: PluginUnloadMode,
     logger: Logger,
+    watched_files: HashMap<PathBuf, Option<SystemTime>>,
+    /// Modules required since the last [`Plugin::has_changed`].
+    required_modules: SharedRequiredModules,
+    commands: Vec<PluginCommand>,
+    hooks: HashSet<String>,
+    tags: Vec<String>,
+    permissions: SharedPermissions,
+    subscriptions: HashSet<String>,
+    shell_processes: SharedShellProcesses,
+    net_connections: SharedNetConnections,
+    /// Queues the calls of a WebAssembly plugin, which share its instance.
+    call_lock: Option<Rc<tokio::sync::Mutex<()>>>,
+    running_calls: HashMap<u64, (Arc<String>, AbortHandle)>,
+    metrics: HashMap<Arc<String>, PluginMetrics>,
+    loaded_at: Instant,
 }
 
+type SharedRequiredModules = Arc<Mutex<Vec<String>>>;
+
 #[derive(Clone, Copy)]
 pub enum PluginUnloadMode {
     None,
@@ -31,23 +66,67 @@ pub enum PluginUnloadMode {
     Reload,
 }
 
+/// A user command exposed by a plugin: a function of the plugin table that
+/// isn't an `on_*` event callback.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PluginCommand {
+    pub name: String,
+    pub args: String,
+    pub doc: Vec<String>,
+}
+
+/// Overhead of a plugin function since the plugin was loaded.
+#[derive(Clone, Debug, Default)]
+pub struct PluginMetrics {
+    pub calls: u64,
+    pub finished: u64,
+    pub errors: u64,
+    pub consecutive_errors: u32,
+    pub lua_time: Duration,
+}
+
+impl PluginMetrics {
+    pub fn avg_lua_time(&self) -> Duration {
+        if self.finished == 0 {
+            return Duration::ZERO;
+        }
+
+        self.lua_time.div_f64(self.finished as f64)
+    }
+}
+
 impl Plugin {
     pub fn new(name: Arc<String>, filepath: PathBuf, logger: Logger) -> Result<Self, String> {
         let lua = Lua::new_with(mlua::StdLib::ALL_SAFE, LuaOptions::default())
             .map_err(|err| err.to_string())?;
-        let plugin_dir = filepath.parent().unwrap_or(Path::new("/"));
-        let code = std::fs::read_to_string(&filepath).map_err(|err| err.to_string())?;
-        lua.load(format!(
-            "package.path = package.path .. ';{}/?.lua'",
-            plugin_dir.to_str().unwrap_or("")
-        ))
-        .exec()
-        .map_err(|err| err.to_string())?;
-        let plugin_table: Table = lua.load(code).eval().map_err(|err| err.to_string())?;
+        let is_wasm = Self::is_wasm(&filepath);
+        let (plugin_table, commands, permissions) = if is_wasm {
+            Self::load_wasm(&lua, &filepath)?
+        } else {
+            Self::load_lua(&lua, &filepath, &logger)?
+        };
+        let hooks = plugin_table
+            .clone()
+            .pairs::<String, Value>()
+            .filter_map(|pair| pair.ok())
+            .filter(|(name, value)| matches!(value, Value::Function(_)) && name.starts_with("on_"))
+            .map(|(name, _)| name)
+            .collect();
         lua.globals()
             .set("M", plugin_table)
             .map_err(|err| err.to_string())?;
 
+        let tag_names = tags::names(&lua);
+        let required_modules = Self::watch_require(&lua).map_err(|err| err.to_string())?;
+        let watched_files = Self::required_files(&lua)
+            .into_iter()
+            .chain([filepath.clone()])
+            .map(|path| {
+                let modified = Self::modified_time(&path);
+                (path, modified)
+            })
+            .collect();
+
         Ok(Self {
             name,
             filepath,
@@ -56,15 +135,328 @@ impl Plugin {
             log_level: LogLevel::Info,
             unload_mode: PluginUnloadMode::None,
             logger,
+            watched_files,
+            required_modules,
+            commands,
+            hooks,
+            tags: tag_names,
+            permissions,
+            subscriptions: HashSet::new(),
+            shell_processes: SharedShellProcesses::default(),
+            net_connections: SharedNetConnections::default(),
+            call_lock: is_wasm.then(Rc::default),
+            running_calls: HashMap::new(),
+            metrics: HashMap::new(),
+            loaded_at: Instant::now(),
         })
     }
 
+    fn is_wasm(filepath: &Path) -> bool {
+        filepath
+            .extension()
+            .is_some_and(|extension| extension == wasm::EXTENSION)
+    }
+
+    fn load_lua<'lua>(
+        lua: &'lua Lua,
+        filepath: &Path,
+        logger: &Logger,
+    ) -> Result<(Table<'lua>, Vec<PluginCommand>, SharedPermissions), String> {
+        let plugin_dir = filepath.parent().unwrap_or(Path::new("/"));
+        let code = std::fs::read_to_string(filepath).map_err(|err| err.to_string())?;
+        stdlib::set_module_dir(lua, plugin_dir).map_err(|err| err.to_string())?;
+        stdlib::register(lua, logger.clone()).map_err(|err| err.to_string())?;
+        api::register(lua).map_err(|err| err.to_string())?;
+        codec::register(lua).map_err(|err| err.to_string())?;
+        tags::register(lua).map_err(|err| err.to_string())?;
+        errors::register(lua).map_err(|err| err.to_string())?;
+        let permissions = Arc::new(Mutex::new(PluginPermissions::parse(&code, plugin_dir)?));
+        PluginPermissions::install_sandbox(lua, permissions.clone())
+            .map_err(|err| err.to_string())?;
+        let chunk = lua
+            .load(&code)
+            .set_name(format!("@{}", filepath.display()))
+            .into_function()
+            .map_err(errors::load_error)?;
+        let plugin_table = errors::eval_chunk(lua, chunk)?;
+        let required_api: Option<String> = plugin_table
+            .get("required_api")
+            .map_err(|_| "\"required_api\" must be a string, like \"1.2\"".to_string())?;
+        if let Some(required_api) = required_api
+            && let Some(warn) = api::check_required(&required_api)?
+        {
+            warning!(logger, "{}", warn);
+        }
+        let commands = Self::parse_commands(&plugin_table, &code);
+
+        Ok((plugin_table, commands, permissions))
+    }
+
+    /// Loads a WebAssembly plugin. Its exports are called through the same
+    /// coroutines as the Lua plugins, so they make the same requests. The
+    /// module can't declare capabilities, so each one is asked on first use.
+    fn load_wasm<'lua>(
+        lua: &'lua Lua,
+        filepath: &Path,
+    ) -> Result<(Table<'lua>, Vec<PluginCommand>, SharedPermissions), String> {
+        errors::register(lua).map_err(|err| err.to_string())?;
+        let plugin_table = wasm::load(lua, filepath)?;
+
+        let mut commands = plugin_table
+            .clone()
+            .pairs::<String, Value>()
+            .filter_map(|pair| pair.ok())
+            .filter(|(name, _)| !name.starts_with("on_"))
+            .map(|(name, _)| PluginCommand {
+                name,
+                args: "...".to_string(),
+                doc: vec![],
+            })
+            .collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok((plugin_table, commands, SharedPermissions::default()))
+    }
+
+    /// Lists the user commands of the plugin table, with the `---` LuaDoc
+    /// comments written right above each function in the plugin source.
+    fn parse_commands(plugin_table: &Table, code: &str) -> Vec<PluginCommand> {
+        let fn_regex = Regex::new(
+            r"^\s*(?:function\s+[\w.]+[.:](\w+)\s*\(([^)]*)\)|[\w.]+\.(\w+)\s*=\s*function\s*\(([^)]*)\))",
+        )
+        .unwrap();
+        let mut sources = HashMap::new();
+        let mut doc = vec![];
+
+        for line in code.lines() {
+            if let Some(doc_line) = line.trim_start().strip_prefix("---") {
+                doc.push(doc_line.trim().to_string());
+                continue;
+            }
+
+            if let Some(captures) = fn_regex.captures(line) {
+                let name = captures.get(1).or(captures.get(3));
+                let args = captures.get(2).or(captures.get(4));
+
+                if let (Some(name), Some(args)) = (name, args) {
+                    let args = args
+                        .as_str()
+                        .split(',')
+                        .map(|arg| arg.trim())
+                        .filter(|arg| !arg.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    sources
+                        .entry(name.as_str().to_string())
+                        .or_insert((args, doc.clone()));
+                }
+            }
+
+            doc.clear();
+        }
+
+        let mut commands = plugin_table
+            .clone()
+            .pairs::<String, Value>()
+            .filter_map(|pair| pair.ok())
+            .filter(|(name, value)| matches!(value, Value::Function(_)) && !name.starts_with("on_"))
+            .map(|(name, _)| {
+                let (args, doc) = sources.remove(&name).unwrap_or_default();
+
+                PluginCommand { name, args, doc }
+            })
+            .collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+
+        commands
+    }
+
+    /// Files of the modules loaded through `require` that come from the
+    /// filesystem. The bundled standard library isn't included here.
+    fn required_files(lua: &Lua) -> Vec<PathBuf> {
+        let Ok(loaded) = lua
+            .globals()
+            .get::<_, Table>("package")
+            .and_then(|package| package.get::<_, Table>("loaded"))
+        else {
+            return vec![];
+        };
+
+        let modules = loaded
+            .pairs::<String, Value>()
+            .filter_map(|pair| pair.ok())
+            .map(|(module, _)| module);
+        Self::module_files(lua, modules)
+    }
+
+    fn module_files(lua: &Lua, modules: impl IntoIterator<Item = String>) -> Vec<PathBuf> {
+        let Ok(package) = lua.globals().get::<_, Table>("package") else {
+            return vec![];
+        };
+        let (Ok(path), Ok(searchpath)) = (
+            stdlib::module_path(lua),
+            package.get::<_, Function>("searchpath"),
+        ) else {
+            return vec![];
+        };
+
+        modules
+            .into_iter()
+            .filter_map(|module| {
+                searchpath
+                    .call::<_, Option<String>>((module, path.clone()))
+                    .ok()
+                    .flatten()
+            })
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    /// Wraps `require` to collect the modules required after the plugin
+    /// loads, like the ones required inside a callback.
+    fn watch_require(lua: &Lua) -> mlua::Result<SharedRequiredModules> {
+        let required_modules = SharedRequiredModules::default();
+        let modules = required_modules.clone();
+        let on_required = lua.create_function(move |_, name: String| {
+            modules
+                .lock()
+                .expect("Cannot get required modules lock")
+                .push(name);
+            Ok(())
+        })?;
+        let require: Function = lua.globals().get("require")?;
+        let wrapper: Function = lua
+            .load(include_str!("require.lua"))
+            .set_name("=require.lua")
+            .call((require, on_required))?;
+        lua.globals().set("require", wrapper)?;
+
+        Ok(required_modules)
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Checks whether the plugin file or one of its required modules changed
+    /// since the last call. Each change is reported only once. Modules
+    /// required since the last call are watched from now on.
+    pub fn has_changed(&mut self) -> bool {
+        let modules = std::mem::take(
+            &mut *self
+                .required_modules
+                .lock()
+                .expect("Cannot get required modules lock"),
+        );
+        for path in Self::module_files(&self.lua, modules) {
+            self.watched_files
+                .entry(path)
+                .or_insert_with_key(|path| Self::modified_time(path));
+        }
+
+        let mut has_changed = false;
+
+        for (path, last_modified) in self.watched_files.iter_mut() {
+            let modified = Self::modified_time(path);
+
+            if modified != *last_modified {
+                *last_modified = modified;
+                has_changed = true;
+            }
+        }
+
+        has_changed
+    }
+
+    /// Compiles the plugin file and its required modules, without running
+    /// them, to catch syntax errors before the plugin is reloaded.
+    pub fn check_syntax(&self) -> Result<(), String> {
+        let lua = Lua::new();
+
+        for path in self.watched_files.keys() {
+            if Self::is_wasm(path) {
+                wasm::check(path)?;
+                continue;
+            }
+
+            let code = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+            lua.load(code)
+                .set_name(format!("@{}", path.display()))
+                .into_function()
+                .map_err(|err| err.to_string())?;
+        }
+
+        Ok(())
+    }
+
     pub fn is_user_command_valid(&self, user_command: &str) -> bool {
         let table: Table = self.lua.globals().get("M").unwrap();
 
         table.get::<_, Function>(user_command).is_ok()
     }
 
+    pub fn commands(&self) -> &[PluginCommand] {
+        &self.commands
+    }
+
+    /// Whether the plugin table defines the `on_*` callback `fn_name`. The
+    /// callbacks are cached when the plugin loads, so the engine can skip
+    /// plugins that don't handle an event without spawning a coroutine.
+    pub fn has_hook(&self, fn_name: &str) -> bool {
+        self.hooks.contains(fn_name)
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Refreshes the tags registered by the plugin, which can change on any
+    /// call. Returns `true` when they changed.
+    pub fn update_tags(&mut self) -> bool {
+        let tag_names = tags::names(&self.lua);
+        if tag_names == self.tags {
+            return false;
+        }
+
+        self.tags = tag_names;
+        true
+    }
+
+    pub fn tag_value(&self, name: &str) -> Result<Vec<u8>, String> {
+        tags::evaluate(&self.lua, name)
+    }
+
+    /// Subscribes to, or unsubscribes from, a bus topic. The `*` topic
+    /// matches every topic.
+    pub fn set_subscription(&mut self, topic: String, enable: bool) {
+        if enable {
+            self.subscriptions.insert(topic);
+        } else {
+            self.subscriptions.remove(&topic);
+        }
+    }
+
+    pub fn is_subscribed(&self, topic: &str) -> bool {
+        self.has_hook("on_event")
+            && (self.subscriptions.contains(topic) || self.subscriptions.contains("*"))
+    }
+
+    pub fn permissions(&self) -> &SharedPermissions {
+        &self.permissions
+    }
+
+    pub fn metrics(&self) -> &HashMap<Arc<String>, PluginMetrics> {
+        &self.metrics
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.loaded_at.elapsed()
+    }
+
     pub fn log_level(&self) -> LogLevel {
         self.log_level
     }
@@ -96,7 +488,7 @@ impl Plugin {
             return;
         }
 
-        PluginMethodCall::spawn(PluginMethodCallArgs {
+        let (id, abort_handle) = PluginMethodCall::spawn(PluginMethodCallArgs {
             plugin_name: self.name.clone(),
             fn_name: fn_name.to_string(),
             index: self.index,
@@ -105,21 +497,144 @@ impl Plugin {
             gate,
             logger: self.logger.clone().with_id(fn_name.to_string()),
             has_unpack,
+            permissions: self.permissions.clone(),
+            shell_processes: self.shell_processes.clone(),
+            net_connections: self.net_connections.clone(),
+            call_lock: self.call_lock.clone(),
         });
 
+        let fn_name = Arc::new(fn_name.to_string());
+        self.metrics.entry(fn_name.clone()).or_default().calls += 1;
+        self.running_calls.insert(id, (fn_name, abort_handle));
         self.index = self.index.overflowing_add_signed(1).0;
     }
+
+    /// Records the end of a call. A hook that fails
+    /// [`errors::MAX_CONSECUTIVE_ERRORS`] times in a row is disabled until
+    /// the plugin is reloaded, and its name is returned.
+    pub fn method_call_finished(
+        &mut self,
+        method_id: u64,
+        lua_time: Duration,
+        failed: bool,
+    ) -> Option<Arc<String>> {
+        let (fn_name, _) = self.running_calls.remove(&method_id)?;
+
+        let metrics = self.metrics.entry(fn_name.clone()).or_default();
+        metrics.finished += 1;
+        metrics.lua_time += lua_time;
+
+        if !failed {
+            metrics.consecutive_errors = 0;
+            return None;
+        }
+
+        metrics.errors += 1;
+        metrics.consecutive_errors += 1;
+
+        if metrics.consecutive_errors >= errors::MAX_CONSECUTIVE_ERRORS
+            && self.hooks.remove(fn_name.as_str())
+        {
+            return Some(fn_name);
+        }
+
+        None
+    }
+
+    /// Aborts the running calls of `fn_name`, or every running call when it's
+    /// `None`. `on_unload` is never cancelled, since the engine waits for it
+    /// to finish the unload. Returns the id and function name of each
+    /// cancelled call.
+    pub fn cancel_method_calls(&mut self, fn_name: Option<&str>) -> Vec<(u64, Arc<String>)> {
+        let ids = self
+            .running_calls
+            .iter()
+            .filter(|(_, (name, _))| {
+                name.as_str() != "on_unload"
+                    && fn_name.is_none_or(|fn_name| fn_name == name.as_str())
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        ids.into_iter()
+            .filter_map(|id| self.running_calls.remove(&id).map(|call| (id, call)))
+            .map(|(id, (name, abort_handle))| {
+                abort_handle.abort();
+                (id, name)
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{path::PathBuf, sync::Arc};
+    use std::{
+        path::{Path, PathBuf},
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
 
     use crate::infra::logger::Logger;
 
-    use super::Plugin;
+    use super::{
+        Plugin, PluginCommand, bridge::PluginEngineGate, errors::MAX_CONSECUTIVE_ERRORS,
+        messages::PluginExternalRequest,
+    };
     use mlua::{Lua, LuaOptions, Table, Value};
 
+    /// Plugin folder of a test, unique to each one and removed on drop.
+    pub(super) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(super) fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = std::env::temp_dir().join(format!(
+                "scope_test_{}_{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::create_dir_all(&path).unwrap();
+
+            Self(path)
+        }
+
+        pub(super) fn path(&self) -> &Path {
+            &self.0
+        }
+
+        /// Writes `contents` to `file_name` and returns its path.
+        pub(super) fn write(&self, file_name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+            let path = self.0.join(file_name);
+            std::fs::write(&path, contents).unwrap();
+
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Loads the plugin of `filepath`, named after the file.
+    pub(super) fn new_test_plugin(filepath: PathBuf) -> Result<Plugin, String> {
+        let name = filepath.file_stem().unwrap().to_string_lossy().to_string();
+
+        Plugin::new(Arc::new(name), filepath, Logger::new("test".to_string()).0)
+    }
+
+    /// Loads `code` as the plugin `<name>.lua` of a new `TempDir`.
+    pub(super) fn load_test_plugin(name: &str, code: &str) -> (Plugin, TempDir) {
+        let dir = TempDir::new();
+        let plugin = new_test_plugin(dir.write(&format!("{}.lua", name), code)).unwrap();
+
+        (plugin, dir)
+    }
+
     fn print_table(lua: Lua) {
         let table: Table = lua.globals().get("M").unwrap();
         let mut keys = vec![];
@@ -162,4 +677,328 @@ mod tests {
             Logger::new("test".to_string()).0,
         );
     }
+
+    #[test]
+    fn test_plugin_new_with_bundled_stdlib() {
+        let (plugin, _dir) = load_test_plugin(
+            "bundled",
+            "local log = require('scope').log\n\
+             local shell = require('shell')\n\
+             local pinned = require('scope.v1')\n\
+             return { log = log, run = shell.run, pinned = pinned.log }",
+        );
+        let table: Table = plugin.lua.globals().get("M").unwrap();
+
+        assert!(table.get::<_, Table>("log").is_ok());
+        assert!(table.get::<_, mlua::Function>("run").is_ok());
+        assert!(table.get::<_, Table>("pinned").is_ok());
+    }
+
+    #[test]
+    fn test_plugin_new_with_user_stdlib() {
+        let dir = TempDir::new();
+        dir.write("scope.lua", "return { custom = true }");
+        let plugin = new_test_plugin(dir.write(
+            "user.lua",
+            "return { custom = require('scope').custom, pinned = require('scope.v1').log }",
+        ))
+        .unwrap();
+        let table: Table = plugin.lua.globals().get("M").unwrap();
+
+        assert!(table.get::<_, bool>("custom").unwrap());
+        assert!(table.get::<_, Table>("pinned").is_ok());
+    }
+
+    #[test]
+    fn test_plugin_watch_required_module() {
+        let dir = TempDir::new();
+        let module_path = dir.write("watch_helper.lua", "return { value = 1 }");
+        let late_path = dir.write("watch_late.lua", "return {}");
+        let mut plugin = new_test_plugin(dir.write(
+            "watch.lua",
+            "return {\n\
+               value = require('watch_helper').value,\n\
+               on_serial_recv = function() require('watch_late') end,\n\
+             }",
+        ))
+        .unwrap();
+
+        assert!(plugin.watched_files.contains_key(&module_path));
+        assert!(!plugin.has_changed());
+
+        plugin.watched_files.insert(module_path.clone(), None);
+        assert!(plugin.has_changed());
+        assert!(!plugin.has_changed());
+        assert!(plugin.check_syntax().is_ok());
+
+        /* Required by a callback, after the plugin loaded. */
+        assert!(!plugin.watched_files.contains_key(&late_path));
+        plugin.lua.load("M.on_serial_recv()").exec().unwrap();
+        assert!(!plugin.has_changed());
+        assert!(plugin.watched_files.contains_key(&late_path));
+        plugin.watched_files.insert(late_path, None);
+        assert!(plugin.has_changed());
+
+        std::fs::write(&module_path, "return { value = }").unwrap();
+        assert!(plugin.check_syntax().is_err());
+    }
+
+    #[test]
+    fn test_plugin_commands_with_doc() {
+        let plugin = Plugin::new(
+            Arc::new("auto_test".to_string()),
+            PathBuf::from("plugins/auto_test.lua"),
+            Logger::new("test".to_string()).0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            plugin.commands(),
+            [
+                PluginCommand {
+                    name: "run_all".to_string(),
+                    args: "".to_string(),
+                    doc: vec!["Run all tests with default parameters".to_string()],
+                },
+                PluginCommand {
+                    name: "test_creg".to_string(),
+                    args: "apn".to_string(),
+                    doc: vec![
+                        "Test CREG comamnd".to_string(),
+                        "@param apn string? The APN to use on CREG command".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plugin_commands_skip_callbacks() {
+        let plugin = Plugin::new(
+            Arc::new("echo".to_string()),
+            PathBuf::from("plugins/echo.lua"),
+            Logger::new("test".to_string()).0,
+        )
+        .unwrap();
+        let names = plugin
+            .commands()
+            .iter()
+            .map(|command| command.name.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(names, ["level"]);
+    }
+
+    #[test]
+    fn test_plugin_cancel_method_calls() {
+        let (mut plugin, _dir) = load_test_plugin(
+            "soak",
+            "local M = {}\n\
+             function M.soak() coroutine.yield({ ':sys.sleep', 60000 }) end\n\
+             function M.idle() coroutine.yield({ ':sys.sleep', 60000 }) end\n\
+             return M",
+        );
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let engine_gate = PluginEngineGate::new(8);
+
+            plugin.spawn_method_call(engine_gate.new_method_call_gate(), "soak", (), false);
+            plugin.spawn_method_call(engine_gate.new_method_call_gate(), "idle", (), false);
+            plugin.spawn_method_call(engine_gate.new_method_call_gate(), "on_unload", (), false);
+            tokio::task::yield_now().await;
+
+            let cancelled = plugin.cancel_method_calls(Some("soak"));
+            assert_eq!(cancelled.len(), 1);
+            assert_eq!(cancelled[0].1.as_str(), "soak");
+
+            let cancelled = plugin.cancel_method_calls(None);
+            assert_eq!(cancelled.len(), 1);
+            assert_eq!(cancelled[0].1.as_str(), "idle");
+
+            assert!(plugin.cancel_method_calls(None).is_empty());
+            assert_eq!(plugin.running_calls.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_plugin_errors_disable_hook() {
+        let dir = TempDir::new();
+        let filepath = dir.write(
+            "broken.lua",
+            "local M = {}\n\
+             function M.on_serial_recv(msg)\n\
+               return msg.missing.field\n\
+             end\n\
+             return M",
+        );
+
+        let (logger, log_receiver) = Logger::new("test".to_string());
+        let mut plugin = Plugin::new(Arc::new("broken".to_string()), filepath, logger).unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let mut engine_gate = PluginEngineGate::new(8);
+
+            for i in 1..=MAX_CONSECUTIVE_ERRORS {
+                plugin.spawn_method_call(
+                    engine_gate.new_method_call_gate(),
+                    "on_serial_recv",
+                    vec![0x41u8],
+                    false,
+                );
+
+                let message = engine_gate.receiver.recv().await.unwrap();
+                let PluginExternalRequest::Finish {
+                    lua_time, failed, ..
+                } = message.data
+                else {
+                    panic!("Expected a Finish request");
+                };
+                assert!(failed);
+
+                let disabled = plugin.method_call_finished(message.method_id, lua_time, failed);
+                assert_eq!(disabled.is_some(), i == MAX_CONSECUTIVE_ERRORS);
+            }
+        });
+
+        assert!(!plugin.has_hook("on_serial_recv"));
+        assert_eq!(
+            plugin.metrics()[&"on_serial_recv".to_string()].errors,
+            MAX_CONSECUTIVE_ERRORS as u64
+        );
+
+        let log = log_receiver.try_recv().unwrap().message;
+        assert!(log.contains("\"on_serial_recv\" failed"));
+        assert!(log.contains("broken.lua:3: attempt to index"));
+        assert!(log.contains("stack traceback:\n"));
+        assert!(log.contains("broken.lua:3: in function"));
+        assert!(!log.contains("[call]"));
+    }
+
+    #[test]
+    fn test_plugin_wasm() {
+        let dir = TempDir::new();
+        let module = r#"
+            (module
+              (import "scope" "request" (func $request (param i32 i32) (result i32)))
+              (memory (export "memory") 1)
+              (data (i32.const 0) "[\":log.info\",\"ready\"]")
+              (func (export "on_serial_recv") (param i32)
+                (drop (call $request (i32.const 0) (i32.const 21))))
+              (func (export "status") (param i32)))
+        "#;
+        let mut plugin =
+            new_test_plugin(dir.write("ready.wasm", wat::parse_str(module).unwrap())).unwrap();
+        assert!(plugin.has_hook("on_serial_recv"));
+        assert!(plugin.is_user_command_valid("status"));
+        assert_eq!(plugin.commands()[0].name, "status");
+        assert!(plugin.check_syntax().is_ok());
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let mut engine_gate = PluginEngineGate::new(8);
+            plugin.spawn_method_call(
+                engine_gate.new_method_call_gate(),
+                "on_serial_recv",
+                vec![0x41u8],
+                false,
+            );
+
+            let message = engine_gate.receiver.recv().await.unwrap();
+            let PluginExternalRequest::Log { message, .. } = message.data else {
+                panic!("Expected a Log request");
+            };
+            assert_eq!(message, "ready");
+        });
+    }
+
+    #[test]
+    fn test_plugin_required_api() {
+        let dir = TempDir::new();
+
+        let newer = dir.write("newer.lua", "return { required_api = '9.0' }");
+        let err = new_test_plugin(newer).err().unwrap();
+        assert!(err.contains("requires API v9.0"));
+
+        let current = dir.write(
+            "current.lua",
+            "local scope = require('scope')\n\
+             assert(scope.has('rtt.read') and not scope.has('rtt.write'))\n\
+             return { required_api = scope.version() }",
+        );
+        assert!(new_test_plugin(current).is_ok());
+    }
+
+    #[test]
+    fn test_plugin_hooks() {
+        let mut plugin = Plugin::new(
+            Arc::new("echo".to_string()),
+            PathBuf::from("plugins/echo.lua"),
+            Logger::new("test".to_string()).0,
+        )
+        .unwrap();
+
+        assert!(plugin.has_hook("on_serial_recv"));
+        assert!(!plugin.has_hook("on_serial_send"));
+        assert!(!plugin.has_hook("level"));
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let engine_gate = PluginEngineGate::new(8);
+            plugin.spawn_method_call(engine_gate.new_method_call_gate(), "level", (), false);
+        });
+
+        let (&method_id, _) = plugin.running_calls.iter().next().unwrap();
+        plugin.method_call_finished(method_id, Duration::from_millis(4), false);
+
+        let metrics = &plugin.metrics()[&"level".to_string()];
+        assert_eq!(metrics.calls, 1);
+        assert_eq!(metrics.finished, 1);
+        assert_eq!(metrics.avg_lua_time(), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_plugin_sandbox_on_top_level() {
+        let dir = TempDir::new();
+        let code = format!(
+            "io.open('{}', 'w'):close()\nreturn {{}}",
+            dir.path().join("out.txt").display()
+        );
+
+        let undeclared = dir.write("undeclared.lua", &code);
+        let err = new_test_plugin(undeclared).err().unwrap();
+        assert!(err.contains("\"fs\" capability not declared"));
+
+        let declared = dir.write("declared.lua", format!("--- @capability fs .\n{}", code));
+        assert!(new_test_plugin(declared).is_ok());
+    }
+
+    #[test]
+    fn test_plugin_subscriptions() {
+        let (mut plugin, _dir) = load_test_plugin(
+            "with_hook",
+            "return { on_event = function(topic, value) end }",
+        );
+
+        assert!(!plugin.is_subscribed("frames"));
+        plugin.set_subscription("frames".to_string(), true);
+        assert!(plugin.is_subscribed("frames"));
+        assert!(!plugin.is_subscribed("tests"));
+        plugin.set_subscription("*".to_string(), true);
+        assert!(plugin.is_subscribed("tests"));
+        plugin.set_subscription("*".to_string(), false);
+        plugin.set_subscription("frames".to_string(), false);
+        assert!(!plugin.is_subscribed("frames"));
+
+        let (mut plugin, _dir) = load_test_plugin("without_hook", "return {}");
+        plugin.set_subscription("*".to_string(), true);
+        assert!(!plugin.is_subscribed("frames"));
+    }
 }
@@ -0,0 +1,361 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        TcpStream, UdpSocket,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::Mutex,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECV_MAX_SIZE: usize = 4096;
+
+/// A TCP connection opened by `net.tcp_connect`. Each half has its own lock,
+/// so a coroutine can send while another one waits on `recv`.
+pub struct NetConnection {
+    reader: Mutex<OwnedReadHalf>,
+    writer: Mutex<OwnedWriteHalf>,
+}
+
+/// The connections opened by a plugin. They're closed when the plugin is
+/// unloaded.
+#[derive(Default)]
+pub struct NetConnections {
+    next_id: u64,
+    connections: HashMap<u64, Rc<NetConnection>>,
+}
+
+pub type SharedNetConnections = Rc<RefCell<NetConnections>>;
+
+/// Response of `net.http_post`.
+#[derive(Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl NetConnections {
+    pub fn insert(&mut self, connection: NetConnection) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.connections.insert(id, Rc::new(connection));
+
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Result<Rc<NetConnection>, String> {
+        self.connections
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Invalid connection id {}", id))
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<(), String> {
+        self.connections
+            .remove(&id)
+            .map(|_| ())
+            .ok_or_else(|| format!("Invalid connection id {}", id))
+    }
+}
+
+impl NetConnection {
+    pub async fn connect(host: &str, port: u16, timeout: Option<Duration>) -> Result<Self, String> {
+        let stream = with_timeout(timeout, TcpStream::connect((host, port))).await?;
+        let _ = stream.set_nodelay(true);
+        let (reader, writer) = stream.into_split();
+
+        Ok(Self {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub async fn send(&self, data: &[u8]) -> Result<(), String> {
+        let mut writer = self.writer.lock().await;
+
+        writer.write_all(data).await.map_err(|err| err.to_string())
+    }
+
+    /// Bytes available on the connection, up to `size`. Waits at most
+    /// `timeout` for them. Returns `eof` error when the peer closed it.
+    pub async fn recv(
+        &self,
+        size: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<u8>, String> {
+        let mut reader = self.reader.lock().await;
+        let mut buf = vec![0; size.unwrap_or(RECV_MAX_SIZE).clamp(1, RECV_MAX_SIZE)];
+
+        let read = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, reader.read(&mut buf))
+                .await
+                .map_err(|_| "timeout".to_string())?,
+            None => reader.read(&mut buf).await,
+        }
+        .map_err(|err| err.to_string())?;
+
+        if read == 0 {
+            return Err("eof".to_string());
+        }
+
+        buf.truncate(read);
+        Ok(buf)
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    future: impl Future<Output = std::io::Result<T>>,
+) -> Result<T, String> {
+    tokio::time::timeout(timeout.unwrap_or(DEFAULT_TIMEOUT), future)
+        .await
+        .map_err(|_| "timeout".to_string())?
+        .map_err(|err| err.to_string())
+}
+
+pub async fn udp_send(host: &str, port: u16, data: &[u8]) -> Result<(), String> {
+    let bind_addr = if host.contains(':') {
+        "[::]:0"
+    } else {
+        "0.0.0.0:0"
+    };
+    let socket = UdpSocket::bind(bind_addr)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    with_timeout(None, socket.send_to(data, (host, port)))
+        .await
+        .map(|_| ())
+}
+
+/// Splits an `http://host[:port]/path` url. HTTPS isn't supported, the
+/// services bridged by the plugins listen on the local network.
+pub fn parse_url(url: &str) -> Result<(String, u16, String), String> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return Err(format!(
+            "Unsupported url \"{}\", only http:// is supported",
+            url
+        ));
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid port on url \"{}\"", url))?,
+        ),
+        _ => (authority, 80),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    if host.is_empty() {
+        return Err(format!("Missing host on url \"{}\"", url));
+    }
+
+    Ok((host.to_string(), port, path.to_string()))
+}
+
+/// Sends a HTTP/1.1 POST and waits the whole response.
+pub async fn http_post(
+    url: &str,
+    body: &[u8],
+    headers: &[(String, String)],
+    timeout: Option<Duration>,
+) -> Result<HttpResponse, String> {
+    let (host, port, path) = parse_url(url)?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        path,
+        host,
+        port,
+        body.len()
+    );
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+    {
+        request.push_str("Content-Type: text/plain\r\n");
+    }
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+
+    let exchange = async {
+        let mut stream = TcpStream::connect((host.as_str(), port)).await?;
+        stream.write_all(request.as_bytes()).await?;
+        stream.write_all(body).await?;
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await?;
+
+        Ok(response)
+    };
+    let response = with_timeout(timeout, exchange).await?;
+
+    parse_response(&response)
+}
+
+fn parse_response(response: &[u8]) -> Result<HttpResponse, String> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| "Incomplete HTTP response".to_string())?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let body = &response[header_end + 4..];
+
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| "Invalid HTTP status line".to_string())?;
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+
+    Ok(HttpResponse { status, body })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = || "Invalid chunked HTTP body".to_string();
+    let mut body = vec![];
+
+    loop {
+        let line_end = data
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(invalid)?;
+        let size = String::from_utf8_lossy(&data[..line_end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+        data = &data[line_end + 2..];
+
+        if size == 0 {
+            return Ok(body);
+        }
+
+        body.extend_from_slice(data.get(..size).ok_or_else(invalid)?);
+        data = data.get(size + 2..).ok_or_else(invalid)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpResponse, NetConnection, http_post, parse_url, udp_send};
+    use std::time::Duration;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, UdpSocket},
+    };
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("http://localhost:8086/write?db=scope").unwrap(),
+            ("localhost".to_string(), 8086, "/write?db=scope".to_string())
+        );
+        assert_eq!(
+            parse_url("http://127.0.0.1").unwrap(),
+            ("127.0.0.1".to_string(), 80, "/".to_string())
+        );
+        assert_eq!(
+            parse_url("http://[::1]:9000/x").unwrap(),
+            ("::1".to_string(), 9000, "/x".to_string())
+        );
+        assert!(parse_url("https://localhost").is_err());
+        assert!(parse_url("http://:80/").is_err());
+    }
+
+    #[test]
+    fn test_tcp_and_udp() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 16];
+                let read = socket.read(&mut buf).await.unwrap();
+                socket.write_all(&buf[..read]).await.unwrap();
+            });
+
+            let connection = NetConnection::connect("127.0.0.1", port, None)
+                .await
+                .unwrap();
+            connection.send(b"ping").await.unwrap();
+            let timeout = Some(Duration::from_secs(5));
+            assert_eq!(connection.recv(None, timeout).await.unwrap(), b"ping");
+            assert_eq!(connection.recv(None, timeout).await, Err("eof".to_string()));
+
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let port = socket.local_addr().unwrap().port();
+            udp_send("127.0.0.1", port, b"cpu value=1").await.unwrap();
+            let mut buf = [0; 32];
+            let read = socket.recv(&mut buf).await.unwrap();
+            assert_eq!(&buf[..read], b"cpu value=1");
+        });
+    }
+
+    #[test]
+    fn test_http_post() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let server = tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 256];
+                while !request.ends_with(b"temp=21") {
+                    let read = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                }
+                socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n")
+                    .await
+                    .unwrap();
+
+                String::from_utf8(request).unwrap()
+            });
+
+            let response = http_post(
+                &format!("http://127.0.0.1:{}/write", port),
+                b"temp=21",
+                &[("X-Device".to_string(), "esp32".to_string())],
+                None,
+            )
+            .await
+            .unwrap();
+            let request = server.await.unwrap();
+
+            assert_eq!(
+                response,
+                HttpResponse {
+                    status: 200,
+                    body: b"ok".to_vec()
+                }
+            );
+            assert!(request.starts_with("POST /write HTTP/1.1\r\n"));
+            assert!(request.contains("Content-Length: 7\r\n"));
+            assert!(request.contains("X-Device: esp32\r\n"));
+        });
+    }
+}
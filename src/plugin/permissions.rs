@@ -0,0 +1,352 @@
+use super::stdlib;
+use mlua::{Lua, Table, Thread};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// What a plugin can do beyond logging and reading the interfaces. Plugins
+/// declare their capabilities on header comments, like:
+///
+/// ```lua
+/// --- @capability shell
+/// --- @capability fs logs
+/// --- @capability send
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Shell,
+    Fs,
+    Send,
+    Network,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::Shell,
+        Capability::Fs,
+        Capability::Send,
+        Capability::Network,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|capability| capability.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Shell => "shell",
+            Capability::Fs => "fs",
+            Capability::Send => "send",
+            Capability::Network => "network",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PermissionState {
+    Allowed,
+    Denied,
+    Undeclared,
+}
+
+pub type SharedPermissions = Arc<Mutex<PluginPermissions>>;
+
+#[derive(Debug, Default)]
+pub struct PluginPermissions {
+    declared: HashMap<Capability, Vec<PathBuf>>,
+    decisions: HashMap<Capability, bool>,
+}
+
+impl PluginPermissions {
+    /// Reads the `@capability` header comments. `fs` may be followed by the
+    /// paths, relative to the plugin folder, the plugin is allowed to use.
+    /// Without paths, every path is allowed.
+    pub fn parse(code: &str, plugin_dir: &Path) -> Result<Self, String> {
+        let capability_regex = Regex::new(r"^\s*---\s*@capability\s+(\w+)(.*)$").unwrap();
+        let mut declared: HashMap<Capability, Vec<PathBuf>> = HashMap::new();
+
+        for captures in code
+            .lines()
+            .filter_map(|line| capability_regex.captures(line))
+        {
+            let name = &captures[1];
+            let Some(capability) = Capability::from_name(name) else {
+                return Err(format!("Unknown capability \"{}\"", name));
+            };
+
+            let paths = declared.entry(capability).or_default();
+            paths.extend(
+                captures[2]
+                    .split_whitespace()
+                    .map(|path| normalize(&plugin_dir.join(path))),
+            );
+        }
+
+        Ok(Self {
+            declared,
+            decisions: HashMap::new(),
+        })
+    }
+
+    /// `detail` is the path for `fs`. It's ignored by the other capabilities.
+    pub fn check(&self, capability: Capability, detail: &str) -> PermissionState {
+        if let Some(&allowed) = self.decisions.get(&capability) {
+            return if allowed {
+                PermissionState::Allowed
+            } else {
+                PermissionState::Denied
+            };
+        }
+
+        let Some(paths) = self.declared.get(&capability) else {
+            return PermissionState::Undeclared;
+        };
+
+        if capability != Capability::Fs || paths.is_empty() {
+            return PermissionState::Allowed;
+        }
+
+        let path = normalize(Path::new(detail));
+        if paths.iter().any(|allowed| path.starts_with(allowed)) {
+            PermissionState::Allowed
+        } else {
+            PermissionState::Undeclared
+        }
+    }
+
+    /// Stores the user answer to an undeclared capability, until the plugin
+    /// is reloaded.
+    pub fn decide(&mut self, capability: Capability, allow: bool) {
+        self.decisions.insert(capability, allow);
+    }
+
+    /// Guards the Lua functions that reach the filesystem or run programs.
+    /// Inside a plugin call they yield a `:permission.check` request, so the
+    /// user can be asked. Outside a coroutine, like on the plugin top level
+    /// code, or on a coroutine created by the plugin, only declared
+    /// capabilities are allowed.
+    ///
+    /// `require` loads the bundled modules and the ones of the plugin folder.
+    /// Any other module needs a declared `fs`, since `require` can't yield.
+    pub fn install_sandbox(lua: &Lua, permissions: SharedPermissions) -> mlua::Result<()> {
+        let call_threads = lua.create_table()?;
+        let weak_keys = lua.create_table()?;
+        weak_keys.set("__mode", "k")?;
+        call_threads.set_metatable(Some(weak_keys));
+        lua.set_named_registry_value(CALL_THREADS, call_threads)?;
+
+        let check = lua.create_function(
+            move |lua, (name, detail, strict): (String, String, bool)| {
+                let capability = Capability::from_name(&name).ok_or_else(|| {
+                    mlua::Error::runtime(format!("Unknown capability \"{}\"", name))
+                })?;
+                let state = permissions
+                    .lock()
+                    .expect("Cannot get plugin permissions lock")
+                    .check(capability, &detail);
+
+                match state {
+                    PermissionState::Allowed => Ok(true),
+                    PermissionState::Undeclared if !strict && is_call_thread(lua)? => Ok(false),
+                    _ => Err(mlua::Error::runtime(format!(
+                        "\"{}\" capability not declared for \"{}\". Add \"--- @capability {}\" to the plugin header",
+                        name, detail, name
+                    ))),
+                }
+            },
+        )?;
+
+        let module_path = stdlib::module_path(lua)?;
+        let temp_dir = std::env::temp_dir().to_string_lossy().to_string();
+        lua.load(include_str!("sandbox.lua"))
+            .set_name("=sandbox.lua")
+            .call::<_, ()>((check, module_path, temp_dir))
+    }
+
+    /// Marks `thread` as the coroutine of a plugin call, whose yields reach
+    /// the engine.
+    pub fn mark_call_thread<'lua>(lua: &'lua Lua, thread: &Thread<'lua>) -> mlua::Result<()> {
+        let call_threads: Option<Table> = lua.named_registry_value(CALL_THREADS)?;
+        match call_threads {
+            Some(call_threads) => call_threads.set(thread.clone(), true),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Registry table with the coroutines of the plugin calls, as weak keys.
+const CALL_THREADS: &str = "scope.call_threads";
+
+fn is_call_thread(lua: &Lua) -> mlua::Result<bool> {
+    let call_threads: Option<Table> = lua.named_registry_value(CALL_THREADS)?;
+    match call_threads {
+        Some(call_threads) => call_threads.contains_key(lua.current_thread()),
+        None => Ok(false),
+    }
+}
+
+/// Makes `path` absolute and resolves its `.` and `..` components without
+/// touching the filesystem, since the file may not exist yet.
+fn normalize(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capability, PermissionState, PluginPermissions};
+    use crate::plugin::stdlib;
+    use mlua::{Lua, LuaOptions, Table, Thread};
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn test_parse_capabilities() {
+        let permissions = PluginPermissions::parse(
+            "--- @capability shell\n---@capability send\nlocal M = {}",
+            Path::new("/plugins"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            permissions.check(Capability::Shell, "ls"),
+            PermissionState::Allowed
+        );
+        assert_eq!(
+            permissions.check(Capability::Send, ""),
+            PermissionState::Allowed
+        );
+        assert_eq!(
+            permissions.check(Capability::Fs, "/tmp/a.txt"),
+            PermissionState::Undeclared
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_capability() {
+        assert!(PluginPermissions::parse("--- @capability root", Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn test_fs_paths() {
+        let permissions =
+            PluginPermissions::parse("--- @capability fs logs ../data", Path::new("/plugins"))
+                .unwrap();
+
+        assert_eq!(
+            permissions.check(Capability::Fs, "/plugins/logs/today.txt"),
+            PermissionState::Allowed
+        );
+        assert_eq!(
+            permissions.check(Capability::Fs, "/data/x.json"),
+            PermissionState::Allowed
+        );
+        assert_eq!(
+            permissions.check(Capability::Fs, "/plugins/logs/../../etc/passwd"),
+            PermissionState::Undeclared
+        );
+    }
+
+    #[test]
+    fn test_decisions() {
+        let mut permissions = PluginPermissions::parse("", Path::new("/")).unwrap();
+
+        permissions.decide(Capability::Shell, true);
+        permissions.decide(Capability::Network, false);
+
+        assert_eq!(
+            permissions.check(Capability::Shell, "ls"),
+            PermissionState::Allowed
+        );
+        assert_eq!(
+            permissions.check(Capability::Network, ""),
+            PermissionState::Denied
+        );
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let lua = Lua::new_with(mlua::StdLib::ALL_SAFE, LuaOptions::default()).unwrap();
+        let permissions =
+            PluginPermissions::parse("--- @capability fs /tmp/scope_sandbox", Path::new("/"))
+                .unwrap();
+        let module_dir = std::env::temp_dir().join("scope_sandbox_modules");
+        let other_dir = std::env::temp_dir().join("scope_sandbox_other");
+        for dir in [&module_dir, &other_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("sandbox_mod.lua"), "return 42").unwrap();
+        }
+        std::fs::write(other_dir.join("sandbox_other.lua"), "return 0").unwrap();
+        stdlib::set_module_dir(&lua, &module_dir).unwrap();
+        PluginPermissions::install_sandbox(&lua, Arc::new(Mutex::new(permissions))).unwrap();
+
+        let required: i64 = lua.load("return require('sandbox_mod')").eval().unwrap();
+        assert_eq!(required, 42);
+
+        lua.load(format!(
+            "package.path = '{}/?.lua'",
+            other_dir.to_str().unwrap()
+        ))
+        .exec()
+        .unwrap();
+        let denied = [
+            "require('sandbox_other')",
+            "package.searchers[2]('sandbox_other')",
+            "io.tmpfile()",
+            "os.tmpname()",
+            "io.open('/etc/scope_sandbox', 'w')",
+            "io.output('/etc/scope_sandbox')",
+            "io.input('/etc/scope_sandbox')",
+            "dofile('/etc/scope_sandbox')",
+            "loadfile('/etc/scope_sandbox')",
+            "os.rename('/tmp/scope_sandbox/a', '/etc/scope_sandbox')",
+        ];
+        for code in denied {
+            let err = lua.load(code).exec().unwrap_err().to_string();
+            assert!(err.contains("capability not declared"), "{}: {}", code, err);
+        }
+        assert!(lua.load("io.output(io.stdout)").exec().is_ok());
+
+        /* Only the call coroutine yields the request, so the plugin can't catch it. */
+        let code = "return coroutine.create(function()
+                      local open = coroutine.wrap(function() return io.open('/etc/scope_sandbox', 'w') end)
+                      open()
+                      return open()
+                    end)";
+        let thread: Thread = lua.load(code).eval().unwrap();
+        PluginPermissions::mark_call_thread(&lua, &thread).unwrap();
+        let err = thread.resume::<_, Table>(()).unwrap_err().to_string();
+        assert!(err.contains("capability not declared"), "{}", err);
+
+        let code =
+            "return coroutine.create(function() return io.open('/etc/scope_sandbox', 'w') end)";
+        let thread: Thread = lua.load(code).eval().unwrap();
+        PluginPermissions::mark_call_thread(&lua, &thread).unwrap();
+        let req: Table = thread.resume(()).unwrap();
+        assert_eq!(req.get::<_, String>(1).unwrap(), ":permission.check");
+        assert_eq!(req.get::<_, String>(2).unwrap(), "fs");
+
+        /* Resumed without a decision, as a coroutine of the plugin would do. */
+        let err = thread.resume::<_, ()>(()).unwrap_err().to_string();
+        assert!(err.contains("capability not declared"), "{}", err);
+    }
+}
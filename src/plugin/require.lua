@@ -0,0 +1,12 @@
+local require, on_required = ...
+
+--- Reports each module loaded for the first time to `Scope`, which watches
+--- the files of the modules required after the plugin loads.
+return function(name)
+  local is_loaded = package.loaded[name] ~= nil
+  local module, data = require(name)
+  if not is_loaded then
+    on_required(name)
+  end
+  return module, data
+end
@@ -0,0 +1,88 @@
+local check, module_path, temp_dir = ...
+
+--- Inside a plugin call, an undeclared capability yields a request so the user
+--- can be asked. `check` only allows the yield from the coroutine of the call,
+--- and the capability is checked again after it, so a coroutine created by the
+--- plugin can't answer the request itself.
+local function permit(capability, detail)
+  detail = tostring(detail)
+  if not check(capability, detail, false) then
+    coroutine.yield({ ":permission.check", capability, detail })
+    check(capability, detail, true)
+  end
+end
+
+local function guard(capability, fn)
+  return function(detail, ...)
+    permit(capability, detail)
+
+    return fn(detail, ...)
+  end
+end
+
+--- Like `guard`, for functions that also take a file handle or nothing, which
+--- don't open a path.
+local function guard_path(fn)
+  return function(path, ...)
+    if path ~= nil and io.type(path) == nil then
+      permit("fs", path)
+    end
+
+    return fn(path, ...)
+  end
+end
+
+--- `require` can't yield, so the modules out of `module_path`, from a
+--- `package.path` set by the plugin, need a declared `fs`. The path found is
+--- the one loaded, whatever `package.path` becomes meanwhile.
+local searchpath = package.searchpath
+local load_file = loadfile
+local function search_lua(name)
+  local path = searchpath(name, module_path)
+  if path == nil then
+    local err
+    path, err = searchpath(name, tostring(package.path))
+    if path == nil then
+      return err
+    end
+    check("fs", path, true)
+  end
+
+  local loader, err = load_file(path)
+  if loader == nil then
+    error(err, 0)
+  end
+  return loader, path
+end
+
+package.searchers = { package.searchers[1], search_lua }
+package.cpath = ""
+package.loadlib = nil
+
+local function guard_temp(fn)
+  return function(...)
+    permit("fs", temp_dir)
+
+    return fn(...)
+  end
+end
+
+io.open = guard("fs", io.open)
+io.lines = guard_path(io.lines)
+io.input = guard_path(io.input)
+io.output = guard_path(io.output)
+io.popen = guard("shell", io.popen)
+dofile = guard_path(dofile)
+loadfile = guard_path(loadfile)
+io.tmpfile = guard_temp(io.tmpfile)
+os.tmpname = guard_temp(os.tmpname)
+os.remove = guard("fs", os.remove)
+os.execute = guard("shell", os.execute)
+
+local rename = os.rename
+os.rename = function(old, new)
+  permit("fs", old)
+  permit("fs", new)
+
+  return rename(old, new)
+end
@@ -1,8 +1,229 @@
-use std::process::Stdio;
-use tokio::process::Command;
+use crate::{
+    info,
+    infra::logger::{LogLevel, Logger},
+    warning,
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    process::Stdio,
+    rc::Rc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::{Mutex, Notify},
+};
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Lines kept for `read_line`. The oldest ones are dropped past it, so the
+/// output of a process that's never read doesn't fill the memory.
+const MAX_QUEUED_LINES: usize = 1024;
 
 pub struct Shell;
 
+/// A process started by `shell.spawn`. Its stdout and stderr lines are
+/// queued to `read_line` and, optionally, forwarded to the Scope log as soon
+/// as they're printed.
+pub struct ShellProcess {
+    child: RefCell<Child>,
+    stdin: Mutex<Option<ChildStdin>>,
+    output: Rc<RefCell<ShellOutput>>,
+    output_notify: Rc<Notify>,
+}
+
+/// Lines printed by the process and not read yet.
+#[derive(Default)]
+struct ShellOutput {
+    lines: VecDeque<String>,
+    /// stdout and stderr, while they aren't closed.
+    open: usize,
+}
+
+/// The processes spawned by a plugin. They're killed when the plugin is
+/// unloaded.
+#[derive(Default)]
+pub struct ShellProcesses {
+    next_id: u64,
+    processes: HashMap<u64, Rc<ShellProcess>>,
+}
+
+pub type SharedShellProcesses = Rc<RefCell<ShellProcesses>>;
+
+impl ShellProcesses {
+    pub fn insert(&mut self, process: ShellProcess) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.processes.insert(id, Rc::new(process));
+
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Result<Rc<ShellProcess>, String> {
+        self.processes
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Invalid process id {}", id))
+    }
+
+    /// Forgets the process, once it's killed, or it exited and it was waited
+    /// or all of its output was read.
+    pub fn remove(&mut self, id: u64) {
+        self.processes.remove(&id);
+    }
+}
+
+impl ShellProcess {
+    pub fn spawn(cmd: String, logger: Option<Logger>) -> Result<Self, String> {
+        let mut command = if cfg!(target_os = "windows") {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+
+        let mut child = command
+            .arg(cmd)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .stdin(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| err.to_string())?;
+
+        let process = Self {
+            stdin: Mutex::new(child.stdin.take()),
+            output: Rc::new(RefCell::new(ShellOutput::default())),
+            output_notify: Rc::new(Notify::new()),
+            child: RefCell::new(child),
+        };
+
+        let mut child = process.child.borrow_mut();
+        if let Some(stdout) = child.stdout.take() {
+            process.forward_lines(stdout, logger.clone(), LogLevel::Info);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            process.forward_lines(stderr, logger, LogLevel::Warning);
+        }
+        drop(child);
+
+        Ok(process)
+    }
+
+    fn forward_lines(
+        &self,
+        output: impl AsyncRead + Unpin + 'static,
+        logger: Option<Logger>,
+        level: LogLevel,
+    ) {
+        let queue = self.output.clone();
+        let notify = self.output_notify.clone();
+        queue.borrow_mut().open += 1;
+
+        tokio::task::spawn_local(async move {
+            let mut lines = BufReader::new(output).lines();
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(logger) = &logger {
+                    match level {
+                        LogLevel::Warning => warning!(logger, "{}", line),
+                        _ => info!(logger, "{}", line),
+                    }
+                }
+
+                let mut queue = queue.borrow_mut();
+                if queue.lines.len() == MAX_QUEUED_LINES {
+                    queue.lines.pop_front();
+                }
+                queue.lines.push_back(line);
+                notify.notify_one();
+            }
+
+            queue.borrow_mut().open -= 1;
+            notify.notify_waiters();
+            notify.notify_one();
+        });
+    }
+
+    /// Next line printed on stdout or stderr. Returns `eof` error when the
+    /// process closed both and every line was read. Only the last
+    /// `MAX_QUEUED_LINES` lines are kept.
+    pub async fn read_line(&self, timeout: Option<Duration>) -> Result<String, String> {
+        let next_line = async {
+            loop {
+                {
+                    let mut output = self.output.borrow_mut();
+                    if let Some(line) = output.lines.pop_front() {
+                        return Ok(line);
+                    }
+                    if output.open == 0 {
+                        return Err("eof".to_string());
+                    }
+                }
+
+                self.output_notify.notified().await;
+            }
+        };
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, next_line)
+                .await
+                .map_err(|_| "timeout".to_string())?,
+            None => next_line.await,
+        }
+    }
+
+    /// Whether the process exited. It isn't waited.
+    pub fn has_exited(&self) -> bool {
+        matches!(self.child.borrow_mut().try_wait(), Ok(Some(_)))
+    }
+
+    pub async fn write(&self, data: &[u8]) -> Result<(), String> {
+        let mut stdin = self.stdin.lock().await;
+        let Some(stdin) = stdin.as_mut() else {
+            return Err("stdin closed".to_string());
+        };
+
+        stdin.write_all(data).await.map_err(|err| err.to_string())?;
+        stdin.flush().await.map_err(|err| err.to_string())
+    }
+
+    /// Closes stdin, so the process reads the end of its input.
+    pub async fn close_stdin(&self) {
+        self.stdin.lock().await.take();
+    }
+
+    pub fn kill(&self) -> Result<(), String> {
+        self.child
+            .borrow_mut()
+            .start_kill()
+            .map_err(|err| err.to_string())
+    }
+
+    /// Waits the process to exit and returns its exit code, which is `None`
+    /// when the process was killed by a signal. Polls the process, so `kill`
+    /// can be called while another coroutine is waiting.
+    pub async fn wait(&self) -> Result<Option<i32>, String> {
+        loop {
+            let status = self
+                .child
+                .borrow_mut()
+                .try_wait()
+                .map_err(|err| err.to_string())?;
+
+            if let Some(status) = status {
+                return Ok(status.code());
+            }
+
+            tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+        }
+    }
+}
+
 impl Shell {
     pub async fn run(cmd: String) -> Result<(String, String), String> {
         let child = if cfg!(target_os = "windows") {
@@ -70,3 +291,90 @@ impl Shell {
         res.success()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_QUEUED_LINES, ShellProcess};
+    use std::time::Duration;
+
+    #[test]
+    fn test_spawn_read_line_and_wait() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let process = ShellProcess::spawn("echo hello".to_string(), None).unwrap();
+
+            assert_eq!(
+                process.read_line(Some(Duration::from_secs(5))).await,
+                Ok("hello".to_string())
+            );
+            assert_eq!(process.wait().await, Ok(Some(0)));
+            assert_eq!(
+                process.read_line(Some(Duration::from_secs(5))).await,
+                Err("eof".to_string())
+            );
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_kill() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let process = ShellProcess::spawn("sleep 60".to_string(), None).unwrap();
+
+            assert_eq!(
+                process.read_line(Some(Duration::from_millis(10))).await,
+                Err("timeout".to_string())
+            );
+            assert!(process.kill().is_ok());
+            assert!(process.wait().await.is_ok());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_drops_oldest_lines() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let process = ShellProcess::spawn("seq 1 3000".to_string(), None).unwrap();
+
+            assert_eq!(process.wait().await, Ok(Some(0)));
+            while process.output.borrow().open > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+
+            let first = 3000 - MAX_QUEUED_LINES + 1;
+            for n in first..=3000 {
+                assert_eq!(process.read_line(None).await, Ok(n.to_string()));
+            }
+            assert_eq!(process.read_line(None).await, Err("eof".to_string()));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_spawn_close_stdin() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let local = tokio::task::LocalSet::new();
+
+        local.block_on(&rt, async {
+            let process = ShellProcess::spawn("cat".to_string(), None).unwrap();
+
+            assert!(process.write(b"hi\n").await.is_ok());
+            process.close_stdin().await;
+
+            assert_eq!(
+                process.read_line(Some(Duration::from_secs(5))).await,
+                Ok("hi".to_string())
+            );
+            assert_eq!(process.wait().await, Ok(Some(0)));
+            assert!(process.write(b"hi\n").await.is_err());
+        });
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{
+    infra::logger::{LogLevel, Logger},
+    warning,
+};
+use mlua::{Function, Lua, Table, Value};
+use std::path::{Path, PathBuf};
+
+/// Version of the bundled Lua standard library. Each bundled module is also
+/// registered as `<name>.v<STDLIB_VERSION>`, which always resolves to the
+/// bundled copy, even when the plugin folder has its own copy of the module.
+pub const STDLIB_VERSION: u32 = 1;
+
+/// Registry value with the templates where `require` looks for modules, the
+/// ones of the plugin folder. `package.path` starts with the same value, but
+/// the plugin can change it.
+const MODULE_PATH: &str = "scope.module_path";
+
+const MODULES: [(&str, &str); 3] = [
+    ("scope", include_str!("../../plugins/scope.lua")),
+    ("shell", include_str!("../../plugins/shell.lua")),
+    ("net", include_str!("../../plugins/net.lua")),
+];
+
+/// Makes `require` look for modules in `plugin_dir` only, besides the
+/// bundled ones.
+pub fn set_module_dir(lua: &Lua, plugin_dir: &Path) -> mlua::Result<()> {
+    let module_path = format!("{}/?.lua", plugin_dir.to_str().unwrap_or(""));
+    let package: Table = lua.globals().get("package")?;
+
+    package.set("path", module_path.as_str())?;
+    lua.set_named_registry_value(MODULE_PATH, module_path)
+}
+
+/// The templates given by `set_module_dir`, or `package.path` without it.
+pub fn module_path(lua: &Lua) -> mlua::Result<String> {
+    match lua.named_registry_value::<Option<String>>(MODULE_PATH)? {
+        Some(module_path) => Ok(module_path),
+        None => lua.globals().get::<_, Table>("package")?.get("path"),
+    }
+}
+
+pub fn register(lua: &Lua, logger: Logger) -> mlua::Result<()> {
+    let package: Table = lua.globals().get("package")?;
+    let preload: Table = package.get("preload")?;
+
+    for (name, code) in MODULES {
+        let bundled = lua.create_function(move |lua, ()| {
+            lua.load(code)
+                .set_name(format!("={}.lua", name))
+                .eval::<Value>()
+        })?;
+
+        let logger = logger.clone();
+        let overridable = lua.create_function(move |lua, ()| {
+            let searchpath: Function = lua.globals().get::<_, Table>("package")?.get("searchpath")?;
+            let user_copy: Option<String> = searchpath.call((name, module_path(lua)?))?;
+
+            let Some(user_copy) = user_copy else {
+                return lua
+                    .load(code)
+                    .set_name(format!("={}.lua", name))
+                    .eval::<Value>();
+            };
+
+            let user_copy = PathBuf::from(user_copy);
+            let user_code = std::fs::read_to_string(&user_copy).map_err(mlua::Error::external)?;
+            if user_code != code {
+                warning!(
+                    logger,
+                    "Using {:?} instead of the bundled \"{}\" module (v{}). Remove it to use the bundled one or require \"{}.v{}\" to pin it",
+                    user_copy,
+                    name,
+                    STDLIB_VERSION,
+                    name,
+                    STDLIB_VERSION
+                );
+            }
+
+            lua.load(user_copy.as_path()).eval::<Value>()
+        })?;
+
+        preload.set(format!("{}.v{}", name, STDLIB_VERSION), bundled)?;
+        preload.set(name, overridable)?;
+    }
+
+    Ok(())
+}
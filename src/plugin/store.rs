@@ -0,0 +1,402 @@
+use mlua::{IntoLua, Lua, Table, Value};
+use serde_yaml::{
+    Mapping, Value as YamlValue,
+    value::{Tag, TaggedValue},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+const MAX_DEPTH: usize = 32;
+const SHARED_NAMESPACE: &str = "shared";
+/// Tag of the strings that aren't valid UTF-8, stored as hex digits.
+const BYTES_TAG: &str = "bytes";
+
+pub type SharedPluginStore = Arc<Mutex<PluginStore>>;
+
+/// Key-value storage of the plugins, persisted as one YAML file per plugin
+/// and a file to the namespace shared by all of them. One store is shared by
+/// the plugin engines of the process, like the ones of the GUI panes, so it
+/// survives plugin reloads and every request is handled in order, even when
+/// sent from concurrent coroutines or engines.
+pub struct PluginStore {
+    dir: Option<PathBuf>,
+    namespaces: HashMap<String, BTreeMap<String, YamlValue>>,
+}
+
+impl PluginStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            namespaces: HashMap::new(),
+        }
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("scope").join("store"))
+    }
+
+    /// The store of the process, on the default directory.
+    pub fn shared() -> SharedPluginStore {
+        static STORE: OnceLock<SharedPluginStore> = OnceLock::new();
+
+        STORE
+            .get_or_init(|| Arc::new(Mutex::new(PluginStore::new(PluginStore::default_dir()))))
+            .clone()
+    }
+
+    pub fn get(&mut self, plugin_name: &str, shared: bool, key: &str) -> Result<YamlValue, String> {
+        let namespace = self.namespace(plugin_name, shared)?;
+
+        Ok(namespace.get(key).cloned().unwrap_or(YamlValue::Null))
+    }
+
+    /// Stores `value` on `key`. A `nil` value removes the key.
+    pub fn set(
+        &mut self,
+        plugin_name: &str,
+        shared: bool,
+        key: String,
+        value: YamlValue,
+    ) -> Result<(), String> {
+        let namespace = self.namespace(plugin_name, shared)?;
+
+        if value.is_null() {
+            namespace.remove(&key);
+        } else {
+            namespace.insert(key, value);
+        }
+
+        self.save(plugin_name, shared)
+    }
+
+    pub fn keys(&mut self, plugin_name: &str, shared: bool) -> Result<Vec<String>, String> {
+        let namespace = self.namespace(plugin_name, shared)?;
+
+        Ok(namespace.keys().cloned().collect())
+    }
+
+    fn file_path(&self, plugin_name: &str, shared: bool) -> Result<PathBuf, String> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| "cannot determine data directory".to_string())?;
+
+        Ok(if shared {
+            dir.join(format!("{}.yml", SHARED_NAMESPACE))
+        } else {
+            dir.join("plugins").join(format!("{}.yml", plugin_name))
+        })
+    }
+
+    fn namespace_name(plugin_name: &str, shared: bool) -> String {
+        if shared {
+            SHARED_NAMESPACE.to_string()
+        } else {
+            format!("plugins/{}", plugin_name)
+        }
+    }
+
+    fn namespace(
+        &mut self,
+        plugin_name: &str,
+        shared: bool,
+    ) -> Result<&mut BTreeMap<String, YamlValue>, String> {
+        let name = Self::namespace_name(plugin_name, shared);
+
+        if !self.namespaces.contains_key(&name) {
+            let file_path = self.file_path(plugin_name, shared)?;
+            let namespace = match std::fs::read_to_string(&file_path) {
+                Ok(content) => serde_yaml::from_str(&content).map_err(|err| {
+                    format!("cannot parse store file {}: {}", file_path.display(), err)
+                })?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(err) => {
+                    return Err(format!(
+                        "cannot read store file {}: {}",
+                        file_path.display(),
+                        err
+                    ));
+                }
+            };
+
+            self.namespaces.insert(name.clone(), namespace);
+        }
+
+        Ok(self.namespaces.get_mut(&name).unwrap())
+    }
+
+    /// Writes the namespace to a temporary file first, so a crash never
+    /// leaves a half written store behind.
+    fn save(&self, plugin_name: &str, shared: bool) -> Result<(), String> {
+        let file_path = self.file_path(plugin_name, shared)?;
+        let Some(namespace) = self
+            .namespaces
+            .get(&Self::namespace_name(plugin_name, shared))
+        else {
+            return Ok(());
+        };
+
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+
+        let content = serde_yaml::to_string(namespace).map_err(|err| err.to_string())?;
+        let tmp_path = file_path.with_extension("yml.tmp");
+        std::fs::write(&tmp_path, content)
+            .and_then(|_| std::fs::rename(&tmp_path, &file_path))
+            .map_err(|err| format!("cannot write store file {}: {}", file_path.display(), err))
+    }
+}
+
+/// Converts a Lua value to be stored. Tables with only the keys `1..n` become
+/// lists; other tables become maps. Strings that aren't valid UTF-8 become
+/// `!bytes` tagged hex digits.
+pub fn lua_to_yaml(value: Value) -> Result<YamlValue, String> {
+    lua_to_yaml_inner(value, 0)
+}
+
+fn lua_to_yaml_inner(value: Value, depth: usize) -> Result<YamlValue, String> {
+    if depth > MAX_DEPTH {
+        return Err("Cannot store tables nested more than 32 levels".to_string());
+    }
+
+    let yaml = match value {
+        Value::Nil => YamlValue::Null,
+        Value::Boolean(b) => YamlValue::Bool(b),
+        Value::Integer(i) => YamlValue::Number(i.into()),
+        Value::Number(n) => YamlValue::Number(n.into()),
+        Value::String(s) => match s.to_str() {
+            Ok(s) => YamlValue::String(s.to_string()),
+            Err(_) => YamlValue::Tagged(Box::new(TaggedValue {
+                tag: Tag::new(BYTES_TAG),
+                value: YamlValue::String(
+                    s.as_bytes().iter().map(|b| format!("{:02x}", b)).collect(),
+                ),
+            })),
+        },
+        Value::Table(table) => table_to_yaml(table, depth)?,
+        value => {
+            return Err(format!(
+                "Cannot store a value of type {}",
+                value.type_name()
+            ));
+        }
+    };
+
+    Ok(yaml)
+}
+
+fn table_to_yaml(table: Table, depth: usize) -> Result<YamlValue, String> {
+    let len = table.raw_len();
+    let pairs = table
+        .clone()
+        .pairs::<Value, Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+
+    if len > 0 && pairs.len() == len {
+        let list = table
+            .sequence_values::<Value>()
+            .map(|value| {
+                value
+                    .map_err(|err| err.to_string())
+                    .and_then(|value| lua_to_yaml_inner(value, depth + 1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        return Ok(YamlValue::Sequence(list));
+    }
+
+    let mut mapping = Mapping::new();
+    for (key, value) in pairs {
+        let key = match key {
+            Value::String(_) | Value::Integer(_) | Value::Number(_) | Value::Boolean(_) => {
+                lua_to_yaml_inner(key, depth + 1)?
+            }
+            key => {
+                return Err(format!(
+                    "Cannot store a table key of type {}",
+                    key.type_name()
+                ));
+            }
+        };
+
+        mapping.insert(key, lua_to_yaml_inner(value, depth + 1)?);
+    }
+
+    Ok(YamlValue::Mapping(mapping))
+}
+
+/// A value converted by [`lua_to_yaml`], to be handed to another Lua state.
+#[derive(Clone, Debug)]
+pub struct LuaData(pub YamlValue);
+
+impl<'lua> IntoLua<'lua> for LuaData {
+    fn into_lua(self, lua: &'lua Lua) -> mlua::Result<Value<'lua>> {
+        yaml_to_lua(lua, &self.0)
+    }
+}
+
+pub fn yaml_to_lua<'a>(lua: &'a Lua, value: &YamlValue) -> mlua::Result<Value<'a>> {
+    let value = match value {
+        YamlValue::Null => Value::Nil,
+        YamlValue::Bool(b) => Value::Boolean(*b),
+        YamlValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or_default()),
+        },
+        YamlValue::String(s) => Value::String(lua.create_string(s)?),
+        YamlValue::Sequence(list) => {
+            let table = lua.create_table()?;
+            for value in list {
+                table.push(yaml_to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+        YamlValue::Mapping(mapping) => {
+            let table = lua.create_table()?;
+            for (key, value) in mapping {
+                table.set(yaml_to_lua(lua, key)?, yaml_to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+        YamlValue::Tagged(tagged) => match (&tagged.value, tagged.tag == BYTES_TAG) {
+            (YamlValue::String(hex), true) => {
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or(""), 16))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| mlua::Error::runtime("Invalid !bytes value on the store"))?;
+                Value::String(lua.create_string(bytes)?)
+            }
+            (value, _) => yaml_to_lua(lua, value)?,
+        },
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PluginStore, lua_to_yaml, yaml_to_lua};
+    use mlua::{Lua, Table, Value};
+    use serde_yaml::Value as YamlValue;
+
+    #[test]
+    fn test_shared_store() {
+        assert!(std::sync::Arc::ptr_eq(
+            &PluginStore::shared(),
+            &PluginStore::shared()
+        ));
+    }
+
+    fn temp_store(name: &str) -> (PluginStore, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        (PluginStore::new(Some(dir.clone())), dir)
+    }
+
+    #[test]
+    fn test_store_persists() {
+        let (mut store, dir) = temp_store("scope_test_store_persists");
+
+        store
+            .set("analytics", false, "recv".to_string(), YamlValue::from(10))
+            .unwrap();
+        store
+            .set(
+                "analytics",
+                true,
+                "port".to_string(),
+                YamlValue::from("COM3"),
+            )
+            .unwrap();
+
+        let mut store = PluginStore::new(Some(dir));
+        assert_eq!(
+            store.get("analytics", false, "recv").unwrap(),
+            YamlValue::from(10)
+        );
+        assert_eq!(store.keys("analytics", false).unwrap(), ["recv"]);
+        assert_eq!(store.keys("analytics", true).unwrap(), ["port"]);
+        assert_eq!(store.keys("echo", false).unwrap(), Vec::<String>::new());
+        assert_eq!(
+            store.get("echo", true, "port").unwrap(),
+            YamlValue::from("COM3")
+        );
+    }
+
+    #[test]
+    fn test_store_set_nil_removes_key() {
+        let (mut store, _dir) = temp_store("scope_test_store_remove");
+
+        store
+            .set("echo", false, "key".to_string(), YamlValue::from(true))
+            .unwrap();
+        store
+            .set("echo", false, "key".to_string(), YamlValue::Null)
+            .unwrap();
+
+        assert_eq!(store.get("echo", false, "key").unwrap(), YamlValue::Null);
+        assert!(store.keys("echo", false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lua_yaml_round_trip() {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load("return { count = 3, ratio = 0.5, name = 'x', list = { 1, 2, 3 }, ok = true }")
+            .eval()
+            .unwrap();
+
+        let yaml = lua_to_yaml(value).unwrap();
+        let table: Table = match yaml_to_lua(&lua, &yaml).unwrap() {
+            Value::Table(table) => table,
+            _ => panic!("Expected a table"),
+        };
+
+        assert_eq!(table.get::<_, i64>("count").unwrap(), 3);
+        assert_eq!(table.get::<_, f64>("ratio").unwrap(), 0.5);
+        assert_eq!(table.get::<_, String>("name").unwrap(), "x");
+        assert_eq!(table.get::<_, Vec<i64>>("list").unwrap(), [1, 2, 3]);
+        assert!(table.get::<_, bool>("ok").unwrap());
+    }
+
+    #[test]
+    fn test_lua_yaml_round_trip_bytes_and_keys() {
+        let lua = Lua::new();
+        let value: Value = lua
+            .load("return { raw = '\\xff\\x00a', [10] = 'ten', [2.5] = true, [false] = 0 }")
+            .eval()
+            .unwrap();
+
+        /* Through the YAML text, as the store saves it. */
+        let text = serde_yaml::to_string(&lua_to_yaml(value).unwrap()).unwrap();
+        let yaml: YamlValue = serde_yaml::from_str(&text).unwrap();
+        let table: Table = match yaml_to_lua(&lua, &yaml).unwrap() {
+            Value::Table(table) => table,
+            _ => panic!("Expected a table"),
+        };
+
+        assert_eq!(
+            table.get::<_, mlua::String>("raw").unwrap().as_bytes(),
+            b"\xff\x00a"
+        );
+        assert_eq!(table.get::<_, String>(10).unwrap(), "ten");
+        assert!(table.get::<_, Option<String>>("10").unwrap().is_none());
+        assert!(table.get::<_, bool>(2.5).unwrap());
+        assert_eq!(table.get::<_, i64>(false).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_lua_to_yaml_rejects_functions() {
+        let lua = Lua::new();
+        let value: Value = lua.load("return function() end").eval().unwrap();
+
+        assert!(lua_to_yaml(value).is_err());
+    }
+}
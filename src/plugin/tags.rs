@@ -0,0 +1,167 @@
+use mlua::{Function, Lua, Table, ThreadStatus, Value};
+
+/// Registry table with the tag functions of the plugin, by tag name.
+const TAGS_REGISTRY: &str = "scope.tags";
+
+/// Makes the native `tags` module available through `require("tags")`.
+/// The functions registered there are called on the plugin engine thread,
+/// outside the plugin calls, every time a message with their tag is sent.
+/// They can't wait for `Scope`, so the APIs that yield a request fail there.
+pub fn register(lua: &Lua) -> mlua::Result<()> {
+    lua.set_named_registry_value(TAGS_REGISTRY, lua.create_table()?)?;
+
+    let package: Table = lua.globals().get("package")?;
+    let preload: Table = package.get("preload")?;
+
+    preload.set("tags", lua.create_function(|lua, ()| module(lua))?)
+}
+
+fn module(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let tags = lua.create_table()?;
+
+    tags.set(
+        "register",
+        lua.create_function(|lua, (name, tag_fn): (String, Function)| {
+            if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '@') {
+                return Err(mlua::Error::runtime(format!(
+                    "Invalid tag name \"{}\"",
+                    name
+                )));
+            }
+
+            lua.named_registry_value::<Table>(TAGS_REGISTRY)?
+                .set(name, tag_fn)
+        })?,
+    )?;
+    tags.set(
+        "unregister",
+        lua.create_function(|lua, name: String| {
+            lua.named_registry_value::<Table>(TAGS_REGISTRY)?
+                .set(name, Value::Nil)
+        })?,
+    )?;
+
+    Ok(tags)
+}
+
+/// Names of the tags registered by the plugin, sorted.
+pub fn names(lua: &Lua) -> Vec<String> {
+    let Ok(registry) = lua.named_registry_value::<Table>(TAGS_REGISTRY) else {
+        return vec![];
+    };
+
+    let mut names = registry
+        .pairs::<String, Function>()
+        .filter_map(|pair| pair.ok())
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    names.sort();
+
+    names
+}
+
+/// Calls the function of the tag `name`. It may return a string, a number or
+/// a table of bytes. The value is sent as is, so strings may hold any byte.
+/// The function runs on its own coroutine, so a request it yields is caught
+/// and reported.
+pub fn evaluate(lua: &Lua, name: &str) -> Result<Vec<u8>, String> {
+    let tag_fn = lua
+        .named_registry_value::<Table>(TAGS_REGISTRY)
+        .and_then(|registry| registry.get::<_, Option<Function>>(name))
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| format!("Tag \"@{}\" not registered", name))?;
+
+    let thread = lua.create_thread(tag_fn).map_err(|err| err.to_string())?;
+    let value = thread
+        .resume::<_, Value>(name)
+        .map_err(|err| format!("Tag \"@{}\" failed: {}", name, err))?;
+    if thread.status() == ThreadStatus::Resumable {
+        let request = match &value {
+            Value::Table(request) => request.get::<_, String>(1).ok(),
+            _ => None,
+        };
+        return Err(format!(
+            "Tag \"@{}\" failed: tag functions can't call yielding APIs{}",
+            name,
+            request
+                .map(|request| format!(", like \"{}\"", request))
+                .unwrap_or_default()
+        ));
+    }
+
+    match value {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Integer(i) => Ok(i.to_string().into_bytes()),
+        Value::Number(n) => Ok(n.to_string().into_bytes()),
+        Value::Table(table) => table
+            .sequence_values::<i64>()
+            .map(|byte| byte.map(|byte| (byte & 0xFF) as u8))
+            .collect::<mlua::Result<Vec<_>>>()
+            .map_err(|err| format!("Tag \"@{}\" returned an invalid table: {}", name, err)),
+        value => Err(format!(
+            "Tag \"@{}\" returned a {} instead of a string",
+            name,
+            value.type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate, names, register};
+    use mlua::Lua;
+
+    #[test]
+    fn test_register_and_evaluate() {
+        let lua = Lua::new();
+        register(&lua).unwrap();
+
+        lua.load(
+            "local tags = require('tags')
+             local seq = 0
+             tags.register('seq', function() seq = seq + 1; return seq end)
+             tags.register('hello', function(name) return name .. '!' end)
+             tags.register('bytes', function() return { 0x4F, 0x4B } end)
+             tags.register('crc', function() return '\\xA5\\x01' end)
+             tags.register('gone', function() return '' end)
+             tags.unregister('gone')",
+        )
+        .exec()
+        .unwrap();
+
+        assert_eq!(names(&lua), ["bytes", "crc", "hello", "seq"]);
+        assert_eq!(evaluate(&lua, "seq").unwrap(), b"1");
+        assert_eq!(evaluate(&lua, "seq").unwrap(), b"2");
+        assert_eq!(evaluate(&lua, "hello").unwrap(), b"hello!");
+        assert_eq!(evaluate(&lua, "bytes").unwrap(), b"OK");
+        assert_eq!(evaluate(&lua, "crc").unwrap(), [0xA5, 0x01]);
+        assert!(evaluate(&lua, "gone").is_err());
+    }
+
+    #[test]
+    fn test_invalid_tag() {
+        let lua = Lua::new();
+        register(&lua).unwrap();
+
+        assert!(
+            lua.load("require('tags').register('two words', function() end)")
+                .exec()
+                .is_err()
+        );
+
+        lua.load("require('tags').register('nil', function() end)")
+            .exec()
+            .unwrap();
+        assert!(evaluate(&lua, "nil").is_err());
+
+        lua.load(
+            "require('tags').register('wait', function() coroutine.yield({ ':sys.sleep', 10 }) end)",
+        )
+        .exec()
+        .unwrap();
+        let err = evaluate(&lua, "wait").unwrap_err();
+        assert!(err.contains("\"@wait\""), "{}", err);
+        assert!(err.contains("can't call yielding APIs"), "{}", err);
+        assert!(err.contains(":sys.sleep"), "{}", err);
+    }
+}
@@ -1,9 +1,10 @@
-coroutine.create(function(t)
-  local err = require("scope").log.error
-  local status, res = pcall(M.{}, table.unpack(t))
+local traceback = ...
+
+return coroutine.create(function(t)
+  local status, res = xpcall(M.{}, traceback, table.unpack(t))
   if not status then
-    err(res:match('%[string ".+"%]:(%d+: .+)') or res)
-  else
-    return res
+    error(res, 0)
   end
+
+  return res
 end)
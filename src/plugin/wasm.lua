@@ -0,0 +1,9 @@
+local start, name = ...
+
+return function(...)
+  local call = start(name, { ... })
+  local req = call:step()
+  while req ~= nil do
+    req = call:step(coroutine.yield(req))
+  end
+end
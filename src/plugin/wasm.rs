@@ -0,0 +1,456 @@
+use super::errors;
+use mlua::{Function, Lua, Table, UserData, UserDataMethods, Value};
+use serde_json::{Map, Value as JsonValue};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use wasmi::{
+    Caller, Config, Engine, Extern, Func, Linker, Module, ResumableCall, ResumableInvocation,
+    Store, Value as WasmValue,
+    core::{HostError, Trap, ValueType},
+};
+
+/// Extension of the WebAssembly plugins. Any other extension is loaded as Lua.
+pub const EXTENSION: &str = "wasm";
+const MAX_DEPTH: usize = 32;
+/// Largest request a guest can make, so its length can't make the host
+/// allocate without bounds.
+const MAX_REQUEST_LEN: usize = 16 * 1024 * 1024;
+/// Fuel of a call until its next request, about one per instruction. A guest
+/// that runs out traps, instead of holding the plugin engine forever.
+const FUEL_PER_STEP: u64 = 100_000_000;
+
+/// Data of the module shared with the host functions.
+#[derive(Default)]
+struct WasmState {
+    /// Arguments of the call, or the response of the last request, copied to
+    /// the guest memory by `scope.read`.
+    payload: Vec<u8>,
+    /// Request made by the guest, waiting to be sent to the engine.
+    request: Option<Vec<u8>>,
+}
+
+/// Host error that suspends the guest until the engine answers its request.
+#[derive(Debug)]
+struct PendingRequest;
+
+impl Display for PendingRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pending request")
+    }
+}
+
+impl HostError for PendingRequest {}
+
+type SharedStore = Arc<Mutex<Store<WasmState>>>;
+
+/// A call of an exported function. Each request of the guest suspends the
+/// call, which is resumed with the response, like a Lua coroutine.
+struct WasmCall {
+    store: SharedStore,
+    func: Func,
+    args: Option<Vec<u8>>,
+    invocation: Option<ResumableInvocation>,
+}
+
+impl UserData for WasmCall {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method_mut("step", |lua, call, rsp: Option<Table>| call.step(lua, rsp));
+    }
+}
+
+impl WasmCall {
+    /// Starts the call, or resumes it with the response `rsp`. Returns the
+    /// next request of the guest, or `nil` when the call finished.
+    fn step<'lua>(
+        &mut self,
+        lua: &'lua Lua,
+        rsp: Option<Table<'lua>>,
+    ) -> mlua::Result<Value<'lua>> {
+        let mut store = self.store.lock().expect("Cannot get wasm store lock");
+        refuel(&mut store).map_err(|err| mlua::Error::runtime(err.to_string()))?;
+
+        let res = match self.args.take() {
+            Some(args) => {
+                let len = payload_len(&args)?;
+                store.data_mut().payload = args;
+                self.func
+                    .call_resumable(&mut *store, &[WasmValue::I32(len)], &mut [])
+            }
+            None => {
+                let Some(invocation) = self.invocation.take() else {
+                    return Ok(Value::Nil);
+                };
+                let rsp = match rsp.map(|rsp| lua_to_json(Value::Table(rsp), 0)) {
+                    Some(Ok(JsonValue::Array(items))) if items.is_empty() => {
+                        JsonValue::Object(Map::new())
+                    }
+                    Some(rsp) => rsp?,
+                    None => JsonValue::Null,
+                };
+                let rsp = serde_json::to_vec(&rsp).map_err(mlua::Error::external)?;
+                let len = payload_len(&rsp)?;
+                store.data_mut().payload = rsp;
+                invocation.resume(&mut *store, &[WasmValue::I32(len)], &mut [])
+            }
+        }
+        .map_err(|err| mlua::Error::runtime(err.to_string()))?;
+
+        let invocation = match res {
+            ResumableCall::Finished => return Ok(Value::Nil),
+            ResumableCall::Resumable(invocation) => invocation,
+        };
+        if invocation
+            .host_error()
+            .downcast_ref::<PendingRequest>()
+            .is_none()
+        {
+            return Err(mlua::Error::runtime(invocation.host_error().to_string()));
+        }
+
+        let request = store.data_mut().request.take().unwrap_or_default();
+        let request: JsonValue = serde_json::from_slice(&request)
+            .map_err(|err| mlua::Error::runtime(format!("Invalid request: {}", err)))?;
+        self.invocation = Some(invocation);
+
+        json_to_lua(lua, &request)
+    }
+}
+
+/// Tops the fuel up to `FUEL_PER_STEP`.
+fn refuel(store: &mut Store<WasmState>) -> Result<(), wasmi::errors::FuelError> {
+    let remaining = store.consume_fuel(0)?;
+    store.add_fuel(FUEL_PER_STEP.saturating_sub(remaining))
+}
+
+fn payload_len(payload: &[u8]) -> mlua::Result<i32> {
+    i32::try_from(payload.len()).map_err(|_| mlua::Error::runtime("Payload too large"))
+}
+
+fn compile(engine: &Engine, filepath: &Path) -> Result<Module, String> {
+    let bytes = std::fs::read(filepath).map_err(|err| err.to_string())?;
+
+    Module::new(engine, &bytes[..]).map_err(|err| err.to_string())
+}
+
+/// Compiles the module, without running it, to catch errors before the
+/// plugin is reloaded.
+pub fn check(filepath: &Path) -> Result<(), String> {
+    compile(&Engine::default(), filepath).map(|_| ())
+}
+
+/// Instantiates the WebAssembly module and returns a plugin table with a
+/// function for each export that takes the length of its arguments, like
+/// `on_serial_recv(len: i32)`.
+///
+/// The guest talks to `Scope` with the same requests as the Lua plugins,
+/// encoded as JSON, through two imports of the `scope` module:
+/// `request(ptr, len) -> i32` sends a request, like `[":log.info", "hi"]`,
+/// and returns the length of the response, and `read(ptr)` copies the call
+/// arguments, or the last response, to `ptr`. Strings that aren't valid UTF-8
+/// are sent as arrays of bytes.
+///
+/// Every call shares the instance, with its memory and shadow stack, so the
+/// engine runs one call of the module at a time. A call that runs about
+/// `FUEL_PER_STEP` instructions without a request fails.
+pub fn load<'lua>(lua: &'lua Lua, filepath: &Path) -> Result<Table<'lua>, String> {
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = compile(&engine, filepath)?;
+    let mut store = Store::new(&engine, WasmState::default());
+    refuel(&mut store).map_err(|err| err.to_string())?;
+    let mut linker = Linker::new(&engine);
+
+    linker
+        .func_wrap("scope", "request", request)
+        .and_then(|linker| linker.func_wrap("scope", "read", read))
+        .map_err(|err| err.to_string())?;
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(|err| err.to_string())?;
+
+    let funcs = instance
+        .exports(&store)
+        .filter_map(|export| {
+            let name = export.name().to_string();
+            export.into_func().map(|func| (name, func))
+        })
+        .filter(|(_, func)| {
+            let ty = func.ty(&store);
+            ty.params() == [ValueType::I32] && ty.results().is_empty()
+        })
+        .collect::<HashMap<_, _>>();
+    let names = funcs.keys().cloned().collect::<Vec<_>>();
+
+    let store = Arc::new(Mutex::new(store));
+    let start = lua
+        .create_function(move |lua, (name, args): (String, Table)| {
+            let func = *funcs
+                .get(&name)
+                .ok_or_else(|| mlua::Error::runtime(format!("Unknown export \"{}\"", name)))?;
+            let args = serde_json::to_vec(&lua_to_json(Value::Table(args), 0)?)
+                .map_err(mlua::Error::external)?;
+
+            lua.create_userdata(WasmCall {
+                store: store.clone(),
+                func,
+                args: Some(args),
+                invocation: None,
+            })
+        })
+        .map_err(|err| err.to_string())?;
+
+    let plugin_table = lua.create_table().map_err(|err| err.to_string())?;
+    for name in names {
+        let plugin_fn: Function = lua
+            .load(include_str!("wasm.lua"))
+            .set_name(errors::CALL_CHUNK_NAME)
+            .call((start.clone(), name.clone()))
+            .map_err(|err| err.to_string())?;
+
+        plugin_table
+            .set(name, plugin_fn)
+            .map_err(|err| err.to_string())?;
+    }
+
+    Ok(plugin_table)
+}
+
+fn guest_memory(caller: &Caller<'_, WasmState>) -> Result<wasmi::Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Trap::new("The module doesn't export its \"memory\""))
+}
+
+fn request(mut caller: Caller<'_, WasmState>, ptr: i32, len: i32) -> Result<i32, Trap> {
+    let memory = guest_memory(&caller)?;
+    let (ptr, len) = (ptr as u32 as usize, len as u32 as usize);
+    if len > MAX_REQUEST_LEN {
+        return Err(Trap::new(format!(
+            "Request of {} bytes, larger than {} bytes",
+            len, MAX_REQUEST_LEN
+        )));
+    }
+    if ptr
+        .checked_add(len)
+        .is_none_or(|end| end > memory.data(&caller).len())
+    {
+        return Err(Trap::new("Request out of the module memory"));
+    }
+
+    let mut request = vec![0; len];
+    memory
+        .read(&caller, ptr, &mut request)
+        .map_err(|err| Trap::new(err.to_string()))?;
+
+    caller.data_mut().request = Some(request);
+
+    Err(Trap::from(PendingRequest))
+}
+
+fn read(mut caller: Caller<'_, WasmState>, ptr: i32) -> Result<(), Trap> {
+    let memory = guest_memory(&caller)?;
+    let payload = std::mem::take(&mut caller.data_mut().payload);
+
+    memory
+        .write(&mut caller, ptr as u32 as usize, &payload)
+        .map_err(|err| Trap::new(err.to_string()))
+}
+
+fn lua_to_json(value: Value, depth: usize) -> mlua::Result<JsonValue> {
+    if depth > MAX_DEPTH {
+        return Err(mlua::Error::runtime(
+            "Cannot send tables nested more than 32 levels",
+        ));
+    }
+
+    let json = match value {
+        Value::Nil => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(b),
+        Value::Integer(i) => JsonValue::from(i),
+        Value::Number(n) => JsonValue::from(n),
+        Value::String(s) => match s.to_str() {
+            Ok(s) => JsonValue::String(s.to_string()),
+            /* Binary data, like the data of `net.tcp_recv`, goes as bytes. */
+            Err(_) => JsonValue::Array(s.as_bytes().iter().map(|&b| JsonValue::from(b)).collect()),
+        },
+        Value::Table(table) => {
+            let pairs = table
+                .clone()
+                .pairs::<Value, Value>()
+                .collect::<mlua::Result<Vec<_>>>()?;
+
+            if pairs.len() == table.raw_len() {
+                JsonValue::Array(
+                    table
+                        .sequence_values::<Value>()
+                        .map(|value| lua_to_json(value?, depth + 1))
+                        .collect::<mlua::Result<_>>()?,
+                )
+            } else {
+                let mut map = Map::new();
+                for (key, value) in pairs {
+                    let key = match key {
+                        Value::String(s) => s.to_string_lossy().to_string(),
+                        Value::Integer(i) => i.to_string(),
+                        key => {
+                            return Err(mlua::Error::runtime(format!(
+                                "Cannot send a table with {} keys",
+                                key.type_name()
+                            )));
+                        }
+                    };
+                    map.insert(key, lua_to_json(value, depth + 1)?);
+                }
+                JsonValue::Object(map)
+            }
+        }
+        value => {
+            return Err(mlua::Error::runtime(format!(
+                "Cannot send a value of type {}",
+                value.type_name()
+            )));
+        }
+    };
+
+    Ok(json)
+}
+
+fn json_to_lua<'lua>(lua: &'lua Lua, value: &JsonValue) -> mlua::Result<Value<'lua>> {
+    let value = match value {
+        JsonValue::Null => Value::Nil,
+        JsonValue::Bool(b) => Value::Boolean(*b),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Number(n.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(s) => Value::String(lua.create_string(s)?),
+        JsonValue::Array(items) => {
+            let table = lua.create_table()?;
+            for item in items {
+                table.push(json_to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        JsonValue::Object(map) => {
+            let table = lua.create_table()?;
+            for (key, value) in map {
+                table.set(key.as_str(), json_to_lua(lua, value)?)?;
+            }
+            Value::Table(table)
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, load};
+    use mlua::{Lua, Table};
+
+    const ECHO_MODULE: &str = r#"
+        (module
+          (import "scope" "request" (func $request (param i32 i32) (result i32)))
+          (import "scope" "read" (func $read (param i32)))
+          (memory (export "memory") 1)
+          (func (export "echo") (param $len i32)
+            (local $rsp i32)
+            (call $read (i32.const 1024))
+            (local.set $rsp (call $request (i32.const 1024) (local.get $len)))
+            (call $read (i32.const 2048))
+            (drop (call $request (i32.const 2048) (local.get $rsp))))
+          (func (export "on_load") (param i32)
+            unreachable)
+          (func (export "helper") (param i32 i32)))
+    "#;
+
+    const FAULTY_MODULE: &str = r#"
+        (module
+          (import "scope" "request" (func $request (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "huge") (param i32)
+            (drop (call $request (i32.const 0) (i32.const 0x7FFFFFFF))))
+          (func (export "outside") (param i32)
+            (drop (call $request (i32.const 65000) (i32.const 1024))))
+          (func (export "spin") (param i32)
+            (loop $forever (br $forever))))
+    "#;
+
+    #[test]
+    fn test_wasm_requests() {
+        let plugin_dir = std::env::temp_dir().join("scope_test_wasm");
+        std::fs::create_dir_all(&plugin_dir).unwrap();
+        let filepath = plugin_dir.join("echo.wasm");
+        std::fs::write(&filepath, wat::parse_str(ECHO_MODULE).unwrap()).unwrap();
+        assert!(check(&filepath).is_ok());
+
+        let lua = Lua::new();
+        let plugin_table = load(&lua, &filepath).unwrap();
+        assert!(plugin_table.contains_key("echo").unwrap());
+        assert!(plugin_table.contains_key("on_load").unwrap());
+        assert!(!plugin_table.contains_key("helper").unwrap());
+        lua.globals().set("M", plugin_table).unwrap();
+
+        let (args, rsp, finished): (Table, Table, bool) = lua
+            .load(
+                "local co = coroutine.create(M.echo)
+                 local _, args = coroutine.resume(co, ':log.info', { 72, 105 })
+                 local _, rsp = coroutine.resume(co, { err = 'timeout', id = 3, data = '\\xA5\\x01' })
+                 coroutine.resume(co, {})
+                 return args, rsp, coroutine.status(co) == 'dead'",
+            )
+            .eval()
+            .unwrap();
+
+        assert_eq!(args.get::<_, String>(1).unwrap(), ":log.info");
+        assert_eq!(args.get::<_, Vec<u8>>(2).unwrap(), b"Hi");
+        assert_eq!(rsp.get::<_, String>("err").unwrap(), "timeout");
+        assert_eq!(rsp.get::<_, i64>("id").unwrap(), 3);
+        let data: Table = rsp.get("data").unwrap();
+        assert_eq!(
+            data.sequence_values()
+                .collect::<mlua::Result<Vec<u8>>>()
+                .unwrap(),
+            [0xA5, 0x01]
+        );
+        assert!(finished);
+
+        let err: String = lua
+            .load("local _, err = pcall(M.on_load) return tostring(err)")
+            .eval()
+            .unwrap();
+        assert!(err.contains("unreachable"));
+    }
+
+    #[test]
+    fn test_wasm_faulty_guest() {
+        let plugin_dir = std::env::temp_dir().join("scope_test_wasm_faulty");
+        std::fs::create_dir_all(&plugin_dir).unwrap();
+        let filepath = plugin_dir.join("faulty.wasm");
+        std::fs::write(&filepath, wat::parse_str(FAULTY_MODULE).unwrap()).unwrap();
+
+        let lua = Lua::new();
+        lua.globals()
+            .set("M", load(&lua, &filepath).unwrap())
+            .unwrap();
+        let err = |name: &str| -> String {
+            lua.load(format!(
+                "local _, err = pcall(M.{}) return tostring(err)",
+                name
+            ))
+            .eval()
+            .unwrap()
+        };
+
+        assert!(err("huge").contains("larger than"));
+        assert!(err("outside").contains("out of the module memory"));
+        assert!(err("spin").contains("fuel"));
+    }
+}